@@ -1,18 +1,54 @@
-use crate::flame::{FlameGraph, SearchPattern};
+use crate::completion::{self, Completion};
+use crate::emit::JsonEmitter;
+use crate::flame::{
+    Butterfly, DuplicateSubtree, FlameGraph, FocusMode, SearchPattern, SearchScope, StackInfo,
+    ROOT_ID,
+};
+use crate::formats::ParseOptions;
+use crate::humanize::CountUnit;
 #[cfg(feature = "python")]
-use crate::py_spy::{record_samples, ProfilerOutput, SamplerState, SamplerStatus};
-use crate::state::FlameGraphState;
+use crate::py_spy::{
+    describe_sampler, get_recording_path, includes_idle, record_samples, ProfilerOutput, Recorder,
+    RecorderMessage, SamplerState, SamplerStatus,
+};
+use crate::state::{
+    ColorScheme, FlameGraphState, LabelTruncation, DEFAULT_GROUP_MIN_SHARE, DEFAULT_GROUP_TOP_K,
+};
 use crate::view::FlameGraphView;
 #[cfg(feature = "python")]
 use remoteprocess;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::error;
+#[cfg(feature = "python")]
+use std::path::PathBuf;
+#[cfg(feature = "python")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "python")]
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 #[cfg(feature = "python")]
 use std::thread;
 use std::time::Duration;
 
+/// Default interval between live flamegraph refreshes.
+pub const DEFAULT_REFRESH_MS: u64 = 250;
+/// Lower bound for the refresh interval to avoid busy-looping the poller.
+pub const MIN_REFRESH_MS: u64 = 50;
+/// Upper bound for the refresh interval when adjusting it at runtime.
+pub const MAX_REFRESH_MS: u64 = 10_000;
+
+/// Default number of live updates frames are marked for after appearing.
+pub const DEFAULT_CHURN_UPDATES: usize = 3;
+
+/// Default interval between ticks, which redraw the UI and swap in live updates.
+pub const DEFAULT_TICK_MS: u64 = 250;
+/// Default interval between ticks once idle, i.e. without input or live updates for a while.
+pub const DEFAULT_IDLE_TICK_MS: u64 = 1000;
+
+/// How often to refresh the memory usage shown in debug mode.
+const RSS_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
@@ -20,6 +56,7 @@ pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 pub enum FlameGraphInput {
     File(String),
     Pid(u64, Option<String>),
+    Stream(String),
 }
 
 #[derive(Debug)]
@@ -28,10 +65,288 @@ pub struct ParsedFlameGraph {
     pub elapsed: Duration,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputKind {
+    /// Replace the search pattern
+    Search,
+    /// Add another highlight pattern
+    Highlight,
+    /// Path of a file to open
+    OpenFile,
+    /// Process to sample instead of the current one
+    #[cfg(feature = "python")]
+    AttachPid,
+}
+
+impl InputKind {
+    pub fn is_path(&self) -> bool {
+        matches!(self, InputKind::OpenFile)
+    }
+}
+
+/// How the text entered in the search prompt is matched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// As a regular expression
+    #[default]
+    Regex,
+    /// As plain text, matching the frame names (or paths) that contain it
+    Text,
+}
+
 #[derive(Debug)]
 pub struct InputBuffer {
     pub buffer: tui_input::Input,
     pub cursor: Option<(u16, u16)>,
+    pub kind: InputKind,
+    pub completion: Option<Completion>,
+    /// What a search entered in the buffer is matched against, toggled with tab
+    pub search_scope: SearchScope,
+    /// How a search entered in the buffer is matched, toggled with ctrl+r
+    pub search_mode: SearchMode,
+    /// Why the search entered so far is not a valid regex, updated while typing
+    pub search_error: Option<String>,
+}
+
+impl InputBuffer {
+    pub fn new(kind: InputKind) -> Self {
+        Self {
+            buffer: tui_input::Input::new("".to_string()),
+            cursor: None,
+            kind,
+            completion: None,
+            search_scope: SearchScope::Frame,
+            search_mode: SearchMode::default(),
+            search_error: None,
+        }
+    }
+
+    pub fn toggle_search_scope(&mut self) {
+        self.search_scope = match self.search_scope {
+            SearchScope::Frame => SearchScope::Path,
+            SearchScope::Path => SearchScope::Frame,
+        };
+    }
+
+    pub fn toggle_search_mode(&mut self) {
+        self.search_mode = match self.search_mode {
+            SearchMode::Regex => SearchMode::Text,
+            SearchMode::Text => SearchMode::Regex,
+        };
+        self.validate_search();
+    }
+
+    /// Check the search entered so far so that an invalid regex shows before submitting it.
+    pub fn validate_search(&mut self) {
+        self.search_error = match (self.kind, self.search_mode) {
+            (InputKind::Search, SearchMode::Regex) => regex::Regex::new(self.buffer.value())
+                .err()
+                .map(|e| describe_regex_error(&e)),
+            _ => None,
+        };
+    }
+
+    /// Complete the buffer as a filesystem path. The first request completes the longest common
+    /// prefix of the candidates, and repeated requests cycle through them.
+    pub fn complete_path(&mut self) {
+        if let Some(completion) = &mut self.completion {
+            let next = completion
+                .selected
+                .map_or(0, |i| (i + 1) % completion.candidates.len());
+            completion.selected = Some(next);
+            self.buffer = tui_input::Input::new(completion.candidates[next].clone());
+            return;
+        }
+        let value = self.buffer.value();
+        let candidates = completion::path_candidates(value);
+        match candidates.len() {
+            0 => {}
+            1 => {
+                self.buffer = tui_input::Input::new(candidates[0].clone());
+            }
+            _ => {
+                let prefix = completion::longest_common_prefix(&candidates);
+                if prefix.len() > value.len() {
+                    self.buffer = tui_input::Input::new(prefix.to_string());
+                }
+                self.completion = Some(Completion {
+                    candidates,
+                    selected: None,
+                });
+            }
+        }
+    }
+}
+
+/// Panel listing duplicated subtrees of the flamegraph
+#[derive(Debug)]
+pub struct DuplicatesPanel {
+    pub entries: Vec<DuplicateSubtree>,
+    pub selected: usize,
+    /// Total count of the flamegraph when the analysis was run
+    pub total_count: u64,
+}
+
+impl DuplicatesPanel {
+    pub fn to_next(&mut self) {
+        self.selected = (self.selected + 1).min(self.entries.len().saturating_sub(1));
+    }
+
+    pub fn to_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// Panel showing the merged callers and callees of a function side by side
+#[derive(Debug)]
+pub struct ButterflyPanel {
+    pub butterfly: Butterfly,
+    /// Number of lines scrolled past in both trees
+    pub offset: usize,
+    /// Total count of the flamegraph when the panel was opened
+    pub total_count: u64,
+}
+
+impl ButterflyPanel {
+    /// Number of lines of the longer of the two trees
+    pub fn num_lines(&self) -> usize {
+        self.butterfly
+            .callers
+            .num_frames()
+            .max(self.butterfly.callees.num_frames())
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.offset = (self.offset + 1).min(self.num_lines().saturating_sub(1));
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.offset = self.offset.saturating_sub(1);
+    }
+}
+
+/// Legend of the top-level frames, e.g. threads or processes when stacks are grouped by them
+#[derive(Debug, Default)]
+pub struct LegendPanel {
+    /// Name of the selected entry, kept by name as the order changes with live updates
+    pub selected: Option<String>,
+}
+
+impl LegendPanel {
+    /// Top-level frames sorted by descending total count
+    pub fn entries(flamegraph: &FlameGraph) -> Vec<&StackInfo> {
+        let mut entries = flamegraph
+            .root()
+            .children
+            .iter()
+            .filter_map(|stack_id| flamegraph.get_stack(stack_id))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            b.total_count.cmp(&a.total_count).then_with(|| {
+                flamegraph
+                    .get_stack_short_name_from_info(a)
+                    .cmp(flamegraph.get_stack_short_name_from_info(b))
+            })
+        });
+        entries
+    }
+
+    /// Index of the selected entry, the first one if it is gone
+    pub fn selected_index(&self, flamegraph: &FlameGraph) -> usize {
+        let Some(selected) = &self.selected else {
+            return 0;
+        };
+        Self::entries(flamegraph)
+            .iter()
+            .position(|stack| flamegraph.get_stack_short_name_from_info(stack) == selected)
+            .unwrap_or(0)
+    }
+
+    pub fn to_next(&mut self, flamegraph: &FlameGraph) {
+        let index = self.selected_index(flamegraph) + 1;
+        self.select_index(flamegraph, index);
+    }
+
+    pub fn to_previous(&mut self, flamegraph: &FlameGraph) {
+        let index = self.selected_index(flamegraph).saturating_sub(1);
+        self.select_index(flamegraph, index);
+    }
+
+    fn select_index(&mut self, flamegraph: &FlameGraph, index: usize) {
+        let entries = Self::entries(flamegraph);
+        if let Some(stack) = entries.get(index.min(entries.len().saturating_sub(1))) {
+            self.selected = Some(flamegraph.get_stack_short_name_from_info(stack).to_string());
+        }
+    }
+}
+
+/// Default limit on the number of frames before narrow frames are hidden automatically
+pub const DEFAULT_MAX_FRAMES: usize = 200_000;
+
+/// Share of the total below which frames are hidden when a flamegraph exceeds the frame limit
+const FRAME_CAP_PRUNE_THRESHOLD: f64 = 0.001;
+
+/// Panel listing the frames on the path from the root to a frame with the cost of each level
+#[derive(Debug)]
+pub struct PathPanel {
+    /// Full name of the frame whose path is listed, kept by name across live updates
+    pub full_name: String,
+    /// Index of the selected entry, the root being 0
+    pub selected: usize,
+}
+
+impl PathPanel {
+    /// Frames from the root down to the one the panel was opened on, or only the root if it is
+    /// gone
+    pub fn entries<'a>(&self, flamegraph: &'a FlameGraph) -> Vec<&'a StackInfo> {
+        let stack_id = flamegraph
+            .get_stack_id_by_full_name(&self.full_name)
+            .unwrap_or(ROOT_ID);
+        let mut entries = flamegraph
+            .get_ancestors(&stack_id)
+            .iter()
+            .filter_map(|stack_id| flamegraph.get_stack(stack_id))
+            .collect::<Vec<_>>();
+        entries.reverse();
+        entries
+    }
+
+    /// Each frame of [`PathPanel::entries`] with its share of the total and the share of it that
+    /// the next level down takes
+    pub fn levels<'a>(&self, flamegraph: &'a FlameGraph) -> Vec<PathLevel<'a>> {
+        let entries = self.entries(flamegraph);
+        let total_count = flamegraph.total_count().max(1);
+        entries
+            .iter()
+            .enumerate()
+            .map(|(i, stack)| PathLevel {
+                stack,
+                share_of_total: stack.total_count as f64 / total_count as f64,
+                next_level_share: entries
+                    .get(i + 1)
+                    .filter(|_| stack.total_count > 0)
+                    .map(|next| next.total_count as f64 / stack.total_count as f64),
+            })
+            .collect()
+    }
+
+    pub fn to_next(&mut self, flamegraph: &FlameGraph) {
+        self.selected = (self.selected + 1).min(self.entries(flamegraph).len().saturating_sub(1));
+    }
+
+    pub fn to_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+}
+
+/// One frame on the path listed by a [`PathPanel`]
+#[derive(Debug)]
+pub struct PathLevel<'a> {
+    pub stack: &'a StackInfo,
+    /// Share of the total count, between 0 and 1
+    pub share_of_total: f64,
+    /// Share of this frame's count taken by the next level down, absent for the last level
+    pub next_level_share: Option<f64>,
 }
 
 /// Application.
@@ -51,10 +366,68 @@ pub struct App {
     pub transient_message: Option<String>,
     /// Debug mode
     pub debug: bool,
+    /// Options used to parse files opened from within the app and live updates, shared with the
+    /// threads parsing the latter
+    parse_options: Arc<Mutex<ParseOptions>>,
+    /// What the counts measure
+    pub count_unit: CountUnit,
+    /// Whether to show counts with SI / binary suffixes instead of exact values
+    pub humanize_counts: bool,
+    /// Path from the root to the selected frame with the cost of each level, if open
+    pub path_panel: Option<PathPanel>,
+    /// How frames are colored
+    pub color_scheme: ColorScheme,
+    /// How frame labels that don't fit are shortened
+    pub label_truncation: LabelTruncation,
+    /// Duplicated subtrees panel, if open
+    pub duplicates_panel: Option<DuplicatesPanel>,
+    /// Callers and callees panel of the selected function, if open
+    pub butterfly_panel: Option<ButterflyPanel>,
+    /// Legend of the top-level frames, if open
+    pub legend_panel: Option<LegendPanel>,
+    /// Whether quitting is waiting for confirmation
+    pub confirm_quit: bool,
+    /// Number of frames above which narrow frames are hidden to keep the UI responsive
+    pub max_frames: Option<usize>,
+    /// Interval in milliseconds between ticks once idle, or `None` to always tick at full rate
+    pub idle_tick_rate: Option<u64>,
+    /// How the search prompt matches by default
+    pub default_search_mode: SearchMode,
+    /// Number of live updates frames are marked for after appearing, when enabled
+    pub churn_updates: usize,
+    /// Number of widest children of each parent kept when siblings are grouped
+    pub group_top_k: usize,
+    /// Share of the parent below which children are grouped
+    pub group_min_share: f64,
+    /// Summaries of live updates written for external monitoring, see `--emit-json`
+    pub json_emitter: Option<JsonEmitter>,
+    /// Whether narrow frames were hidden because of the frame limit, so that it is only done once
+    frame_cap_applied: bool,
+    /// Resident memory of this process, refreshed periodically in debug mode
+    pub rss_bytes: Option<u64>,
+    rss_updated_at: Option<std::time::Instant>,
     /// Next flamegraph to swap in
     next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>>,
+    /// Error from a background thread loading flamegraphs, reported on tick
+    next_error: Arc<Mutex<Option<String>>>,
     #[cfg(feature = "python")]
     sampler_state: Option<Arc<Mutex<SamplerState>>>,
+    /// Interval in ms between live refreshes, shared with the poller and sampler threads
+    #[cfg(feature = "python")]
+    refresh_interval_ms: Arc<AtomicU64>,
+    /// Additional arguments passed to py-spy, kept for attaching to another process
+    #[cfg(feature = "python")]
+    py_spy_args: Option<String>,
+    /// Recording of the raw samples to disk
+    #[cfg(feature = "python")]
+    recorder: Option<Recorder>,
+    /// File given to record to, which the recordings of processes attached to later are named
+    /// after
+    #[cfg(feature = "python")]
+    record_path: Option<PathBuf>,
+    /// Whether the sampler failed, ending the live session
+    #[cfg(feature = "python")]
+    sampler_failed: bool,
     pub log_messages: VecDeque<String>,
     pub show_log_panel: bool,
     pub has_log_channel: bool,
@@ -71,6 +444,7 @@ pub struct App {
 impl App {
     /// Constructs a new instance of [`App`].
     pub fn with_flamegraph(filename: &str, flamegraph: FlameGraph) -> Self {
+        let parse_options = Arc::new(Mutex::new(ParseOptions::default()));
         Self {
             running: true,
             flamegraph_view: FlameGraphView::new(flamegraph),
@@ -79,9 +453,40 @@ impl App {
             elapsed: HashMap::new(),
             transient_message: None,
             debug: false,
+            parse_options: parse_options.clone(),
+            count_unit: CountUnit::default(),
+            humanize_counts: true,
+            path_panel: None,
+            color_scheme: ColorScheme::default(),
+            label_truncation: LabelTruncation::default(),
+            duplicates_panel: None,
+            butterfly_panel: None,
+            legend_panel: None,
+            confirm_quit: false,
+            max_frames: Some(DEFAULT_MAX_FRAMES),
+            idle_tick_rate: Some(DEFAULT_IDLE_TICK_MS),
+            default_search_mode: SearchMode::default(),
+            churn_updates: DEFAULT_CHURN_UPDATES,
+            group_top_k: DEFAULT_GROUP_TOP_K,
+            group_min_share: DEFAULT_GROUP_MIN_SHARE,
+            json_emitter: None,
+            frame_cap_applied: false,
+            rss_bytes: None,
+            rss_updated_at: None,
             next_flamegraph: Arc::new(Mutex::new(None)),
+            next_error: Arc::new(Mutex::new(None)),
             #[cfg(feature = "python")]
             sampler_state: None,
+            #[cfg(feature = "python")]
+            refresh_interval_ms: Arc::new(AtomicU64::new(DEFAULT_REFRESH_MS)),
+            #[cfg(feature = "python")]
+            py_spy_args: None,
+            #[cfg(feature = "python")]
+            recorder: None,
+            #[cfg(feature = "python")]
+            record_path: None,
+            #[cfg(feature = "python")]
+            sampler_failed: false,
             log_messages: VecDeque::new(),
             show_log_panel: false,
             has_log_channel: false,
@@ -96,27 +501,143 @@ impl App {
         }
     }
 
+    /// Constructs a new instance of [`App`] whose flamegraph is fed by a live stream.
+    pub fn with_live_stream(title: &str) -> Self {
+        let flamegraph = FlameGraph::from_string(String::new(), true);
+        let mut app = Self::with_flamegraph(title, flamegraph);
+        app.flamegraph_input = FlameGraphInput::Stream(title.to_string());
+        app.set_parse_options(ParseOptions {
+            sorted: true,
+            ..ParseOptions::default()
+        });
+        app
+    }
+
     #[cfg(feature = "python")]
-    pub fn with_pid(pid: u64, py_spy_args: Option<String>) -> Self {
+    pub fn with_pid(
+        pid: u64,
+        py_spy_args: Option<String>,
+        refresh_ms: u64,
+        recorder: Option<Recorder>,
+    ) -> Self {
         let next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>> = Arc::new(Mutex::new(None));
+        let sampler_state = Arc::new(Mutex::new(SamplerState::new(includes_idle(
+            py_spy_args.as_deref(),
+        ))));
+        let refresh_interval_ms = Arc::new(AtomicU64::new(refresh_ms.max(MIN_REFRESH_MS)));
+        // Sorted by default so that frames don't move around between updates
+        let parse_options = Arc::new(Mutex::new(ParseOptions {
+            sorted: true,
+            ..ParseOptions::default()
+        }));
+        let sampler_description = Self::spawn_sampler(
+            pid,
+            py_spy_args.clone(),
+            next_flamegraph.clone(),
+            parse_options.clone(),
+            sampler_state.clone(),
+            refresh_interval_ms.clone(),
+            recorder.as_ref().map(|r| r.sender()),
+        );
+
+        let flamegraph = FlameGraph::from_string("".to_string(), true);
+        let process_info = Self::get_process_info(pid);
+        let mut app = Self::with_flamegraph("", flamegraph);
+        app.flamegraph_input = FlameGraphInput::Pid(pid, process_info);
+        app.next_flamegraph = next_flamegraph;
+        app.parse_options = parse_options;
+        app.sampler_state = Some(sampler_state);
+        app.refresh_interval_ms = refresh_interval_ms;
+        app.py_spy_args = py_spy_args;
+        app.record_path = recorder.as_ref().map(|r| r.path().to_path_buf());
+        app.recorder = recorder;
+        if let Some(description) = sampler_description {
+            app.log_message(format!("INFO {}", description));
+        }
+        app
+    }
+
+    /// Start the py-spy sampler thread for the process and the thread polling its output into
+    /// `next_flamegraph`. Both exit once a stop is requested through `sampler_state`.
+    ///
+    /// Returns a description of the sampler for the log, or `None` if the arguments are invalid,
+    /// in which case the error is reported through `sampler_state` as for a failed attach.
+    #[cfg(feature = "python")]
+    fn spawn_sampler(
+        pid: u64,
+        py_spy_args: Option<String>,
+        next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>>,
+        parse_options: Arc<Mutex<ParseOptions>>,
+        sampler_state: Arc<Mutex<SamplerState>>,
+        refresh_interval_ms: Arc<AtomicU64>,
+        recorder_sender: Option<mpsc::Sender<RecorderMessage>>,
+    ) -> Option<String> {
+        // Note: mimic a record command's invocation vs simply getting default Config as
+        // from_args does a lot of heavy lifting
+        let mut args = [
+            "py-spy",
+            "record",
+            "--pid",
+            pid.to_string().as_str(),
+            "--format",
+            "raw",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<String>>();
+        if let Some(py_spy_args) = &py_spy_args {
+            args.extend(py_spy_args.split_whitespace().map(|s| s.to_string()));
+        }
+        let config = match py_spy::Config::from_args(&args) {
+            Ok(config) => config,
+            Err(e) => {
+                // Only the first line, without the usage that follows it
+                let error = e.to_string();
+                let error = error.lines().next().unwrap_or_default();
+                let message = format!(
+                    "Invalid --py-spy-args {:?}: {}",
+                    py_spy_args.unwrap_or_default(),
+                    error.trim_start_matches("error: ")
+                );
+                sampler_state
+                    .lock()
+                    .unwrap()
+                    .set_status(SamplerStatus::Error(message));
+                return None;
+            }
+        };
+        // The parsed config has the final say, e.g. on combined short flags
+        sampler_state.lock().unwrap().include_idle = config.include_idle;
+        let description = describe_sampler(&config, &args[1..]);
+
         let pyspy_data: Arc<Mutex<Option<ProfilerOutput>>> = Arc::new(Mutex::new(None));
-        let sampler_state = Arc::new(Mutex::new(SamplerState::default()));
 
         // Thread to poll data from pyspy and construct the next flamegraph
         {
             let next_flamegraph = next_flamegraph.clone();
             let pyspy_data = pyspy_data.clone();
+            let refresh_interval_ms = refresh_interval_ms.clone();
+            let sampler_state = sampler_state.clone();
             let _handle = thread::spawn(move || loop {
+                if sampler_state.lock().unwrap().stop_requested {
+                    break;
+                }
                 if let Some(output) = pyspy_data.lock().unwrap().take() {
+                    if let Some(sender) = &recorder_sender {
+                        let _ = sender.send(RecorderMessage::Data(output.data.clone()));
+                    }
+                    let options = parse_options.lock().unwrap().clone();
                     let tic = std::time::Instant::now();
-                    let flamegraph = FlameGraph::from_string(output.data, true);
+                    let flamegraph = FlameGraph::from_string_with_options(output.data, &options);
                     let parsed = ParsedFlameGraph {
                         flamegraph,
                         elapsed: tic.elapsed(),
                     };
                     *next_flamegraph.lock().unwrap() = Some(parsed);
                 }
-                thread::sleep(std::time::Duration::from_millis(250));
+                thread::sleep(std::time::Duration::from_millis(
+                    refresh_interval_ms.load(Ordering::Relaxed),
+                ));
             });
         }
 
@@ -124,86 +645,199 @@ impl App {
         {
             let pyspy_data = pyspy_data.clone();
             let sampler_state = sampler_state.clone();
+            let refresh_interval_ms = refresh_interval_ms.clone();
             let _handle = thread::spawn(move || {
-                // Note: mimic a record command's invocation vs simply getting default Config as
-                // from_args does a lot of heavy lifting
-                let mut args = [
-                    "py-spy",
-                    "record",
-                    "--pid",
-                    pid.to_string().as_str(),
-                    "--format",
-                    "raw",
-                ]
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>();
-                if let Some(py_spy_args) = py_spy_args {
-                    args.extend(py_spy_args.split_whitespace().map(|s| s.to_string()));
-                }
-                let config = py_spy::Config::from_args(&args).unwrap();
                 let pid = pid as remoteprocess::Pid;
-                record_samples(pid, &config, pyspy_data, sampler_state);
+                record_samples(pid, &config, pyspy_data, sampler_state, refresh_interval_ms);
             });
         }
+        Some(description)
+    }
 
-        let flamegraph = FlameGraph::from_string("".to_string(), true);
-        let process_info = remoteprocess::Process::new(pid as remoteprocess::Pid)
+    #[cfg(feature = "python")]
+    fn get_process_info(pid: u64) -> Option<String> {
+        remoteprocess::Process::new(pid as remoteprocess::Pid)
             .and_then(|p| p.cmdline())
             .ok()
-            .map(|c| c.join(" "));
-        Self {
-            running: true,
-            flamegraph_view: FlameGraphView::new(flamegraph),
-            flamegraph_input: FlameGraphInput::Pid(pid, process_info),
-            next_flamegraph: next_flamegraph.clone(),
-            input_buffer: None,
-            elapsed: HashMap::new(),
-            transient_message: None,
-            debug: false,
-            sampler_state: Some(sampler_state),
-            log_messages: VecDeque::new(),
-            show_log_panel: false,
-            has_log_channel: false,
-            log_scroll_offset: 0,
-            log_auto_scroll: true,
-            log_search_pattern: None,
-            log_search_text: None,
-            log_input_buffer: None,
-            log_max_capacity: 1000,
-            log_current_match_line: None,
-            log_visible_lines: 8,
-        }
+            .map(|c| c.join(" "))
     }
 
     /// Handles the tick event of the terminal.
     pub fn tick(&mut self) {
         // Replace flamegraph
         if !self.flamegraph_view.state.freeze {
-            if let Some(parsed) = self.next_flamegraph.lock().unwrap().take() {
-                self.elapsed
-                    .insert("flamegraph".to_string(), parsed.elapsed);
-                let tic = std::time::Instant::now();
-                self.flamegraph_view.replace_flamegraph(parsed.flamegraph);
-                self.elapsed
-                    .insert("replacement".to_string(), tic.elapsed());
-            }
+            self.swap_in_next_flamegraph();
+        }
+
+        // Keep what was loaded so far and let the error be dismissed like other messages
+        let next_error = self.next_error.lock().unwrap().take();
+        if let Some(error) = next_error {
+            self.log_message(format!("ERROR {}", error));
+            self.set_transient_message(&error);
+        }
+
+        let emit_error = self.json_emitter.as_mut().and_then(|e| e.take_error());
+        if let Some(error) = emit_error {
+            let message = format!("Stopped emitting JSON updates: {}", error);
+            self.log_message(format!("ERROR {}", message));
+            self.set_transient_message(&message);
         }
 
-        // Exit if fatal error in sampler
+        // Memory usage is only shown in debug mode. Throttle the query so that it doesn't skew
+        // the timings displayed next to it.
+        if self.debug
+            && self
+                .rss_updated_at
+                .is_none_or(|t| t.elapsed() >= RSS_UPDATE_INTERVAL)
+        {
+            self.rss_bytes = get_rss_bytes();
+            self.rss_updated_at = Some(std::time::Instant::now());
+        }
+
+        // Report a fatal error in the sampler once and keep running with what was sampled so far
         #[cfg(feature = "python")]
-        if let Some(SamplerStatus::Error(s)) = self
-            .sampler_state
-            .as_ref()
-            .map(|s| s.lock().unwrap().status.clone())
+        if !self.sampler_failed {
+            if let Some(SamplerStatus::Error(s)) = self
+                .sampler_state
+                .as_ref()
+                .map(|s| s.lock().unwrap().status.clone())
+            {
+                self.sampler_failed = true;
+                self.log_message(format!("ERROR {}", s));
+                self.set_transient_message(&format!(
+                    "{}. Press 'o' to open a file or 'A' to attach to another process",
+                    s
+                ));
+            }
+        }
+    }
+
+    fn swap_in_next_flamegraph(&mut self) {
+        let next = self.next_flamegraph.lock().unwrap().take();
+        if let Some(mut parsed) = next {
+            // Parsed before the setting was last toggled
+            let collapse = self.flamegraph_state().collapse_common_prefix;
+            if parsed.flamegraph.collapses_common_prefix() != collapse {
+                parsed.flamegraph = parsed.flamegraph.with_collapsed_common_prefix(collapse);
+            }
+            self.replace_flamegraph(parsed);
+            if let Some(emitter) = self.json_emitter.as_mut() {
+                emitter.emit(&self.flamegraph_view.flamegraph);
+            }
+        }
+    }
+
+    /// Swap in a flamegraph parsed outside of the app, e.g. by a live stream parsing thread.
+    pub fn replace_flamegraph(&mut self, parsed: ParsedFlameGraph) {
+        self.elapsed
+            .insert("flamegraph".to_string(), parsed.elapsed);
+        let tic = std::time::Instant::now();
+        self.flamegraph_view.replace_flamegraph(parsed.flamegraph);
+        self.apply_frame_cap();
+        self.elapsed
+            .insert("replacement".to_string(), tic.elapsed());
+    }
+
+    /// Hide narrow frames if the flamegraph has more frames than the limit, as laying out that
+    /// many frames makes the UI stall. Only done once so that it can be undone.
+    pub fn apply_frame_cap(&mut self) {
+        let Some(max_frames) = self.max_frames else {
+            return;
+        };
+        let num_frames = self.flamegraph().num_stacks();
+        if self.frame_cap_applied
+            || num_frames <= max_frames
+            || self.flamegraph_state().prune_threshold.is_some()
         {
-            panic!("py-spy sampler exited with error: {}\n\nYou likely need to rerun this program with sudo.", s);
+            return;
+        }
+        self.frame_cap_applied = true;
+        self.flamegraph_view.state.prune_threshold = Some(FRAME_CAP_PRUNE_THRESHOLD);
+        let hidden = self
+            .flamegraph()
+            .count_stacks_narrower_than(FRAME_CAP_PRUNE_THRESHOLD);
+        self.log_message(format!(
+            "WARN The flamegraph has {} frames, more than the limit of {}. Hiding {} frames \
+             narrower than {}% of the total to keep the UI responsive; press 'p' to show them or \
+             rerun with --max-frames 0",
+            num_frames,
+            max_frames,
+            hidden,
+            FRAME_CAP_PRUNE_THRESHOLD * 100.0
+        ));
+        self.set_transient_message(&format!(
+            "Large flamegraph: hid {} frames narrower than {}% (press 'p' to show them)",
+            hidden,
+            FRAME_CAP_PRUNE_THRESHOLD * 100.0
+        ));
+    }
+
+    /// Slot that background threads fill with the next flamegraph to be swapped in on tick.
+    pub fn next_flamegraph_slot(&self) -> Arc<Mutex<Option<ParsedFlameGraph>>> {
+        self.next_flamegraph.clone()
+    }
+
+    /// Options used to parse files opened from within the app and live updates.
+    pub fn parse_options(&self) -> ParseOptions {
+        self.parse_options.lock().unwrap().clone()
+    }
+
+    /// Replace the parse options, including those of the threads parsing live updates.
+    pub fn set_parse_options(&mut self, options: ParseOptions) {
+        *self.parse_options.lock().unwrap() = options;
+    }
+
+    /// Options that background threads parse live updates with, as changed at runtime.
+    pub fn parse_options_slot(&self) -> Arc<Mutex<ParseOptions>> {
+        self.parse_options.clone()
+    }
+
+    /// Slot that background threads fill with an error to be reported on tick.
+    pub fn next_error_slot(&self) -> Arc<Mutex<Option<String>>> {
+        self.next_error.clone()
+    }
+
+    /// Whether the flamegraph is continuously updated from a live source.
+    pub fn is_live(&self) -> bool {
+        match self.flamegraph_input {
+            FlameGraphInput::File(_) => false,
+            #[cfg(feature = "python")]
+            FlameGraphInput::Pid(_, _) => !self.sampler_failed,
+            #[cfg(not(feature = "python"))]
+            FlameGraphInput::Pid(_, _) => true,
+            FlameGraphInput::Stream(_) => true,
+        }
+    }
+
+    /// Freeze or unfreeze live updates. On unfreeze, the latest sampled flamegraph is swapped in
+    /// right away instead of waiting for the next tick.
+    pub fn toggle_freeze(&mut self) {
+        self.flamegraph_view.state.toggle_freeze();
+        if !self.flamegraph_view.state.freeze {
+            self.swap_in_next_flamegraph();
+        }
+    }
+
+    /// Quit the application, or ask for confirmation first during a live session as its samples
+    /// would be lost.
+    pub fn request_quit(&mut self) {
+        if self.is_live() {
+            self.confirm_quit = true;
+        } else {
+            self.quit();
         }
     }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
+        if let Some(emitter) = self.json_emitter.as_mut() {
+            emitter.finish();
+        }
+        #[cfg(feature = "python")]
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.finish();
+        }
     }
 
     pub fn flamegraph(&self) -> &FlameGraph {
@@ -221,6 +855,26 @@ impl App {
             .map(|s| s.lock().unwrap().clone())
     }
 
+    #[cfg(feature = "python")]
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_ref()
+    }
+
+    #[cfg(feature = "python")]
+    pub fn refresh_interval_ms(&self) -> u64 {
+        self.refresh_interval_ms.load(Ordering::Relaxed)
+    }
+
+    /// Halve (faster) or double (slower) the live refresh interval within the allowed bounds.
+    #[cfg(feature = "python")]
+    pub fn adjust_refresh_interval(&mut self, faster: bool) {
+        let current = self.refresh_interval_ms();
+        let next = if faster { current / 2 } else { current * 2 };
+        let next = next.clamp(MIN_REFRESH_MS, MAX_REFRESH_MS);
+        self.refresh_interval_ms.store(next, Ordering::Relaxed);
+        self.set_transient_message(&format!("Refresh interval: {}ms", next));
+    }
+
     pub fn add_elapsed(&mut self, name: &str, elapsed: Duration) {
         self.elapsed.insert(name.to_string(), elapsed);
     }
@@ -235,7 +889,7 @@ impl App {
                 .to_string()
         });
         if let Some(short_name) = short_name {
-            self.set_manual_search_pattern(short_name.as_str(), false);
+            self.set_exact_search_pattern(short_name.as_str());
         }
     }
 
@@ -245,20 +899,172 @@ impl App {
             .get_selected_row_name()
             .map(|s| s.to_string());
         if let Some(short_name) = short_name {
-            self.set_manual_search_pattern(short_name.as_str(), false);
+            self.set_exact_search_pattern(short_name.as_str());
         }
         self.flamegraph_view.state.toggle_view_kind();
     }
 
-    pub fn set_manual_search_pattern(&mut self, pattern: &str, is_regex: bool) {
-        match SearchPattern::new(pattern, is_regex, true) {
+    /// Search for the frames named exactly like the given name.
+    fn set_exact_search_pattern(&mut self, name: &str) {
+        match SearchPattern::new(name, false, true) {
             Ok(p) => self.flamegraph_view.set_search_pattern(p),
+            Err(e) => self.set_name_pattern_error(&e),
+        }
+    }
+
+    /// Focus on or exclude the subtrees of frames named like the selected one, see
+    /// [`FlameGraphView::toggle_focus_selected`].
+    pub fn toggle_focus_selected(&mut self, mode: FocusMode) {
+        if let Err(e) = self.flamegraph_view.toggle_focus_selected(mode) {
+            self.set_name_pattern_error(&e);
+        }
+    }
+
+    /// Report that a pattern matching a frame name could not be built, e.g. as the name is too
+    /// long for the regex engine. The name is left out as it is likely too long to show.
+    fn set_name_pattern_error(&mut self, e: &regex::Error) {
+        self.set_transient_message(&format!(
+            "Cannot search for the frame name: {}",
+            describe_regex_error(e)
+        ));
+    }
+
+    pub fn set_manual_search_pattern(
+        &mut self,
+        pattern: &str,
+        mode: SearchMode,
+        scope: SearchScope,
+    ) {
+        let p = match mode {
+            SearchMode::Regex => SearchPattern::new(pattern, true, true),
+            SearchMode::Text => SearchPattern::containing(pattern, true),
+        };
+        match p {
+            Ok(p) => self.flamegraph_view.set_search_pattern(p.with_scope(scope)),
+            Err(_) => {
+                self.set_transient_message(&format!("Invalid regex: {}", pattern));
+            }
+        }
+    }
+
+    pub fn add_highlight_pattern(&mut self, pattern: &str) {
+        match SearchPattern::new(pattern, true, true) {
+            Ok(p) => self.flamegraph_view.add_highlight_pattern(p),
             Err(_) => {
                 self.set_transient_message(&format!("Invalid regex: {}", pattern));
             }
         }
     }
 
+    /// Open the search prompt, matching against full paths again if the current search does.
+    pub fn open_search_prompt(&mut self) {
+        let mut input_buffer = InputBuffer::new(InputKind::Search);
+        input_buffer.search_mode = self.default_search_mode;
+        if let Some(p) = &self.flamegraph_state().search_pattern {
+            if p.is_manual {
+                input_buffer.search_scope = p.scope;
+            }
+        }
+        self.input_buffer = Some(input_buffer);
+    }
+
+    /// Stop sampling the current process, if any, and start a live flamegraph of another one.
+    #[cfg(feature = "python")]
+    pub fn attach_to_pid(&mut self, pid: &str) {
+        let Ok(pid) = pid.trim().parse::<u64>() else {
+            self.set_transient_message(&format!("Invalid PID: {}", pid));
+            return;
+        };
+        if let FlameGraphInput::Stream(_) = self.flamegraph_input {
+            self.set_transient_message("Cannot attach to a process while viewing a stream");
+            return;
+        }
+        let previous = match &self.flamegraph_input {
+            FlameGraphInput::Pid(previous_pid, _) => format!("PID {}", previous_pid),
+            FlameGraphInput::File(path) => path.clone(),
+            FlameGraphInput::Stream(title) => title.clone(),
+        };
+        // The old threads keep the old state and slot, so anything they still produce is dropped
+        if let Some(sampler_state) = self.sampler_state.take() {
+            sampler_state.lock().unwrap().request_stop();
+        }
+        // Not waiting for the old recording to be written out keeps attaching responsive
+        if let Some(recorder) = self.recorder.take() {
+            self.log_message(format!(
+                "INFO Stopped recording to {} as it only covers {}",
+                recorder.path().display(),
+                previous
+            ));
+            recorder.finish_in_background();
+        }
+        // Live graphs are sorted by default, whatever order was used for the file
+        if let FlameGraphInput::File(_) = self.flamegraph_input {
+            self.parse_options.lock().unwrap().sorted = true;
+        }
+        // Each process is recorded to its own file so that the previous recording stays valid
+        if let Some(record_path) = &self.record_path {
+            let path = get_recording_path(record_path, pid as remoteprocess::Pid);
+            match Recorder::start(&path) {
+                Ok(recorder) => {
+                    self.recorder = Some(recorder);
+                    self.log_message(format!("INFO Recording PID {} to {}", pid, path.display()));
+                }
+                Err(e) => self.log_message(format!(
+                    "ERROR Could not record PID {} to {}: {}",
+                    pid,
+                    path.display(),
+                    e
+                )),
+            }
+        }
+        self.next_flamegraph = Arc::new(Mutex::new(None));
+        let sampler_state = Arc::new(Mutex::new(SamplerState::new(includes_idle(
+            self.py_spy_args.as_deref(),
+        ))));
+        let sampler_description = Self::spawn_sampler(
+            pid,
+            self.py_spy_args.clone(),
+            self.next_flamegraph.clone(),
+            self.parse_options.clone(),
+            sampler_state.clone(),
+            self.refresh_interval_ms.clone(),
+            self.recorder.as_ref().map(|r| r.sender()),
+        );
+        self.sampler_state = Some(sampler_state);
+        self.sampler_failed = false;
+        self.flamegraph_input = FlameGraphInput::Pid(pid, Self::get_process_info(pid));
+        self.flamegraph_view = FlameGraphView::new(FlameGraph::from_string(String::new(), true));
+        self.frame_cap_applied = false;
+        self.log_message(format!("INFO Switched from {} to PID {}", previous, pid));
+        if let Some(description) = sampler_description {
+            self.log_message(format!("INFO {}", description));
+        }
+    }
+
+    /// Replace the current flamegraph with the one in the given file.
+    pub fn open_file(&mut self, filename: &str) {
+        if self.is_live() {
+            self.set_transient_message("Cannot open a file during a live session");
+            return;
+        }
+        match std::fs::read_to_string(completion::expand_tilde(filename)) {
+            Ok(content) => {
+                let tic = std::time::Instant::now();
+                let flamegraph =
+                    FlameGraph::from_string_with_options(content, &self.parse_options());
+                self.add_elapsed("flamegraph", tic.elapsed());
+                self.flamegraph_view = FlameGraphView::new(flamegraph);
+                self.flamegraph_input = FlameGraphInput::File(filename.to_string());
+                self.log_flamegraph_warnings();
+                self.frame_cap_applied = false;
+                self.apply_frame_cap();
+            }
+            Err(e) => {
+                self.set_transient_message(&format!("Could not open {}: {}", filename, e));
+            }
+        }
+    }
+
     pub fn set_transient_message(&mut self, message: &str) {
         self.transient_message = Some(message.to_string());
     }
@@ -267,6 +1073,147 @@ impl App {
         self.transient_message = None;
     }
 
+    /// Open a panel listing subtrees that appear in several places.
+    pub fn show_duplicate_subtrees(&mut self) {
+        let entries = self.flamegraph().find_duplicate_subtrees();
+        if entries.is_empty() {
+            self.set_transient_message("No duplicated subtrees found");
+            return;
+        }
+        self.duplicates_panel = Some(DuplicatesPanel {
+            entries,
+            selected: 0,
+            total_count: self.flamegraph().total_count(),
+        });
+    }
+
+    /// Reset the view to how it was right after loading, closing the panels.
+    pub fn reset_view_state(&mut self) {
+        self.flamegraph_view.reset_view_state();
+        self.duplicates_panel = None;
+        self.butterfly_panel = None;
+        self.legend_panel = None;
+        self.path_panel = None;
+        self.set_transient_message("View reset to its state after loading");
+    }
+
+    /// Open a panel with the callers and callees of the selected function merged across all the
+    /// places it appears.
+    pub fn show_butterfly(&mut self) {
+        let Some(short_name) = self
+            .flamegraph_view
+            .get_selected_stack()
+            .filter(|stack| stack.id != ROOT_ID)
+            .map(|stack| self.flamegraph().get_stack_short_name_from_info(stack))
+        else {
+            self.set_transient_message("Select a frame to see its callers and callees");
+            return;
+        };
+        let Some(butterfly) = self.flamegraph().get_butterfly(short_name) else {
+            return;
+        };
+        self.butterfly_panel = Some(ButterflyPanel {
+            butterfly,
+            offset: 0,
+            total_count: self.flamegraph().total_count(),
+        });
+    }
+
+    pub fn toggle_legend_panel(&mut self) {
+        self.legend_panel = match self.legend_panel {
+            Some(_) => None,
+            None => Some(LegendPanel::default()),
+        };
+    }
+
+    /// Zoom into the top-level frame selected in the legend, showing only that group.
+    pub fn zoom_to_selected_legend_entry(&mut self) {
+        let Some(panel) = &self.legend_panel else {
+            return;
+        };
+        let index = panel.selected_index(self.flamegraph());
+        let Some(stack_id) = LegendPanel::entries(self.flamegraph())
+            .get(index)
+            .map(|stack| stack.id)
+        else {
+            return;
+        };
+        self.flamegraph_view.select_id(&stack_id);
+        self.flamegraph_view.set_zoom();
+    }
+
+    /// Highlight all occurrences of the duplicated subtree selected in the panel and close it.
+    pub fn highlight_selected_duplicate(&mut self) {
+        let Some(panel) = self.duplicates_panel.take() else {
+            return;
+        };
+        if let Some(entry) = panel.entries.get(panel.selected) {
+            match SearchPattern::new(&entry.name, false, true) {
+                Ok(pattern) => self.flamegraph_view.add_highlight_pattern(pattern),
+                Err(e) => self.set_name_pattern_error(&e),
+            }
+        }
+    }
+
+    pub fn toggle_humanize_counts(&mut self) {
+        self.humanize_counts = !self.humanize_counts;
+    }
+
+    /// Collapse the frames all stacks start with into the root, or expand them again, reporting
+    /// which frames were collapsed.
+    pub fn toggle_collapsed_common_prefix(&mut self) {
+        self.flamegraph_view.toggle_collapsed_common_prefix();
+        // Live updates are parsed accordingly from now on
+        self.parse_options.lock().unwrap().collapse_common_prefix =
+            self.flamegraph_state().collapse_common_prefix;
+        let message = match self.flamegraph().root_prefix() {
+            _ if !self.flamegraph_state().collapse_common_prefix => {
+                "Expanded the frames shared by all stacks".to_string()
+            }
+            Some(prefix) => format!(
+                "Collapsed {} frames shared by all stacks into the root: {}",
+                prefix.split(';').count(),
+                prefix
+            ),
+            None => "No frames shared by all stacks to collapse".to_string(),
+        };
+        self.log_message(format!("INFO {}", message));
+        self.set_transient_message(&message);
+    }
+
+    /// Open or close the panel listing the path from the root to the selected frame.
+    pub fn toggle_path_panel(&mut self) {
+        if self.path_panel.take().is_some() {
+            return;
+        }
+        let Some(stack) = self.flamegraph_view.get_selected_stack() else {
+            return;
+        };
+        self.path_panel = Some(PathPanel {
+            full_name: self
+                .flamegraph()
+                .get_stack_full_name_from_info(stack)
+                .to_string(),
+            selected: stack.level,
+        });
+    }
+
+    /// Move the flamegraph selection to the frame selected in the path panel.
+    pub fn select_path_panel_entry(&mut self) {
+        let Some(panel) = &self.path_panel else {
+            return;
+        };
+        let Some(stack_id) = panel
+            .entries(self.flamegraph())
+            .get(panel.selected)
+            .map(|stack| stack.id)
+        else {
+            return;
+        };
+        self.flamegraph_view.select_id(&stack_id);
+        self.flamegraph_view.scroll_to_selected();
+    }
+
     pub fn toggle_debug(&mut self) {
         self.debug = !self.debug;
     }
@@ -284,6 +1231,43 @@ impl App {
         }
     }
 
+    /// Add a message to the log panel, making the panel available if it wasn't.
+    pub fn log_message(&mut self, msg: String) {
+        self.has_log_channel = true;
+        self.push_log_message(msg);
+    }
+
+    /// Log warnings about the loaded flamegraph that may make it misleading.
+    pub fn log_flamegraph_warnings(&mut self) {
+        let malformed_line_count = self.flamegraph().malformed_line_count();
+        if malformed_line_count > 0 {
+            self.log_message(format!(
+                "WARN Skipped {} malformed line(s) not in the `stack count` format",
+                malformed_line_count
+            ));
+            let diagnostics = self.flamegraph().parse_diagnostics().to_vec();
+            for diagnostic in diagnostics.iter() {
+                self.log_message(format!("WARN   {}", diagnostic));
+            }
+            if malformed_line_count > diagnostics.len() {
+                self.log_message(format!(
+                    "WARN   ... and {} more",
+                    malformed_line_count - diagnostics.len()
+                ));
+            }
+        }
+        let truncated_count = self.flamegraph().truncated_count();
+        if truncated_count > 0 {
+            let total_count = self.flamegraph().total_count();
+            self.log_message(format!(
+                "WARN {} of {} samples ({:.2}%) have truncated stacks; their depth may be wrong",
+                truncated_count,
+                total_count,
+                100.0 * truncated_count as f64 / total_count as f64
+            ));
+        }
+    }
+
     pub fn toggle_log_panel(&mut self) {
         if self.has_log_channel {
             self.show_log_panel = !self.show_log_panel;
@@ -391,3 +1375,448 @@ impl App {
         self.log_max_capacity = capacity;
     }
 }
+
+/// One line explanation of a regex error, whose message otherwise spans several lines to point
+/// at the error in the pattern.
+fn describe_regex_error(e: &regex::Error) -> String {
+    let message = e.to_string();
+    let reason = message.lines().last().unwrap_or_default().trim();
+    reason.trim_start_matches("error: ").to_string()
+}
+
+/// Resident set size of the current process in bytes, if it can be determined on this platform.
+fn get_rss_bytes() -> Option<u64> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "linux")] {
+            let status = std::fs::read_to_string("/proc/self/status").ok()?;
+            parse_vm_rss_bytes(&status)
+        } else if #[cfg(unix)] {
+            let output = std::process::Command::new("ps")
+                .args(["-o", "rss=", "-p", &std::process::id().to_string()])
+                .output()
+                .ok()?;
+            let kb = String::from_utf8(output.stdout).ok()?.trim().parse::<u64>().ok()?;
+            Some(kb * 1024)
+        } else {
+            None
+        }
+    }
+}
+
+/// Resident set size in bytes from the `VmRSS` line of a `/proc/<pid>/status` file.
+#[cfg(target_os = "linux")]
+fn parse_vm_rss_bytes(status: &str) -> Option<u64> {
+    let kb = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_next_flamegraph(app: &App, content: &str) {
+        *app.next_flamegraph_slot().lock().unwrap() = Some(ParsedFlameGraph {
+            flamegraph: FlameGraph::from_string(content.to_string(), true),
+            elapsed: Duration::ZERO,
+        });
+    }
+
+    #[test]
+    fn test_toggle_freeze() {
+        let mut app = App::with_live_stream("stream");
+        assert!(app.is_live());
+        set_next_flamegraph(&app, "main;work 5\n");
+        app.tick();
+        assert_eq!(app.flamegraph().total_count(), 5);
+
+        // Updates wait while frozen
+        app.toggle_freeze();
+        set_next_flamegraph(&app, "main;work 8\n");
+        app.tick();
+        assert_eq!(app.flamegraph().total_count(), 5);
+
+        // and the pending one is swapped in on unfreeze, without waiting for the next tick
+        app.toggle_freeze();
+        assert!(!app.flamegraph_state().freeze);
+        assert_eq!(app.flamegraph().total_count(), 8);
+        assert!(app.next_flamegraph_slot().lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_path_panel_levels() {
+        let content = "main;parse;alloc 30\nmain;render 10\nmain;parse 20\nother 40\n";
+        let fg = FlameGraph::from_string(content.to_string(), true);
+        let panel = PathPanel {
+            full_name: "main;parse;alloc".to_string(),
+            selected: 0,
+        };
+        let levels = panel.levels(&fg);
+        let names = levels
+            .iter()
+            .map(|level| fg.get_stack_short_name_from_info(level.stack))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["all", "main", "parse", "alloc"]);
+        let shares = levels
+            .iter()
+            .map(|level| (level.share_of_total, level.next_level_share))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shares,
+            vec![
+                (1.0, Some(0.6)),
+                (0.6, Some(50.0 / 60.0)),
+                (0.5, Some(0.6)),
+                (0.3, None),
+            ]
+        );
+
+        // A frame that is gone leaves only the root
+        let panel = PathPanel {
+            full_name: "main;gone".to_string(),
+            selected: 0,
+        };
+        assert_eq!(panel.levels(&fg).len(), 1);
+    }
+
+    #[test]
+    fn test_toggle_collapsed_common_prefix_live() {
+        let content = "run;loop;a 10\nrun;loop;b 20\n";
+        let mut app = App::with_live_stream("stream");
+        set_next_flamegraph(&app, content);
+        app.tick();
+
+        // Updates are parsed with the setting by the thread producing them
+        app.toggle_collapsed_common_prefix();
+        assert!(
+            app.parse_options_slot()
+                .lock()
+                .unwrap()
+                .collapse_common_prefix
+        );
+        assert_eq!(app.flamegraph().root_prefix(), Some("run;loop"));
+
+        // while one parsed before the toggle is brought in line when swapped in
+        set_next_flamegraph(&app, content);
+        app.tick();
+        assert_eq!(app.flamegraph().root_prefix(), Some("run;loop"));
+
+        app.toggle_collapsed_common_prefix();
+        assert!(!app.parse_options().collapse_common_prefix);
+        assert_eq!(app.flamegraph().root_prefix(), None);
+    }
+
+    #[test]
+    fn test_validate_search() {
+        let mut input_buffer = InputBuffer::new(InputKind::Search);
+        input_buffer.buffer = tui_input::Input::new("parse(".to_string());
+        input_buffer.validate_search();
+        assert!(input_buffer.search_error.is_some());
+
+        // Plain text is never invalid, and switching back validates the text again
+        input_buffer.toggle_search_mode();
+        assert_eq!(input_buffer.search_mode, SearchMode::Text);
+        assert_eq!(input_buffer.search_error, None);
+        input_buffer.toggle_search_mode();
+        assert_eq!(input_buffer.search_mode, SearchMode::Regex);
+        assert!(input_buffer.search_error.is_some());
+
+        input_buffer.buffer = tui_input::Input::new("parse(a|b)".to_string());
+        input_buffer.validate_search();
+        assert_eq!(input_buffer.search_error, None);
+
+        // Only searches are validated
+        let mut input_buffer = InputBuffer::new(InputKind::OpenFile);
+        input_buffer.buffer = tui_input::Input::new("parse(".to_string());
+        input_buffer.validate_search();
+        assert_eq!(input_buffer.search_error, None);
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let content = "main;Vec::push 30\nmain;Vec::push(inlined) 10\nmain;parse 20\n";
+        let fg = FlameGraph::from_string(content.to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        let get_hits = |app: &App| {
+            let mut hits = app
+                .flamegraph()
+                .hit_ids()
+                .into_iter()
+                .flatten()
+                .map(|id| {
+                    app.flamegraph()
+                        .get_stack_short_name(id)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>();
+            hits.sort();
+            hits
+        };
+
+        // Text matches the names containing it, with regex characters taken literally
+        app.set_manual_search_pattern("push(", SearchMode::Text, SearchScope::Frame);
+        assert_eq!(get_hits(&app), vec!["Vec::push(inlined)"]);
+        app.set_manual_search_pattern("::push", SearchMode::Text, SearchScope::Frame);
+        assert_eq!(get_hits(&app), vec!["Vec::push", "Vec::push(inlined)"]);
+        app.set_manual_search_pattern("main;par", SearchMode::Text, SearchScope::Path);
+        assert_eq!(get_hits(&app), vec!["parse"]);
+
+        app.set_manual_search_pattern("^Vec::push$", SearchMode::Regex, SearchScope::Frame);
+        assert_eq!(get_hits(&app), vec!["Vec::push"]);
+
+        // Searching like the selected frame matches its exact name
+        let stack_id = app
+            .flamegraph()
+            .get_stack_by_full_name("main;Vec::push")
+            .unwrap()
+            .id;
+        app.flamegraph_view.select_id(&stack_id);
+        app.search_selected();
+        assert_eq!(get_hits(&app), vec!["Vec::push"]);
+    }
+
+    #[test]
+    fn test_name_too_long_to_search() {
+        // Too large for the regex engine even though it is escaped
+        let name = "a".repeat(1 << 20);
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        app.set_exact_search_pattern(&name);
+        assert!(app.flamegraph_state().search_pattern.is_none());
+        let message = "Cannot search for the frame name: Compiled regex exceeds size limit of \
+                       10485760 bytes.";
+        assert_eq!(app.transient_message.take().as_deref(), Some(message));
+
+        app.duplicates_panel = Some(DuplicatesPanel {
+            entries: vec![DuplicateSubtree {
+                name: name.clone(),
+                occurrences: 2,
+                total_count: 5,
+            }],
+            selected: 0,
+            total_count: 5,
+        });
+        app.highlight_selected_duplicate();
+        assert!(app.flamegraph_state().get_highlight_patterns().is_empty());
+        assert_eq!(app.transient_message.take().as_deref(), Some(message));
+
+        let fg = FlameGraph::from_string(format!("main;{} 5\n", name), true);
+        let mut app = App::with_flamegraph("test", fg);
+        let stack_id = app
+            .flamegraph()
+            .get_stack_id_by_full_name(&format!("main;{}", name))
+            .unwrap();
+        app.flamegraph_view.state.select_id(&stack_id);
+        app.toggle_focus_selected(FocusMode::Focus);
+        assert!(app.flamegraph_state().focus.is_none());
+        assert_eq!(app.transient_message.as_deref(), Some(message));
+    }
+
+    #[test]
+    fn test_legend_panel() {
+        let content = "thread-b;work 20\nthread-a;work 20\nthread-c;idle 50\n";
+        let fg = FlameGraph::from_string(content.to_string(), true);
+        let names = LegendPanel::entries(&fg)
+            .iter()
+            .map(|stack| fg.get_stack_short_name_from_info(stack))
+            .collect::<Vec<_>>();
+        // Sorted by descending count, ties broken by name
+        assert_eq!(names, vec!["thread-c", "thread-a", "thread-b"]);
+
+        let mut panel = LegendPanel::default();
+        assert_eq!(panel.selected_index(&fg), 0);
+        panel.to_previous(&fg);
+        assert_eq!(panel.selected.as_deref(), Some("thread-c"));
+        panel.to_next(&fg);
+        panel.to_next(&fg);
+        assert_eq!(panel.selected.as_deref(), Some("thread-b"));
+        panel.to_next(&fg);
+        assert_eq!(panel.selected.as_deref(), Some("thread-b"));
+        assert_eq!(panel.selected_index(&fg), 2);
+
+        // Falls back to the first entry when the selected one is gone
+        panel.selected = Some("thread-gone".to_string());
+        assert_eq!(panel.selected_index(&fg), 0);
+    }
+
+    #[test]
+    fn test_zoom_to_selected_legend_entry() {
+        let content = "thread-a;work 20\nthread-b;idle 50\n";
+        let fg = FlameGraph::from_string(content.to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+
+        // Nothing to zoom into without the panel
+        app.zoom_to_selected_legend_entry();
+        assert!(app.flamegraph_state().zoom.is_none());
+
+        app.toggle_legend_panel();
+        let panel = app.legend_panel.as_mut().unwrap();
+        panel.to_next(&app.flamegraph_view.flamegraph);
+        app.zoom_to_selected_legend_entry();
+        let stack_id = app
+            .flamegraph()
+            .get_stack_id_by_full_name("thread-a")
+            .unwrap();
+        assert_eq!(app.flamegraph_state().selected, stack_id);
+        assert_eq!(
+            app.flamegraph_state()
+                .zoom
+                .as_ref()
+                .map(|zoom| zoom.stack_id),
+            Some(stack_id)
+        );
+    }
+
+    #[test]
+    fn test_apply_frame_cap() {
+        let mut content = "main;big 10000\n".to_string();
+        for i in 0..5 {
+            content.push_str(&format!("main;tiny{} 1\n", i));
+        }
+        let new_app = |max_frames| {
+            let fg = FlameGraph::from_string(content.clone(), true);
+            let mut app = App::with_flamegraph("test", fg);
+            app.max_frames = max_frames;
+            app
+        };
+
+        // Under the limit nothing is hidden
+        let mut app = new_app(Some(100));
+        app.apply_frame_cap();
+        assert!(app.flamegraph_state().prune_threshold.is_none());
+        assert!(app.log_messages.is_empty());
+
+        let mut app = new_app(Some(3));
+        app.apply_frame_cap();
+        assert_eq!(
+            app.flamegraph_state().prune_threshold,
+            Some(FRAME_CAP_PRUNE_THRESHOLD)
+        );
+        assert_eq!(app.log_messages.len(), 1);
+        assert!(app.log_messages[0].starts_with("WARN The flamegraph has"));
+        assert!(app.log_messages[0].contains("Hiding 5 frames"));
+        assert!(app.transient_message.is_some());
+
+        // Only applied once so that showing the frames again sticks
+        app.flamegraph_view.state.prune_threshold = None;
+        app.apply_frame_cap();
+        assert!(app.flamegraph_state().prune_threshold.is_none());
+        assert_eq!(app.log_messages.len(), 1);
+
+        // --max-frames 0 disables the limit
+        let mut app = new_app(None);
+        app.apply_frame_cap();
+        assert!(app.flamegraph_state().prune_threshold.is_none());
+        assert!(app.log_messages.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_vm_rss_bytes() {
+        let status = "Name:\tflamelens\nVmPeak:\t  20480 kB\nVmRSS:\t   12345 kB\nThreads:\t4\n";
+        assert_eq!(parse_vm_rss_bytes(status), Some(12345 * 1024));
+        assert_eq!(parse_vm_rss_bytes("Name:\tflamelens\n"), None);
+        assert_eq!(parse_vm_rss_bytes("VmRSS:\t   ? kB\n"), None);
+    }
+
+    #[test]
+    fn test_rss_update_throttled() {
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+
+        // Only queried in debug mode
+        app.tick();
+        assert!(app.rss_updated_at.is_none());
+
+        app.debug = true;
+        app.tick();
+        let updated_at = app.rss_updated_at.unwrap();
+        app.rss_bytes = Some(1);
+        app.tick();
+        assert_eq!(app.rss_updated_at, Some(updated_at));
+        assert_eq!(app.rss_bytes, Some(1));
+
+        // and again once the interval passed
+        app.rss_updated_at = Some(updated_at - RSS_UPDATE_INTERVAL);
+        app.tick();
+        assert!(app.rss_updated_at.unwrap() > updated_at);
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_sampler_failure_reported_once() {
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        let sampler_state = Arc::new(Mutex::new(SamplerState::default()));
+        app.sampler_state = Some(sampler_state.clone());
+        app.tick();
+        assert!(app.log_messages.is_empty());
+        assert!(app.transient_message.is_none());
+
+        sampler_state
+            .lock()
+            .unwrap()
+            .set_status(SamplerStatus::Error("Process 1234 exited".to_string()));
+        app.tick();
+        assert_eq!(app.log_messages, vec!["ERROR Process 1234 exited"]);
+        assert_eq!(
+            app.transient_message.as_deref(),
+            Some(
+                "Process 1234 exited. Press 'o' to open a file or 'A' to attach to another process"
+            )
+        );
+
+        // Not repeated on later ticks once dismissed
+        app.transient_message = None;
+        app.tick();
+        app.tick();
+        assert_eq!(app.log_messages.len(), 1);
+        assert!(app.transient_message.is_none());
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_adjust_refresh_interval() {
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        app.refresh_interval_ms.store(200, Ordering::Relaxed);
+        app.adjust_refresh_interval(true);
+        assert_eq!(app.refresh_interval_ms(), 100);
+        assert_eq!(
+            app.transient_message.as_deref(),
+            Some("Refresh interval: 100ms")
+        );
+
+        // Stops at the bounds however many times it is adjusted
+        for _ in 0..10 {
+            app.adjust_refresh_interval(true);
+        }
+        assert_eq!(app.refresh_interval_ms(), MIN_REFRESH_MS);
+        for _ in 0..20 {
+            app.adjust_refresh_interval(false);
+        }
+        assert_eq!(app.refresh_interval_ms(), MAX_REFRESH_MS);
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_attach_to_pid_rejected() {
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        app.attach_to_pid("12ab");
+        assert_eq!(app.transient_message.as_deref(), Some("Invalid PID: 12ab"));
+        assert!(!app.is_live());
+
+        let mut app = App::with_live_stream("stream");
+        app.attach_to_pid("1234");
+        assert!(matches!(app.flamegraph_input, FlameGraphInput::Stream(_)));
+        assert!(app.sampler_state.is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use crate::flame::{FlameGraph, SearchPattern, SearchScope};
+use crate::formats::{ParseDiagnostic, ParseOptions};
+
+/// Outcome of `flamelens check`, which is also its exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckResult {
+    Passed = 0,
+    Matched = 1,
+    Malformed = 2,
+}
+
+/// What `flamelens check` looks for in the profile data.
+#[derive(Debug, Clone)]
+pub struct CheckOptions {
+    /// Regex matching the frames to look for
+    pub pattern: String,
+    /// Match the pattern against the full path of frames rather than their name
+    pub full_path: bool,
+    /// Share of the total in percent the matches may cover without failing
+    pub threshold_pct: f64,
+    /// Fail if any line of the profile data is malformed
+    pub strict: bool,
+}
+
+/// A frame matching the pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckMatch {
+    pub full_name: String,
+    pub total_count: u64,
+    /// Share of the total in percent
+    pub share_pct: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckReport {
+    pub result: CheckResult,
+    /// Frames matching the pattern, widest first. Empty if the check failed on malformed lines
+    pub matches: Vec<CheckMatch>,
+    /// Samples covered by the matches, nested matches being counted once
+    pub coverage_count: u64,
+    /// Share of the total covered by the matches in percent
+    pub coverage_pct: f64,
+    /// Malformed lines kept by the parser
+    pub diagnostics: Vec<ParseDiagnostic>,
+    /// Malformed lines beyond those in `diagnostics`
+    pub num_more_malformed: usize,
+}
+
+/// Search the profile data for frames matching the pattern and whether they cover more than the
+/// threshold. Only fails on an invalid pattern.
+pub fn check(
+    content: String,
+    parse_options: &ParseOptions,
+    options: &CheckOptions,
+) -> Result<CheckReport, regex::Error> {
+    let scope = if options.full_path {
+        SearchScope::Path
+    } else {
+        SearchScope::Frame
+    };
+    let pattern = SearchPattern::new(&options.pattern, true, true)?.with_scope(scope);
+    let mut flamegraph = FlameGraph::from_string_with_options(content, parse_options);
+    let diagnostics = flamegraph.parse_diagnostics().to_vec();
+    let mut report = CheckReport {
+        result: CheckResult::Passed,
+        matches: vec![],
+        coverage_count: 0,
+        coverage_pct: 0.0,
+        num_more_malformed: flamegraph.malformed_line_count() - diagnostics.len(),
+        diagnostics,
+    };
+    if options.strict && flamegraph.malformed_line_count() > 0 {
+        report.result = CheckResult::Malformed;
+        return Ok(report);
+    }
+    flamegraph.set_hits(&pattern);
+
+    let total_count = flamegraph.total_count();
+    let share = |count: u64| 100.0 * count as f64 / total_count.max(1) as f64;
+    let mut hits = flamegraph
+        .hit_ids()
+        .into_iter()
+        .flatten()
+        .filter_map(|stack_id| flamegraph.get_stack(stack_id))
+        .collect::<Vec<_>>();
+    hits.sort_by_key(|stack| std::cmp::Reverse(stack.total_count));
+    report.matches = hits
+        .iter()
+        .map(|stack| CheckMatch {
+            full_name: flamegraph.get_stack_full_name_from_info(stack).to_string(),
+            total_count: stack.total_count,
+            share_pct: share(stack.total_count),
+        })
+        .collect();
+    report.coverage_count = flamegraph.hit_coverage_count().unwrap_or(0);
+    report.coverage_pct = share(report.coverage_count);
+    if !report.matches.is_empty() && report.coverage_pct > options.threshold_pct {
+        report.result = CheckResult::Matched;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = "main;parse;malloc 30\nmain;render;malloc 10\nmain;parse 40\nidle 20\n";
+
+    fn get_options(pattern: &str, threshold_pct: f64) -> CheckOptions {
+        CheckOptions {
+            pattern: pattern.to_string(),
+            full_path: false,
+            threshold_pct,
+            strict: false,
+        }
+    }
+
+    #[test]
+    fn test_check_matched() {
+        let report = check(
+            CONTENT.to_string(),
+            &ParseOptions::default(),
+            &get_options("malloc", 0.0),
+        )
+        .unwrap();
+        assert_eq!(report.result, CheckResult::Matched);
+        assert_eq!(
+            report
+                .matches
+                .iter()
+                .map(|m| (m.full_name.as_str(), m.total_count))
+                .collect::<Vec<_>>(),
+            vec![("main;parse;malloc", 30), ("main;render;malloc", 10)]
+        );
+        assert_eq!(report.coverage_count, 40);
+        assert_eq!(report.coverage_pct, 40.0);
+
+        // Nested matches are only counted once
+        let mut options = get_options("^main;parse", 0.0);
+        options.full_path = true;
+        let report = check(CONTENT.to_string(), &ParseOptions::default(), &options).unwrap();
+        assert_eq!(report.matches.len(), 2);
+        assert_eq!(report.coverage_count, 70);
+    }
+
+    #[test]
+    fn test_check_passed() {
+        let report = check(
+            CONTENT.to_string(),
+            &ParseOptions::default(),
+            &get_options("malloc", 40.0),
+        )
+        .unwrap();
+        assert_eq!(report.result, CheckResult::Passed);
+        assert_eq!(report.matches.len(), 2);
+
+        let report = check(
+            CONTENT.to_string(),
+            &ParseOptions::default(),
+            &get_options("deprecated_api", 0.0),
+        )
+        .unwrap();
+        assert_eq!(report.result, CheckResult::Passed);
+        assert!(report.matches.is_empty());
+    }
+
+    #[test]
+    fn test_check_malformed() {
+        let content = format!("{}main;broken\n", CONTENT);
+        let report = check(
+            content.clone(),
+            &ParseOptions::default(),
+            &get_options("deprecated_api", 0.0),
+        )
+        .unwrap();
+        assert_eq!(report.result, CheckResult::Passed);
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].line_number, 5);
+
+        let mut options = get_options("deprecated_api", 0.0);
+        options.strict = true;
+        let report = check(content, &ParseOptions::default(), &options).unwrap();
+        assert_eq!(report.result, CheckResult::Malformed);
+    }
+
+    #[test]
+    fn test_check_invalid_pattern() {
+        let result = check(
+            CONTENT.to_string(),
+            &ParseOptions::default(),
+            &get_options("malloc(", 0.0),
+        );
+        assert!(result.is_err());
+    }
+}
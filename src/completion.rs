@@ -0,0 +1,121 @@
+use std::path::PathBuf;
+
+/// Expand a leading `~` to the home directory.
+pub fn expand_tilde(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = std::env::var_os("HOME") {
+            let mut expanded = PathBuf::from(home);
+            if let Some(rest) = path.get(2..) {
+                expanded.push(rest);
+            }
+            return expanded;
+        }
+    }
+    PathBuf::from(path)
+}
+
+/// Filesystem entries completing `input`, sorted by name. The directory part of `input` is kept as
+/// typed (including any `~`) and directories end with a `/`. Hidden entries are only included if
+/// the typed file name starts with a dot.
+pub fn path_candidates(input: &str) -> Vec<String> {
+    if input == "~" {
+        return vec!["~/".to_string()];
+    }
+    let (dir_part, file_prefix) = match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    };
+    let dir = if dir_part.is_empty() {
+        PathBuf::from(".")
+    } else {
+        expand_tilde(dir_part)
+    };
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut candidates = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(file_prefix)
+                || (name.starts_with('.') && !file_prefix.starts_with('.'))
+            {
+                return None;
+            }
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir_part, name, suffix))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates
+}
+
+/// Longest prefix shared by all candidates.
+pub fn longest_common_prefix(candidates: &[String]) -> &str {
+    let Some(first) = candidates.first() else {
+        return "";
+    };
+    let mut len = first.len();
+    for candidate in candidates.iter().skip(1) {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .take_while(|((_, a), b)| a == b)
+            .map(|((i, a), _)| i + a.len_utf8())
+            .last()
+            .unwrap_or(0)
+            .min(len);
+    }
+    &first[..len]
+}
+
+/// State of an ongoing completion in a prompt.
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub candidates: Vec<String>,
+    /// Candidate currently filled into the prompt when cycling with repeated completion requests
+    pub selected: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_longest_common_prefix() {
+        let candidates = ["src/flame.rs", "src/flames/", "src/fl"].map(String::from);
+        assert_eq!(longest_common_prefix(&candidates), "src/fl");
+        assert_eq!(longest_common_prefix(&["abc".to_string()]), "abc");
+        assert_eq!(longest_common_prefix(&["abc", "xyz"].map(String::from)), "");
+        assert_eq!(longest_common_prefix(&[]), "");
+    }
+
+    #[test]
+    fn test_path_candidates() {
+        let dir = std::env::temp_dir().join(format!("flamelens-completion-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("profiles")).unwrap();
+        for name in ["perf.folded", "profile.folded", ".hidden.folded"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let dir_part = format!("{}/", dir.to_str().unwrap());
+        let complete = |input: &str| {
+            path_candidates(&format!("{}{}", dir_part, input))
+                .into_iter()
+                .map(|candidate| candidate[dir_part.len()..].to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(complete("pro"), vec!["profile.folded", "profiles/"]);
+        assert_eq!(complete("pe"), vec!["perf.folded"]);
+        assert_eq!(
+            complete(""),
+            vec!["perf.folded", "profile.folded", "profiles/"]
+        );
+        assert_eq!(complete("."), vec![".hidden.folded"]);
+        assert!(complete("profiles/").is_empty());
+        assert!(complete("does-not-exist/").is_empty());
+        assert_eq!(path_candidates("~"), vec!["~/"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,294 @@
+use crate::flame::FlameGraph;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Default number of functions listed in each event.
+pub const DEFAULT_EMIT_TOP: usize = 10;
+/// Events waiting to be written beyond which new ones are dropped rather than queued.
+const MAX_PENDING_EVENTS: usize = 64;
+/// How long quitting waits for the pending events to be written.
+pub const FINISH_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FunctionSummary {
+    pub name: String,
+    pub self_count: u64,
+    pub total_count: u64,
+}
+
+/// Summary of a live flamegraph written as one JSON line when it is swapped in.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct UpdateEvent {
+    /// Seconds since the session started
+    pub elapsed_secs: f64,
+    pub total_samples: u64,
+    /// Samples added per second since the previous update
+    pub samples_per_sec: f64,
+    /// Functions with the highest self count, merged across every place they appear
+    pub top_self: Vec<FunctionSummary>,
+    /// Events dropped so far because the output could not keep up
+    pub dropped_events: usize,
+}
+
+impl UpdateEvent {
+    pub fn new(
+        flamegraph: &FlameGraph,
+        top: usize,
+        elapsed: Duration,
+        samples_per_sec: f64,
+        dropped_events: usize,
+    ) -> Self {
+        let mut entries = flamegraph
+            .ordered_stacks
+            .entries
+            .iter()
+            .filter(|entry| entry.count.own > 0)
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| b.count.own.cmp(&a.count.own).then(a.name.cmp(&b.name)));
+        Self {
+            elapsed_secs: elapsed.as_secs_f64(),
+            total_samples: flamegraph.total_count(),
+            samples_per_sec,
+            top_self: entries
+                .into_iter()
+                .take(top)
+                .map(|entry| FunctionSummary {
+                    name: entry.name.clone(),
+                    self_count: entry.count.own,
+                    total_count: entry.count.total,
+                })
+                .collect(),
+            dropped_events,
+        }
+    }
+}
+
+/// Rate of samples between two updates. A smaller total than before starts a new window, as
+/// stream snapshots need not be cumulative.
+fn get_sample_rate(previous_total: u64, total: u64, interval: Duration) -> f64 {
+    let secs = interval.as_secs_f64();
+    if secs <= 0.0 {
+        return 0.0;
+    }
+    let added = if total >= previous_total {
+        total - previous_total
+    } else {
+        total
+    };
+    added as f64 / secs
+}
+
+/// Writes an [`UpdateEvent`] per live update to a file, a FIFO or stdout (`-`), one JSON object
+/// per line.
+///
+/// Writing happens on a dedicated thread so that a slow reader never stalls rendering: events
+/// are dropped, and counted in the next ones, while too many are pending.
+#[derive(Debug)]
+pub struct JsonEmitter {
+    sender: Option<crossbeam_channel::Sender<String>>,
+    handle: Option<thread::JoinHandle<()>>,
+    error: Arc<Mutex<Option<String>>>,
+    top: usize,
+    started_at: Instant,
+    /// Total count and time of the previous event
+    previous: Option<(u64, Instant)>,
+    dropped_events: usize,
+}
+
+impl JsonEmitter {
+    /// Start the writer thread. A regular file is created right away so that an invalid path
+    /// fails here, while a FIFO is opened on the writer thread as opening it blocks until it has
+    /// a reader.
+    pub fn start(path: &str, top: usize) -> std::io::Result<Self> {
+        let output = if path == "-" {
+            Some(Box::new(std::io::stdout()) as Box<dyn Write + Send>)
+        } else if is_fifo(path) {
+            None
+        } else {
+            Some(Box::new(std::fs::File::create(path)?) as Box<dyn Write + Send>)
+        };
+        let path = path.to_string();
+        let (sender, receiver) = crossbeam_channel::bounded::<String>(MAX_PENDING_EVENTS);
+        let error = Arc::new(Mutex::new(None));
+        let handle = {
+            let error = error.clone();
+            thread::spawn(move || {
+                let mut output = match output {
+                    Some(output) => output,
+                    None => match std::fs::OpenOptions::new().write(true).open(&path) {
+                        Ok(file) => Box::new(file),
+                        Err(e) => {
+                            *error.lock().unwrap() = Some(e.to_string());
+                            return;
+                        }
+                    },
+                };
+                for line in receiver {
+                    let result = output
+                        .write_all(line.as_bytes())
+                        .and_then(|_| output.write_all(b"\n"))
+                        .and_then(|_| output.flush());
+                    if let Err(e) = result {
+                        *error.lock().unwrap() = Some(e.to_string());
+                        break;
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            sender: Some(sender),
+            handle: Some(handle),
+            error,
+            top,
+            started_at: Instant::now(),
+            previous: None,
+            dropped_events: 0,
+        })
+    }
+
+    /// Queue the summary of a flamegraph that was just swapped in, without blocking.
+    pub fn emit(&mut self, flamegraph: &FlameGraph) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let now = Instant::now();
+        let total = flamegraph.total_count();
+        let (previous_total, previous_at) = self.previous.unwrap_or((0, self.started_at));
+        let event = UpdateEvent::new(
+            flamegraph,
+            self.top,
+            now - self.started_at,
+            get_sample_rate(previous_total, total, now - previous_at),
+            self.dropped_events,
+        );
+        self.previous = Some((total, now));
+        let line = serde_json::to_string(&event).expect("update events are serializable");
+        if sender.try_send(line).is_err() {
+            self.dropped_events += 1;
+        }
+    }
+
+    /// Error that stopped the writer thread, returned once.
+    pub fn take_error(&mut self) -> Option<String> {
+        let error = self.error.lock().unwrap().take();
+        if error.is_some() {
+            self.sender = None;
+        }
+        error
+    }
+
+    /// Write out the pending events and wait for the writer thread to exit, for at most
+    /// [`FINISH_TIMEOUT`] as a stalled reader must not hold up quitting.
+    pub fn finish(&mut self) {
+        self.sender = None;
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let deadline = Instant::now() + FINISH_TIMEOUT;
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn is_fifo(path: &str) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            use std::os::unix::fs::FileTypeExt;
+            std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo())
+        } else {
+            let _ = path;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_event() {
+        let content = "main;parse;alloc 30\nmain;render;alloc 10\nmain;parse 20\nmain 5\n";
+        let fg = FlameGraph::from_string(content.to_string(), false);
+        let event = UpdateEvent::new(&fg, 2, Duration::from_millis(1500), 40.0, 0);
+        assert_eq!(event.total_samples, 65);
+        assert_eq!(
+            event.top_self,
+            vec![
+                FunctionSummary {
+                    name: "alloc".to_string(),
+                    self_count: 40,
+                    total_count: 40,
+                },
+                FunctionSummary {
+                    name: "parse".to_string(),
+                    self_count: 20,
+                    total_count: 50,
+                },
+            ]
+        );
+        let line = serde_json::to_string(&event).unwrap();
+        assert!(
+            line.starts_with(r#"{"elapsed_secs":1.5,"total_samples":65,"samples_per_sec":40.0,"#)
+        );
+        assert!(!line.contains('\n'));
+    }
+
+    #[test]
+    fn test_get_sample_rate() {
+        assert_eq!(get_sample_rate(100, 150, Duration::from_millis(500)), 100.0);
+        // A new window of samples
+        assert_eq!(get_sample_rate(100, 20, Duration::from_secs(2)), 10.0);
+        assert_eq!(get_sample_rate(0, 20, Duration::ZERO), 0.0);
+    }
+
+    fn get_temp_path(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("flamelens-emit-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_emit_to_file() {
+        let path = get_temp_path("events.jsonl");
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), false);
+        let mut emitter = JsonEmitter::start(path.to_str().unwrap(), 1).unwrap();
+        emitter.emit(&fg);
+        emitter.emit(&fg);
+        emitter.finish();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        assert!(content
+            .lines()
+            .all(|line| line.contains(r#""total_samples":5"#)));
+        assert_eq!(emitter.take_error(), None);
+
+        assert!(JsonEmitter::start("/nonexistent-dir/events.jsonl", 1).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_emit_to_fifo_without_reader() {
+        let path = get_temp_path("events.fifo");
+        let status = std::process::Command::new("mkfifo")
+            .arg(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        // Neither starting nor finishing waits for a reader to open the FIFO
+        let tic = Instant::now();
+        let mut emitter = JsonEmitter::start(path.to_str().unwrap(), 1).unwrap();
+        emitter.emit(&FlameGraph::from_string("main 1\n".to_string(), false));
+        emitter.finish();
+        assert!(tic.elapsed() < FINISH_TIMEOUT + Duration::from_secs(1));
+    }
+}
@@ -1,9 +1,13 @@
 use crate::app::AppResult;
 use crossterm::event::{self, Event as CrosstermEvent, KeyEvent, KeyEventKind, MouseEvent};
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
 use std::time::{Duration, Instant};
 
+/// How long without input before ticking at the idle rate.
+const IDLE_AFTER: Duration = Duration::from_secs(3);
+
 /// Terminal events.
 #[derive(Clone, Copy, Debug)]
 pub enum Event {
@@ -27,23 +31,43 @@ pub struct EventHandler {
     receiver: mpsc::Receiver<Event>,
     /// Event handler thread.
     handler: thread::JoinHandle<()>,
+    /// Whether ticks may slow down to the idle rate, i.e. nothing changes without input.
+    idle_allowed: Arc<AtomicBool>,
 }
 
 impl EventHandler {
     /// Constructs a new instance of [`EventHandler`].
     pub fn new(tick_rate: u64) -> Self {
+        Self::with_idle_tick_rate(tick_rate, None)
+    }
+
+    /// Constructs a new instance of [`EventHandler`] that ticks every `idle_tick_rate`
+    /// milliseconds instead of `tick_rate` once there was no input for a while, as long as
+    /// idling is allowed with [`EventHandler::set_idle_allowed`].
+    pub fn with_idle_tick_rate(tick_rate: u64, idle_tick_rate: Option<u64>) -> Self {
         let tick_rate = Duration::from_millis(tick_rate);
+        let idle_tick_rate = idle_tick_rate.map(Duration::from_millis);
+        let idle_allowed = Arc::new(AtomicBool::new(false));
         let (sender, receiver) = mpsc::channel();
         let handler = {
             let sender = sender.clone();
+            let idle_allowed = idle_allowed.clone();
             thread::spawn(move || {
                 let mut last_tick = Instant::now();
+                let mut last_input = Instant::now();
                 loop {
+                    let tick_rate = get_tick_rate(
+                        tick_rate,
+                        idle_tick_rate,
+                        idle_allowed.load(Ordering::Relaxed),
+                        last_input.elapsed(),
+                    );
                     let timeout = tick_rate
                         .checked_sub(last_tick.elapsed())
                         .unwrap_or(tick_rate);
 
                     if event::poll(timeout).expect("failed to poll new events") {
+                        last_input = Instant::now();
                         let send_result = match event::read().expect("unable to read event") {
                             CrosstermEvent::Key(e) => {
                                 if e.kind == KeyEventKind::Press {
@@ -76,9 +100,16 @@ impl EventHandler {
             sender,
             receiver,
             handler,
+            idle_allowed,
         }
     }
 
+    /// Allow or disallow ticking at the idle rate, e.g. disallow it while live updates need to be
+    /// swapped in promptly.
+    pub fn set_idle_allowed(&self, allowed: bool) {
+        self.idle_allowed.store(allowed, Ordering::Relaxed);
+    }
+
     /// Receive the next event from the handler thread.
     ///
     /// This function will always block the current thread if
@@ -87,3 +118,50 @@ impl EventHandler {
         Ok(self.receiver.recv()?)
     }
 }
+
+/// Interval until the next tick: the idle rate if there is one, idling is allowed and there was
+/// no input for [`IDLE_AFTER`], or else the regular rate.
+fn get_tick_rate(
+    tick_rate: Duration,
+    idle_tick_rate: Option<Duration>,
+    idle_allowed: bool,
+    since_input: Duration,
+) -> Duration {
+    match idle_tick_rate {
+        Some(idle_tick_rate) if idle_allowed && since_input >= IDLE_AFTER => idle_tick_rate,
+        _ => tick_rate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_tick_rate() {
+        let tick_rate = Duration::from_millis(250);
+        let idle_tick_rate = Some(Duration::from_millis(2000));
+
+        // Active until there was no input for a while
+        assert_eq!(
+            get_tick_rate(tick_rate, idle_tick_rate, true, Duration::ZERO),
+            tick_rate
+        );
+        assert_eq!(
+            get_tick_rate(tick_rate, idle_tick_rate, true, IDLE_AFTER),
+            Duration::from_millis(2000)
+        );
+
+        // Live sessions disallow idling so that updates are swapped in promptly
+        assert_eq!(
+            get_tick_rate(tick_rate, idle_tick_rate, false, IDLE_AFTER * 10),
+            tick_rate
+        );
+
+        // and there is no idle rate with --idle-tick-ms 0
+        assert_eq!(
+            get_tick_rate(tick_rate, None, true, IDLE_AFTER * 10),
+            tick_rate
+        );
+    }
+}
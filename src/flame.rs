@@ -2,6 +2,11 @@ use std::collections::{HashMap, HashSet};
 
 use serde::Serialize;
 
+use crate::formats::{
+    get_common_prefix, get_single_top_frame, parse_line_values, ParseDiagnostic, ParseOptions,
+    MAX_PARSE_DIAGNOSTICS,
+};
+
 pub type StackIdentifier = usize;
 pub static ROOT: &str = "all";
 pub static ROOT_ID: usize = 0;
@@ -21,12 +26,23 @@ pub struct StackInfo {
     pub hit: bool,
 }
 
+/// What a search pattern is matched against.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum SearchScope {
+    /// The name of the frame itself
+    #[default]
+    Frame,
+    /// The full path of the frame from the root, e.g. `main;serialize;malloc`
+    Path,
+}
+
 #[derive(Debug, Clone)]
 pub struct SearchPattern {
     pub pattern: String,
     pub is_regex: bool,
     pub re: regex::Regex,
     pub is_manual: bool,
+    pub scope: SearchScope,
 }
 
 impl SearchPattern {
@@ -42,8 +58,104 @@ impl SearchPattern {
             is_regex,
             re,
             is_manual,
+            scope: SearchScope::Frame,
+        })
+    }
+
+    /// Pattern matching the frame names (or paths) that contain the text.
+    pub fn containing(text: &str, is_manual: bool) -> Result<Self, regex::Error> {
+        Ok(Self {
+            re: regex::Regex::new(&regex::escape(text))?,
+            ..Self::new(text, false, is_manual)?
         })
     }
+
+    pub fn with_scope(self, scope: SearchScope) -> Self {
+        Self { scope, ..self }
+    }
+
+    /// Whether matches can be highlighted within frame names, i.e. the pattern is matched
+    /// against them rather than against full paths.
+    pub fn matches_frame_names(&self) -> bool {
+        self.is_manual && self.scope == SearchScope::Frame
+    }
+}
+
+/// How frames are dimmed relative to the subtrees of frames matching a pattern.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FocusMode {
+    /// Dim everything outside the matching subtrees
+    Focus,
+    /// Dim the matching subtrees
+    Exclude,
+}
+
+/// Frames whose subtrees are structurally identical, found in several places.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateSubtree {
+    pub name: String,
+    pub occurrences: usize,
+    /// Combined total count of all occurrences
+    pub total_count: u64,
+}
+
+/// Frames of a function's callers or callees merged by name across all places it appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergedCallTree {
+    pub name: String,
+    pub total_count: u64,
+    /// Sorted by descending total count
+    pub children: Vec<MergedCallTree>,
+}
+
+impl MergedCallTree {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            total_count: 0,
+            children: vec![],
+        }
+    }
+
+    fn child_mut(&mut self, name: &str) -> &mut MergedCallTree {
+        let index = match self.children.iter().position(|child| child.name == name) {
+            Some(index) => index,
+            None => {
+                self.children.push(MergedCallTree::new(name));
+                self.children.len() - 1
+            }
+        };
+        &mut self.children[index]
+    }
+
+    /// Number of frames in the tree, including its root
+    pub fn num_frames(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(MergedCallTree::num_frames)
+            .sum::<usize>()
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| {
+            b.total_count
+                .cmp(&a.total_count)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        for child in self.children.iter_mut() {
+            child.sort();
+        }
+    }
+}
+
+/// Who calls a function and what it calls, aggregated over all its occurrences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Butterfly {
+    /// Rooted at the function, with its callers as children and their callers below them
+    pub callers: MergedCallTree,
+    /// Rooted at the function, with its callees as children
+    pub callees: MergedCallTree,
 }
 
 #[derive(Debug, Clone)]
@@ -65,6 +177,17 @@ pub struct CountEntry {
     pub visible: bool,
 }
 
+/// What the width of frames is proportional to.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum WidthMetric {
+    /// The total (inclusive) count, as in usual flamegraphs
+    #[default]
+    Total,
+    /// The self (exclusive) count, or the combined width of the children if they are wider so
+    /// that the call structure still fits. Hot leaves stand out without comparing depths.
+    Own,
+}
+
 #[derive(Serialize, Debug, Clone, Eq, PartialEq, Copy)]
 pub enum SortColumn {
     Total,
@@ -80,10 +203,15 @@ pub struct Ordered {
 }
 
 impl Ordered {
-    pub fn set_search_pattern(&mut self, p: &SearchPattern) {
+    /// Show only the entries matching the pattern. With the path scope, entries are matched by
+    /// the names of the frames hit by the pattern, `hit_names`.
+    pub fn set_search_pattern(&mut self, p: &SearchPattern, hit_names: &HashSet<String>) {
         if p.is_manual {
             self.entries.iter_mut().for_each(|entry| {
-                entry.visible = p.re.is_match(&entry.name);
+                entry.visible = match p.scope {
+                    SearchScope::Frame => p.re.is_match(&entry.name),
+                    SearchScope::Path => hit_names.contains(&entry.name),
+                };
             });
             self.num_rows = self.entries.iter().filter(|entry| entry.visible).count();
             if self.num_rows == 0 {
@@ -122,6 +250,15 @@ impl Ordered {
     }
 }
 
+/// Per-frame counts of a flamegraph snapshot, keyed by full name, to diff later flamegraphs
+/// against.
+#[derive(Debug, Clone)]
+pub struct Baseline {
+    counts: HashMap<String, u64>,
+    total_count: u64,
+    pub created_at: std::time::Instant,
+}
+
 #[derive(Debug, Clone)]
 pub struct FlameGraph {
     data: String,
@@ -129,11 +266,65 @@ pub struct FlameGraph {
     levels: Vec<Vec<StackIdentifier>>,
     pub ordered_stacks: Ordered,
     hits: Option<Hits>,
+    /// Index of the first highlight pattern matched by each stack, empty if there are none
+    highlights: Vec<Option<usize>>,
+    /// Whether each stack is dimmed by the focus pattern, empty if there is none
+    dimmed: Vec<bool>,
+    /// Leaf stacks of lines containing a truncation marker
+    truncated_ids: HashSet<StackIdentifier>,
+    /// Number of samples with a truncated stack
+    truncated_count: u64,
+    /// Change in share of total samples per stack against a baseline, in percentage points.
+    /// Empty if not diffing.
+    diff: Vec<f64>,
+    max_abs_diff: f64,
+    /// Share of the total width covered by the stacks of each level
+    level_coverage: Vec<f64>,
+    /// Number of non-blank, non-comment lines skipped because they could not be parsed
+    malformed_line_count: usize,
+    /// Details of the first [`MAX_PARSE_DIAGNOSTICS`] malformed lines
+    parse_diagnostics: Vec<ParseDiagnostic>,
+    /// Number of levels below each stack, 0 for leaves
+    depth_below: Vec<usize>,
+    /// Names of the values of each line if there are several, see [`ParseOptions::value_names`]
+    value_names: Vec<String>,
+    /// Index of the value currently weighting the frames
+    value_column: usize,
+    /// Self count of each stack for every value, to re-weight without parsing again. Empty if
+    /// lines have a single value.
+    self_values: Vec<Vec<u64>>,
     sorted: bool,
+    /// Name of the root frame if not `all`, see [`ParseOptions::root_label`]
+    root_name: Option<String>,
+    /// Frames all stacks start with that the root stands for, see
+    /// [`ParseOptions::promote_single_root`] and [`ParseOptions::collapse_common_prefix`]
+    root_prefix: Option<String>,
+    /// Options to parse `data` again with, which has already been rewritten
+    parse_options: ParseOptions,
+    width_metric: WidthMetric,
+    /// Count each stack is as wide as for [`WidthMetric::Own`]. Empty for [`WidthMetric::Total`].
+    width_counts: Vec<u64>,
 }
 
 impl FlameGraph {
-    pub fn from_string(mut content: String, sorted: bool) -> Self {
+    /// Parse folded stacks with the root frame first, e.g. `main;parse;alloc 10`. `sorted`
+    /// orders sibling frames by count instead of keeping the input order; it does not change the
+    /// stack direction, see [`ParseOptions::reverse_stacks`] for leaf-first input.
+    pub fn from_string(content: String, sorted: bool) -> Self {
+        Self::from_string_with_options(
+            content,
+            &ParseOptions {
+                sorted,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn from_string_with_options(mut content: String, options: &ParseOptions) -> Self {
+        let sorted = options.sorted;
+        if options.rewrites_stacks() {
+            content = options.rewrite_content(&content);
+        }
         // Make sure content ends with newline to simplify parsing
         if !content.ends_with('\n') {
             content.push('\n');
@@ -154,38 +345,72 @@ impl FlameGraph {
         });
         let mut last_line_index = 0;
         let mut counts: HashMap<String, Count> = HashMap::new();
-        for line_index in content
+        let mut truncated_ids = HashSet::<StackIdentifier>::new();
+        let mut truncated_count = 0;
+        let mut malformed_line_count = 0;
+        let num_values = options.num_values();
+        let value_column = options.value_column.min(num_values - 1);
+        let mut self_values: Vec<Vec<u64>> = vec![];
+        // The frames standing in for the root are skipped at the start of every stack
+        let root_prefix = if options.collapse_common_prefix {
+            get_common_prefix(&content, num_values)
+        } else if options.promote_single_root {
+            get_single_top_frame(&content, num_values)
+        } else {
+            None
+        }
+        .map(|prefix| prefix.to_string());
+        let mut parse_diagnostics = vec![];
+        for (line_number, line_index) in content
             .char_indices()
             .filter(|(_, c)| *c == '\n')
             .map(|(i, _)| i)
+            .enumerate()
+            .map(|(i, line_index)| (i + 1, line_index))
         {
             let line = &content[last_line_index..line_index];
-            #[allow(clippy::unnecessary_unwrap)]
-            let line_and_count = match line.rsplit_once(' ') {
-                Some((line, count)) => {
-                    let parsed_count = count.parse::<u64>();
-                    if line.is_empty() || parsed_count.is_err() {
-                        None
-                    } else {
-                        Some((line, parsed_count.unwrap()))
-                    }
-                }
-                _ => None,
-            };
-            if line_and_count.is_none() || line.starts_with('#') {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
                 last_line_index = line_index + 1;
                 continue;
             }
-            let (line, count) = line_and_count.unwrap();
+            let Some((line, values)) = parse_line_values(line, num_values) else {
+                malformed_line_count += 1;
+                if parse_diagnostics.len() < MAX_PARSE_DIAGNOSTICS {
+                    parse_diagnostics.push(ParseDiagnostic::new(line_number, line, num_values));
+                }
+                last_line_index = line_index + 1;
+                continue;
+            };
+            let count = values[value_column];
 
             stacks[ROOT_ID].total_count += count;
             let mut parent_id = ROOT_ID;
             let mut level = 1;
             let mut last_delim_index = 0;
+            let mut frames_start = 0;
+            if let Some(root) = &root_prefix {
+                if root.starts_with(line)
+                    && matches!(root.as_bytes().get(line.len()), None | Some(b';'))
+                {
+                    // Samples in the frames the root stands for are the root's own
+                    stacks[ROOT_ID].self_count += count;
+                    if num_values > 1 {
+                        self_values.resize(stacks.len(), vec![0; num_values]);
+                        for (self_value, value) in self_values[ROOT_ID].iter_mut().zip(values) {
+                            *self_value += value;
+                        }
+                    }
+                    last_line_index = line_index + 1;
+                    continue;
+                }
+                frames_start = root.len() + 1;
+                last_delim_index = frames_start;
+            }
             let mut counted_names = HashSet::<String>::new();
             for delim_index in line
                 .char_indices()
-                .filter(|(_, c)| *c == ';')
+                .filter(|(i, c)| *c == ';' && *i >= frames_start)
                 .map(|(i, _)| i)
             {
                 let stack_id = FlameGraph::update_one(
@@ -205,7 +430,7 @@ impl FlameGraph {
                 level += 1;
                 last_delim_index = delim_index + 1;
             }
-            FlameGraph::update_one(
+            let leaf_id = FlameGraph::update_one(
                 &mut stacks,
                 &mut counts,
                 &mut counted_names,
@@ -218,6 +443,18 @@ impl FlameGraph {
                 level,
                 true,
             );
+            if num_values > 1 {
+                self_values.resize(stacks.len(), vec![0; num_values]);
+                for (self_value, value) in self_values[leaf_id].iter_mut().zip(values) {
+                    *self_value += value;
+                }
+            }
+            if let Some(re) = &options.truncation_marker {
+                if line.split(';').any(|frame| re.is_match(frame)) {
+                    truncated_ids.insert(leaf_id);
+                    truncated_count += count;
+                }
+            }
             last_line_index = line_index + 1;
         }
 
@@ -228,12 +465,194 @@ impl FlameGraph {
             levels: vec![],
             ordered_stacks: ordered,
             hits: None,
+            highlights: vec![],
+            dimmed: vec![],
+            truncated_ids,
+            truncated_count,
+            diff: vec![],
+            max_abs_diff: 0.0,
+            level_coverage: vec![],
+            malformed_line_count,
+            parse_diagnostics,
+            depth_below: vec![],
+            value_names: options.value_names.clone(),
+            value_column,
+            self_values,
             sorted,
+            root_name: options.root_label.clone().or(root_prefix.clone()),
+            root_prefix,
+            parse_options: ParseOptions {
+                comm: None,
+                reverse_stacks: false,
+                ..options.clone()
+            },
+            width_metric: WidthMetric::Total,
+            width_counts: vec![],
         };
+        if num_values > 1 {
+            out.self_values
+                .resize(out.stacks.len(), vec![0; num_values]);
+        }
         out.populate_levels(&ROOT_ID, 0, None);
+        out.populate_level_stats();
         out
     }
 
+    fn populate_level_stats(&mut self) {
+        self.level_coverage = self
+            .levels
+            .iter()
+            .map(|level| {
+                level
+                    .iter()
+                    .map(|stack_id| self.stacks[*stack_id].width_factor)
+                    .sum()
+            })
+            .collect();
+        self.depth_below = vec![0; self.stacks.len()];
+        for level in self.levels.iter().rev() {
+            for stack_id in level.iter() {
+                self.depth_below[*stack_id] = self.stacks[*stack_id]
+                    .children
+                    .iter()
+                    .map(|child| self.depth_below[*child] + 1)
+                    .max()
+                    .unwrap_or(0);
+            }
+        }
+    }
+
+    /// Names of the values of each line, empty if lines have a single count.
+    pub fn value_names(&self) -> &[String] {
+        &self.value_names
+    }
+
+    /// Index into [`Self::value_names`] of the value weighting the frames.
+    pub fn value_column(&self) -> usize {
+        self.value_column
+    }
+
+    /// Weight the frames by another of the values of each line, keeping stack ids so that
+    /// selection and zoom stay valid. The diff against a baseline needs to be set again.
+    pub fn set_value_column(&mut self, column: usize) {
+        if self.self_values.is_empty() || column >= self.value_names.len() {
+            return;
+        }
+        self.value_column = column;
+        for stack in self.stacks.iter_mut() {
+            stack.self_count = self.self_values[stack.id][column];
+        }
+        for level in self.levels.iter().rev() {
+            for stack_id in level.iter() {
+                let children_count = self.stacks[*stack_id]
+                    .children
+                    .iter()
+                    .map(|child| self.stacks[*child].total_count)
+                    .sum::<u64>();
+                let stack = &mut self.stacks[*stack_id];
+                stack.total_count = stack.self_count + children_count;
+            }
+        }
+        self.truncated_count = self
+            .truncated_ids
+            .iter()
+            .map(|stack_id| self.stacks[*stack_id].self_count)
+            .sum();
+
+        // Summarized counts, counting the total of a name once per stack as when parsing
+        let mut counts: HashMap<String, Count> = HashMap::new();
+        let mut to_visit = vec![(ROOT_ID, HashSet::<&str>::new())];
+        while let Some((stack_id, mut names_above)) = to_visit.pop() {
+            let stack = &self.stacks[stack_id];
+            if stack_id != ROOT_ID {
+                let short_name = self.get_stack_short_name_from_info(stack);
+                let count = counts.entry(short_name.to_string()).or_default();
+                count.own += stack.self_count;
+                if names_above.insert(short_name) {
+                    count.total += stack.total_count;
+                }
+            }
+            for child in stack.children.iter() {
+                to_visit.push((*child, names_above.clone()));
+            }
+        }
+        let sorted_column = self.ordered_stacks.sorted_column;
+        self.ordered_stacks = FlameGraph::get_ordered_stacks(&counts);
+        self.ordered_stacks.set_sort_column(sorted_column);
+
+        self.levels.clear();
+        self.populate_levels(&ROOT_ID, 0, None);
+        self.set_width_metric(self.width_metric);
+        if self.hits.is_some() {
+            self.hits = Some(Hits {
+                coverage_count: self._count_hit_coverage(ROOT_ID),
+                ids: self._collect_hit_ids(),
+            });
+        }
+    }
+
+    pub fn width_metric(&self) -> WidthMetric {
+        self.width_metric
+    }
+
+    /// Lay out the frames by another width metric. Stack ids and counts stay the same.
+    pub fn set_width_metric(&mut self, width_metric: WidthMetric) {
+        self.width_metric = width_metric;
+        self.width_counts.clear();
+        if width_metric == WidthMetric::Own {
+            self.width_counts = vec![0; self.stacks.len()];
+            for level in self.levels.iter().rev() {
+                for stack_id in level.iter() {
+                    let stack = &self.stacks[*stack_id];
+                    let children_width_count = stack
+                        .children
+                        .iter()
+                        .map(|child| self.width_counts[*child])
+                        .sum::<u64>();
+                    self.width_counts[*stack_id] = stack.self_count.max(children_width_count);
+                }
+            }
+        }
+        for level in self.levels.iter() {
+            for stack_id in level.iter() {
+                let stack = &self.stacks[*stack_id];
+                let width_factor = match stack.parent.and_then(|parent| self.get_stack(&parent)) {
+                    Some(parent) if self.width_count(parent) > 0 => {
+                        parent.width_factor * self.width_count(stack) as f64
+                            / self.width_count(parent) as f64
+                    }
+                    Some(_) => 0.0,
+                    None => 1.0,
+                };
+                self.stacks[*stack_id].width_factor = width_factor;
+            }
+        }
+        self.populate_level_stats();
+    }
+
+    /// Count the width of the stack is proportional to given the [`WidthMetric`].
+    pub fn width_count(&self, stack: &StackInfo) -> u64 {
+        self.width_counts
+            .get(stack.id)
+            .copied()
+            .unwrap_or(stack.total_count)
+    }
+
+    /// Share of the width of the stack not covered by its children.
+    pub fn self_width_share(&self, stack: &StackInfo) -> f64 {
+        let width_count = self.width_count(stack);
+        if width_count == 0 {
+            return 0.0;
+        }
+        let children_width_count = stack
+            .children
+            .iter()
+            .filter_map(|child| self.get_stack(child))
+            .map(|child| self.width_count(child))
+            .sum::<u64>();
+        width_count.saturating_sub(children_width_count) as f64 / width_count as f64
+    }
+
     fn get_ordered_stacks(counts: &HashMap<String, Count>) -> Ordered {
         let mut counts = counts.iter().collect::<Vec<_>>();
         counts.sort_by_key(|(short_name, count)| (count.own, short_name.to_string()));
@@ -391,7 +810,7 @@ impl FlameGraph {
 
     pub fn get_stack_short_name_from_info(&self, stack: &StackInfo) -> &str {
         if stack.id == ROOT_ID {
-            ROOT
+            self.root_name()
         } else {
             &self.data[stack.start_index..stack.end_index]
         }
@@ -399,12 +818,39 @@ impl FlameGraph {
 
     pub fn get_stack_full_name_from_info(&self, stack: &StackInfo) -> &str {
         if stack.id == ROOT_ID {
-            ROOT
+            self.root_name()
         } else {
             &self.data[stack.line_index..stack.end_index]
         }
     }
 
+    /// Name of the root frame, `all` unless labeled or promoted from a single top-level frame.
+    pub fn root_name(&self) -> &str {
+        self.root_name.as_deref().unwrap_or(ROOT)
+    }
+
+    /// Frames the root stands for when all stacks start with them, e.g. `run;event_loop`.
+    pub fn root_prefix(&self) -> Option<&str> {
+        self.root_prefix.as_deref()
+    }
+
+    /// Whether the frames all stacks start with are collapsed into the root.
+    pub fn collapses_common_prefix(&self) -> bool {
+        self.parse_options.collapse_common_prefix
+    }
+
+    /// Parse the same stacks again with or without collapsing the frames all of them start with
+    /// into the root, keeping the value weighting the frames.
+    pub fn with_collapsed_common_prefix(&self, collapse: bool) -> FlameGraph {
+        let options = ParseOptions {
+            collapse_common_prefix: collapse,
+            ..self.parse_options.clone()
+        };
+        let mut flamegraph = Self::from_string_with_options(self.data.clone(), &options);
+        flamegraph.set_value_column(self.value_column);
+        flamegraph
+    }
+
     pub fn get_stack_by_full_name(&self, full_name: &str) -> Option<&StackInfo> {
         self.stacks
             .iter()
@@ -431,6 +877,47 @@ impl FlameGraph {
         self.levels.len()
     }
 
+    /// Ids and full names of all the stacks, excluding the root.
+    pub fn stack_full_names(&self) -> impl Iterator<Item = (StackIdentifier, &str)> {
+        self.stacks
+            .iter()
+            .skip(1)
+            .map(|stack| (stack.id, self.get_stack_full_name_from_info(stack)))
+    }
+
+    /// Number of frames in the graph, excluding the root.
+    pub fn num_stacks(&self) -> usize {
+        self.stacks.len() - 1
+    }
+
+    /// Number of frames with a smaller share of the total than `share`.
+    pub fn count_stacks_narrower_than(&self, share: f64) -> usize {
+        self.stacks
+            .iter()
+            .filter(|stack| stack.width_factor < share)
+            .count()
+    }
+
+    /// Number of levels below the stack, 0 for leaves.
+    pub fn get_depth_below(&self, stack_id: &StackIdentifier) -> usize {
+        self.depth_below.get(*stack_id).copied().unwrap_or(0)
+    }
+
+    pub fn malformed_line_count(&self) -> usize {
+        self.malformed_line_count
+    }
+
+    /// Line numbers and reasons of the first malformed lines, see [`Self::malformed_line_count`]
+    /// for how many there are in total.
+    pub fn parse_diagnostics(&self) -> &[ParseDiagnostic] {
+        &self.parse_diagnostics
+    }
+
+    /// Share of the total width covered by each level, from the root level down.
+    pub fn level_coverage(&self) -> &[f64] {
+        &self.level_coverage
+    }
+
     pub fn get_ancestors(&self, stack_id: &StackIdentifier) -> Vec<StackIdentifier> {
         let mut ancestors = vec![];
         let mut current_id = *stack_id;
@@ -459,14 +946,26 @@ impl FlameGraph {
 
     pub fn set_hits(&mut self, p: &SearchPattern) {
         self.stacks.iter_mut().for_each(|stack| {
-            stack.hit =
-                p.re.is_match(&self.data[stack.start_index..stack.end_index]);
+            let start_index = match p.scope {
+                SearchScope::Frame => stack.start_index,
+                SearchScope::Path => stack.line_index,
+            };
+            stack.hit = p.re.is_match(&self.data[start_index..stack.end_index]);
         });
         self.hits = Some(Hits {
             coverage_count: self._count_hit_coverage(ROOT_ID),
             ids: self._collect_hit_ids(),
         });
-        self.ordered_stacks.set_search_pattern(p);
+        let hit_names = match p.scope {
+            SearchScope::Frame => HashSet::new(),
+            SearchScope::Path => self
+                .stacks
+                .iter()
+                .filter(|stack| stack.hit)
+                .map(|stack| self.get_stack_short_name_from_info(stack).to_string())
+                .collect(),
+        };
+        self.ordered_stacks.set_search_pattern(p, &hit_names);
     }
 
     pub fn clear_hits(&mut self) {
@@ -475,6 +974,229 @@ impl FlameGraph {
         self.ordered_stacks.clear_search_pattern();
     }
 
+    pub fn set_highlights(&mut self, patterns: &[SearchPattern]) {
+        if patterns.is_empty() {
+            self.highlights.clear();
+            return;
+        }
+        self.highlights = self
+            .stacks
+            .iter()
+            .map(|stack| {
+                let short_name = &self.data[stack.start_index..stack.end_index];
+                patterns.iter().position(|p| p.re.is_match(short_name))
+            })
+            .collect();
+    }
+
+    /// Index of the first highlight pattern matching the stack, if any.
+    pub fn get_highlight(&self, stack_id: &StackIdentifier) -> Option<usize> {
+        self.highlights.get(*stack_id).copied().flatten()
+    }
+
+    /// Dim stacks based on whether they are in the subtree of a stack matching the pattern.
+    pub fn set_focus(&mut self, p: &SearchPattern, mode: FocusMode) {
+        let mut in_subtree = vec![false; self.stacks.len()];
+        for level in self.levels.iter() {
+            for stack_id in level.iter() {
+                let stack = &self.stacks[*stack_id];
+                in_subtree[*stack_id] = stack.parent.is_some_and(|parent| in_subtree[parent])
+                    || p.re
+                        .is_match(&self.data[stack.start_index..stack.end_index]);
+            }
+        }
+        self.dimmed = match mode {
+            FocusMode::Focus => in_subtree.into_iter().map(|x| !x).collect(),
+            FocusMode::Exclude => in_subtree,
+        };
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.dimmed.clear();
+    }
+
+    pub fn is_dimmed(&self, stack_id: &StackIdentifier) -> bool {
+        self.dimmed.get(*stack_id).copied().unwrap_or(false)
+    }
+
+    /// Merge the callers and callees of all frames with the given name. Recursive occurrences
+    /// below another occurrence are already counted by the outer one and are skipped.
+    pub fn get_butterfly(&self, short_name: &str) -> Option<Butterfly> {
+        let occurrences = self
+            .stacks
+            .iter()
+            .filter(|stack| stack.id != ROOT_ID)
+            .filter(|stack| self.get_stack_short_name_from_info(stack) == short_name)
+            .filter(|stack| {
+                !self
+                    .get_ancestors(&stack.id)
+                    .iter()
+                    .filter(|ancestor| **ancestor != stack.id)
+                    .any(|ancestor| self.get_stack_short_name(ancestor) == Some(short_name))
+            })
+            .collect::<Vec<_>>();
+        if occurrences.is_empty() {
+            return None;
+        }
+
+        let mut callers = MergedCallTree::new(short_name);
+        let mut callees = MergedCallTree::new(short_name);
+        for stack in occurrences {
+            callers.total_count += stack.total_count;
+            let mut node = &mut callers;
+            let mut parent = stack.parent.filter(|parent| *parent != ROOT_ID);
+            while let Some(parent_stack) = parent.and_then(|parent| self.get_stack(&parent)) {
+                node = node.child_mut(self.get_stack_short_name_from_info(parent_stack));
+                node.total_count += stack.total_count;
+                parent = parent_stack.parent.filter(|parent| *parent != ROOT_ID);
+            }
+            self.merge_callees(&mut callees, stack);
+        }
+        callers.sort();
+        callees.sort();
+        Some(Butterfly { callers, callees })
+    }
+
+    fn merge_callees(&self, node: &mut MergedCallTree, stack: &StackInfo) {
+        node.total_count += stack.total_count;
+        for child in stack.children.iter().filter_map(|id| self.get_stack(id)) {
+            let name = self.get_stack_short_name_from_info(child);
+            self.merge_callees(node.child_mut(name), child);
+        }
+    }
+
+    /// Find frames whose subtrees (frame names and shape, regardless of counts) appear in more
+    /// than one place, sorted by combined total count. Duplicates that only ever appear as the
+    /// child of a larger duplicated subtree are left out as they are already covered by it.
+    pub fn find_duplicate_subtrees(&self) -> Vec<DuplicateSubtree> {
+        let mut shape_ids: HashMap<(&str, Vec<usize>), usize> = HashMap::new();
+        let mut shape_of = vec![0; self.stacks.len()];
+        for level in self.levels.iter().skip(1).rev() {
+            for stack_id in level.iter() {
+                let stack = &self.stacks[*stack_id];
+                let mut children = stack
+                    .children
+                    .iter()
+                    .map(|child| shape_of[*child])
+                    .collect::<Vec<_>>();
+                children.sort_unstable();
+                let key = (self.get_stack_short_name_from_info(stack), children);
+                let next_id = shape_ids.len();
+                shape_of[*stack_id] = *shape_ids.entry(key).or_insert(next_id);
+            }
+        }
+
+        let mut occurrences: HashMap<usize, Vec<StackIdentifier>> = HashMap::new();
+        for level in self.levels.iter().skip(1) {
+            for stack_id in level.iter() {
+                occurrences
+                    .entry(shape_of[*stack_id])
+                    .or_default()
+                    .push(*stack_id);
+            }
+        }
+
+        let mut duplicates = occurrences
+            .values()
+            .filter(|stack_ids| stack_ids.len() > 1)
+            .filter(|stack_ids| {
+                let parent_shapes = stack_ids
+                    .iter()
+                    .map(|stack_id| match self.stacks[*stack_id].parent {
+                        Some(parent) if parent != ROOT_ID => Some(shape_of[parent]),
+                        _ => None,
+                    })
+                    .collect::<HashSet<_>>();
+                // Skip subtrees always found under the same duplicated parent subtree
+                parent_shapes.len() != 1 || parent_shapes.contains(&None)
+            })
+            .map(|stack_ids| DuplicateSubtree {
+                name: self
+                    .get_stack_short_name_from_info(&self.stacks[stack_ids[0]])
+                    .to_string(),
+                occurrences: stack_ids.len(),
+                total_count: stack_ids
+                    .iter()
+                    .map(|stack_id| self.stacks[*stack_id].total_count)
+                    .sum(),
+            })
+            .collect::<Vec<_>>();
+        duplicates.sort_by(|a, b| {
+            b.total_count
+                .cmp(&a.total_count)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        duplicates
+    }
+
+    pub fn snapshot_baseline(&self) -> Baseline {
+        Baseline {
+            counts: self
+                .stacks
+                .iter()
+                .map(|stack| {
+                    (
+                        self.get_stack_full_name_from_info(stack).to_string(),
+                        stack.total_count,
+                    )
+                })
+                .collect(),
+            total_count: self.total_count(),
+            created_at: std::time::Instant::now(),
+        }
+    }
+
+    /// Compute how much each stack's share of the total samples changed since the baseline.
+    /// Stacks absent from the baseline are compared against zero.
+    pub fn set_diff(&mut self, baseline: &Baseline) {
+        let share = |count: u64, total: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / total as f64
+            }
+        };
+        let total_count = self.total_count();
+        self.diff = self
+            .stacks
+            .iter()
+            .map(|stack| {
+                let baseline_count = baseline
+                    .counts
+                    .get(self.get_stack_full_name_from_info(stack))
+                    .copied()
+                    .unwrap_or(0);
+                share(stack.total_count, total_count) - share(baseline_count, baseline.total_count)
+            })
+            .collect();
+        self.max_abs_diff = self.diff.iter().fold(0.0, |acc, d| f64::max(acc, d.abs()));
+    }
+
+    pub fn clear_diff(&mut self) {
+        self.diff.clear();
+        self.max_abs_diff = 0.0;
+    }
+
+    /// Change in percentage points of the stack's share of all samples against the baseline.
+    pub fn get_diff(&self, stack_id: &StackIdentifier) -> Option<f64> {
+        self.diff.get(*stack_id).copied()
+    }
+
+    /// Largest absolute change against the baseline across all stacks.
+    pub fn max_abs_diff(&self) -> f64 {
+        self.max_abs_diff
+    }
+
+    /// Number of samples whose stack contains a truncation marker.
+    pub fn truncated_count(&self) -> u64 {
+        self.truncated_count
+    }
+
+    /// Whether the stack is the leaf of a truncated stack, so its depth is not to be trusted.
+    pub fn is_truncated_leaf(&self, stack_id: &StackIdentifier) -> bool {
+        self.truncated_ids.contains(stack_id)
+    }
+
     pub fn hit_coverage_count(&self) -> Option<u64> {
         self.hits.as_ref().map(|h| h.coverage_count)
     }
@@ -513,6 +1235,7 @@ impl FlameGraph {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::formats::{CommMode, DEFAULT_TRUNCATION_MARKER};
 
     const UPDATE_FIXTURES: bool = false;
 
@@ -589,7 +1312,9 @@ mod tests {
         let expected = std::fs::read_to_string(&filename).unwrap();
         assert_eq!(serialized, expected);
 
-        assert_eq!(UPDATE_FIXTURES, false, "Set UPDATE_FIXTURES to false");
+        if UPDATE_FIXTURES {
+            panic!("Fixtures updated, set UPDATE_FIXTURES back to false");
+        }
         fg
     }
 
@@ -630,4 +1355,470 @@ mod tests {
     fn test_recursive() {
         check_result("tests/data/recursive.txt");
     }
+
+    #[test]
+    fn test_comm_mode() {
+        let content = "python3;main;work 10\nswapper/0;idle 5\nswapper/1;idle 7\nbash 3\n";
+        let parse = |comm| {
+            FlameGraph::from_string_with_options(
+                content.to_string(),
+                &ParseOptions {
+                    comm: Some(comm),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let fg = parse(CommMode::Group);
+        assert_eq!(fg.total_count(), 25);
+        assert_eq!(
+            fg.get_stack_by_full_name("swapper;idle")
+                .unwrap()
+                .total_count,
+            12
+        );
+        assert!(fg.get_stack_by_full_name("python3;main;work").is_some());
+
+        let fg = parse(CommMode::Strip);
+        assert_eq!(fg.total_count(), 25);
+        assert_eq!(fg.get_stack_by_full_name("idle").unwrap().total_count, 12);
+        assert!(fg.get_stack_by_full_name("main;work").is_some());
+        // A lone command frame is kept so that its samples are not dropped
+        assert_eq!(fg.get_stack_by_full_name("bash").unwrap().total_count, 3);
+    }
+
+    #[test]
+    fn test_root_label_and_promotion() {
+        let content = "python;main;work 10\npython;main 5\npython 2\n";
+        let parse = |root_label: Option<&str>, promote_single_root| {
+            FlameGraph::from_string_with_options(
+                content.to_string(),
+                &ParseOptions {
+                    root_label: root_label.map(|label| label.to_string()),
+                    promote_single_root,
+                    ..Default::default()
+                },
+            )
+        };
+
+        let fg = parse(Some("my app"), false);
+        assert_eq!(fg.get_stack_short_name(&ROOT_ID), Some("my app"));
+        assert!(fg.get_stack_by_full_name("python;main").is_some());
+
+        // The single top-level frame becomes the root, keeping its own samples
+        let fg = parse(None, true);
+        assert_eq!(fg.get_stack_short_name(&ROOT_ID), Some("python"));
+        assert_eq!((fg.total_count(), fg.root().self_count), (17, 2));
+        let main = fg.get_stack_by_full_name("python;main").unwrap();
+        assert_eq!((main.level, main.parent), (1, Some(ROOT_ID)));
+        assert_eq!(fg.get_stack_short_name_from_info(main), "main");
+        assert_eq!(fg.get_num_levels(), 3);
+        assert_eq!(parse(Some("my app"), true).root_name(), "my app");
+
+        // Nothing to promote with several top-level frames
+        let fg = FlameGraph::from_string_with_options(
+            "a;b 1\nc 1\n".to_string(),
+            &ParseOptions {
+                promote_single_root: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(fg.root_name(), ROOT);
+        assert_eq!(fg.root().children.len(), 2);
+    }
+
+    #[test]
+    fn test_collapse_common_prefix() {
+        let content = "run;loop 3\nrun;loop;poll;a 10\nrun;loop;poll;b;c 5\nrun 2\n";
+        let fg = FlameGraph::from_string_with_options(
+            content.to_string(),
+            &ParseOptions {
+                collapse_common_prefix: true,
+                ..Default::default()
+            },
+        );
+        assert_eq!(fg.root_prefix(), Some("run;loop;poll"));
+        // Samples ending in the collapsed frames are the root's own
+        assert_eq!((fg.total_count(), fg.root().self_count), (20, 5));
+        let b = fg.get_stack_by_full_name("run;loop;poll;b").unwrap();
+        assert_eq!((b.level, b.parent), (1, Some(ROOT_ID)));
+        assert_eq!(fg.get_num_levels(), 3);
+
+        // Expanding parses the stacks again as they were
+        let fg = fg.with_collapsed_common_prefix(false);
+        assert_eq!((fg.root_prefix(), fg.root_name()), (None, ROOT));
+        assert_eq!(fg.get_num_levels(), 6);
+        assert_eq!(fg.with_collapsed_common_prefix(true).get_num_levels(), 3);
+    }
+
+    #[test]
+    fn test_width_metric_own() {
+        let content = "main;parse;tokenize 30\nmain;parse 50\nmain;render 20\n".to_string();
+        let mut fg = FlameGraph::from_string(content, false);
+        let width_factor = |fg: &FlameGraph, name| {
+            let stack = fg.get_stack_by_full_name(name).unwrap();
+            (stack.width_factor * 100.0).round() as u64
+        };
+        assert_eq!(width_factor(&fg, "main;parse"), 80);
+
+        // The self count of parse outweighs its child, render is only as wide as its self count
+        fg.set_width_metric(WidthMetric::Own);
+        assert_eq!(fg.width_count(fg.root()), 70);
+        assert_eq!(width_factor(&fg, "main;parse"), 71);
+        assert_eq!(width_factor(&fg, "main;parse;tokenize"), 43);
+        assert_eq!(width_factor(&fg, "main;render"), 29);
+        let parse = fg.get_stack_by_full_name("main;parse").unwrap();
+        assert!((fg.self_width_share(parse) - 0.4).abs() < 1e-9);
+        // Counts stay as they are
+        assert_eq!(parse.total_count, 80);
+
+        fg.set_width_metric(WidthMetric::Total);
+        assert_eq!(width_factor(&fg, "main;parse"), 80);
+        assert_eq!(fg.width_count(fg.root()), 100);
+    }
+
+    #[test]
+    fn test_reverse_stacks() {
+        let content = "work;main 10\nidle;swapper/0 5\n";
+        let fg = FlameGraph::from_string_with_options(
+            content.to_string(),
+            &ParseOptions {
+                reverse_stacks: true,
+                comm: Some(CommMode::Group),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            fg.get_stack_by_full_name("main;work").unwrap().total_count,
+            10
+        );
+        assert_eq!(
+            fg.get_stack_by_full_name("swapper;idle")
+                .unwrap()
+                .total_count,
+            5
+        );
+        assert!(fg.get_stack_by_full_name("work").is_none());
+    }
+
+    #[test]
+    fn test_truncation_marker() {
+        let content = "main;a;b 10\n[truncated];c;d 4\n0x7f00ab;e 2\nmain;0x12 1\n";
+        let fg = FlameGraph::from_string_with_options(
+            content.to_string(),
+            &ParseOptions {
+                truncation_marker: Some(regex::Regex::new(DEFAULT_TRUNCATION_MARKER).unwrap()),
+                ..Default::default()
+            },
+        );
+        assert_eq!(fg.truncated_count(), 7);
+        let id = |name| fg.get_stack_id_by_full_name(name).unwrap();
+        assert!(fg.is_truncated_leaf(&id("[truncated];c;d")));
+        assert!(fg.is_truncated_leaf(&id("0x7f00ab;e")));
+        assert!(!fg.is_truncated_leaf(&id("[truncated];c")));
+        assert!(!fg.is_truncated_leaf(&id("main;a;b")));
+
+        let fg = FlameGraph::from_string(content.to_string(), false);
+        assert_eq!(fg.truncated_count(), 0);
+    }
+
+    #[test]
+    fn test_diff_against_baseline() {
+        let baseline = FlameGraph::from_string("a;b 5\na;c 5\n".to_string(), false);
+        let mut fg = FlameGraph::from_string("a;b 15\na;c 5\nd 20\n".to_string(), false);
+        fg.set_diff(&baseline.snapshot_baseline());
+        let diff = |name| fg.get_diff(&fg.get_stack_id_by_full_name(name).unwrap());
+        assert_eq!(diff("a"), Some(-50.0));
+        assert_eq!(diff("a;b"), Some(-12.5));
+        assert_eq!(diff("a;c"), Some(-37.5));
+        assert_eq!(diff("d"), Some(50.0));
+        assert_eq!(fg.max_abs_diff(), 50.0);
+
+        fg.clear_diff();
+        assert_eq!(fg.get_diff(&ROOT_ID), None);
+    }
+
+    #[test]
+    fn test_highlights_use_first_matching_pattern() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let mut fg = FlameGraph::from_string(content, false);
+        let patterns = [
+            SearchPattern::new("level2-a", false, true).unwrap(),
+            SearchPattern::new("level2", true, true).unwrap(),
+        ];
+        fg.set_highlights(&patterns);
+        let level2_a = fg.get_stack_id_by_full_name("level1-a;level2-a").unwrap();
+        let level2_b = fg.get_stack_id_by_full_name("level1-a;level2-b").unwrap();
+        let level1_a = fg.get_stack_id_by_full_name("level1-a").unwrap();
+        assert_eq!(fg.get_highlight(&level2_a), Some(0));
+        assert_eq!(fg.get_highlight(&level2_b), Some(1));
+        assert_eq!(fg.get_highlight(&level1_a), None);
+
+        fg.set_highlights(&[]);
+        assert_eq!(fg.get_highlight(&level2_a), None);
+    }
+
+    #[test]
+    fn test_search_full_paths() {
+        let content = [
+            "main;serialize;malloc 10",
+            "main;parse;malloc 5",
+            "main;serialize;write 3",
+        ]
+        .join("\n");
+        let mut fg = FlameGraph::from_string(content, false);
+        let p = SearchPattern::new("serialize;.*malloc$", true, true)
+            .unwrap()
+            .with_scope(SearchScope::Path);
+        fg.set_hits(&p);
+        let hit = fg
+            .get_stack_id_by_full_name("main;serialize;malloc")
+            .unwrap();
+        assert_eq!(fg.hit_ids(), Some(&vec![hit]));
+        assert_eq!(fg.hit_coverage_count(), Some(10));
+        // The table view shows the functions hit somewhere
+        let visible = fg
+            .ordered_stacks
+            .entries
+            .iter()
+            .filter(|entry| entry.visible)
+            .map(|entry| entry.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(visible, vec!["malloc"]);
+
+        // The same pattern never matches a single frame name
+        fg.set_hits(&SearchPattern::new("serialize;.*malloc$", true, true).unwrap());
+        assert_eq!(fg.hit_coverage_count(), Some(0));
+    }
+
+    #[test]
+    fn test_skips_blank_comment_and_malformed_lines() {
+        let content = [
+            "# header",
+            "",
+            "   ",
+            "  # indented comment",
+            "a;b\t10",
+            "a;c   5  ",
+            "a;b 1 # trailing",
+            "not a stack",
+            "a;d ten",
+        ]
+        .join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        assert_eq!(fg.total_count(), 16);
+        assert_eq!(fg.get_stack_by_full_name("a;b").unwrap().total_count, 11);
+        assert_eq!(fg.get_stack_by_full_name("a;c").unwrap().total_count, 5);
+        assert_eq!(fg.malformed_line_count(), 2);
+        let line_numbers = fg
+            .parse_diagnostics()
+            .iter()
+            .map(|diagnostic| diagnostic.line_number)
+            .collect::<Vec<_>>();
+        assert_eq!(line_numbers, vec![8, 9]);
+    }
+
+    #[test]
+    fn test_count_stacks_narrower_than() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let fg = FlameGraph::from_string(content, false);
+        assert_eq!(fg.num_stacks(), 7);
+        // level2-a, level2-c and level2-d each have 10 of the 70 samples
+        assert_eq!(fg.count_stacks_narrower_than(0.2), 3);
+        assert_eq!(fg.count_stacks_narrower_than(0.1), 0);
+    }
+
+    #[test]
+    fn test_depth_below() {
+        let fg = FlameGraph::from_string("a;b;c 3\na;d 1\n".to_string(), false);
+        assert_eq!(fg.get_depth_below(&ROOT_ID), 3);
+        let a = fg.get_stack_id_by_full_name("a").unwrap();
+        let d = fg.get_stack_id_by_full_name("a;d").unwrap();
+        assert_eq!(fg.get_depth_below(&a), 2);
+        assert_eq!(fg.get_depth_below(&d), 0);
+    }
+
+    #[test]
+    fn test_level_coverage() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let fg = FlameGraph::from_string(content, false);
+        let approx_eq = |a: &[f64], b: &[f64]| {
+            a.len() == b.len() && a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-9)
+        };
+        assert!(approx_eq(fg.level_coverage(), &[1.0, 1.0, 1.0]));
+
+        let fg = FlameGraph::from_string("a;b 3\na 1\n".to_string(), false);
+        assert!(approx_eq(fg.level_coverage(), &[1.0, 1.0, 0.75]));
+    }
+
+    #[test]
+    fn test_self_count_excludes_children() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let fg = FlameGraph::from_string(content, false);
+        for stack in fg.stacks.iter() {
+            let children_count: u64 = stack
+                .children
+                .iter()
+                .map(|child| fg.get_stack(child).unwrap().total_count)
+                .sum();
+            assert_eq!(stack.self_count, stack.total_count - children_count);
+        }
+    }
+
+    #[test]
+    fn test_set_value_column() {
+        let content = [
+            "main;parse;alloc 2 30",
+            "main;parse 3 0",
+            "main;render 5 10",
+            "main;render;main 1 1",
+        ]
+        .join("\n");
+        let options = ParseOptions {
+            sorted: true,
+            value_names: vec!["cpu".to_string(), "wall".to_string()],
+            value_column: 1,
+            ..Default::default()
+        };
+        let mut fg = FlameGraph::from_string_with_options(content, &options);
+        assert_eq!(fg.malformed_line_count(), 0);
+        assert_eq!(fg.total_count(), 41);
+        let parse_id = fg.get_stack_id_by_full_name("main;parse").unwrap();
+        let render_id = fg.get_stack_id_by_full_name("main;render").unwrap();
+        assert_eq!(fg.get_stack(&parse_id).unwrap().total_count, 30);
+        assert_eq!(
+            fg.get_stacks_at_level(2).unwrap(),
+            &vec![parse_id, render_id]
+        );
+
+        fg.set_value_column(0);
+        assert_eq!(fg.value_column(), 0);
+        assert_eq!(fg.total_count(), 11);
+        let parse = fg.get_stack(&parse_id).unwrap();
+        assert_eq!((parse.total_count, parse.self_count), (5, 3));
+        assert_eq!(fg.get_stack(&render_id).unwrap().total_count, 6);
+        // Siblings are sorted by the new weight
+        assert_eq!(
+            fg.get_stacks_at_level(2).unwrap(),
+            &vec![render_id, parse_id]
+        );
+        // "main" is counted once per stack in the summary
+        let main = fg
+            .ordered_stacks
+            .entries
+            .iter()
+            .find(|entry| entry.name == "main")
+            .unwrap();
+        assert_eq!((main.count.total, main.count.own), (11, 1));
+
+        // Out of range columns are ignored
+        fg.set_value_column(2);
+        assert_eq!(fg.value_column(), 0);
+    }
+
+    #[test]
+    fn test_get_butterfly() {
+        let content = [
+            "main;a;util;hash 10",
+            "main;b;util;hash 5",
+            "main;b;util 3",
+            "main;b;util;util;copy 2",
+            "main;c 4",
+        ]
+        .join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        assert_eq!(fg.get_butterfly("missing"), None);
+
+        let tree = |name: &str, total_count: u64, children: Vec<MergedCallTree>| MergedCallTree {
+            name: name.to_string(),
+            total_count,
+            children,
+        };
+        let butterfly = fg.get_butterfly("util").unwrap();
+        assert_eq!(
+            butterfly.callers,
+            tree(
+                "util",
+                20,
+                vec![
+                    tree("a", 10, vec![tree("main", 10, vec![])]),
+                    tree("b", 10, vec![tree("main", 10, vec![])]),
+                ]
+            )
+        );
+        // The recursive call is merged as a callee rather than counted again
+        assert_eq!(
+            butterfly.callees,
+            tree(
+                "util",
+                20,
+                vec![
+                    tree("hash", 15, vec![]),
+                    tree("util", 2, vec![tree("copy", 2, vec![])]),
+                ]
+            )
+        );
+        assert_eq!(butterfly.callees.num_frames(), 4);
+    }
+
+    #[test]
+    fn test_find_duplicate_subtrees() {
+        let content = [
+            "main;a;work;parse;alloc 10",
+            "main;a;work;emit 5",
+            "main;b;work;parse;alloc 20",
+            "main;b;work;emit 5",
+            "main;c;work 5",
+            "main;c;alloc 1",
+            "main;d;work;parse;alloc 7",
+        ]
+        .join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        // "emit" only appears under identical "work" subtrees so it is covered by them
+        assert_eq!(
+            fg.find_duplicate_subtrees(),
+            vec![
+                DuplicateSubtree {
+                    name: "work".to_string(),
+                    occurrences: 2,
+                    total_count: 40,
+                },
+                DuplicateSubtree {
+                    name: "alloc".to_string(),
+                    occurrences: 4,
+                    total_count: 38,
+                },
+                DuplicateSubtree {
+                    name: "parse".to_string(),
+                    occurrences: 3,
+                    total_count: 37,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_focus_and_exclude_subtrees() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let mut fg = FlameGraph::from_string(content, false);
+        let level1_a = fg.get_stack_id_by_full_name("level1-a").unwrap();
+        let level2_a = fg.get_stack_id_by_full_name("level1-a;level2-a").unwrap();
+        let level1_b = fg.get_stack_id_by_full_name("level1-b").unwrap();
+        let p = SearchPattern::new("level1-a", false, false).unwrap();
+
+        fg.set_focus(&p, FocusMode::Focus);
+        assert!(fg.is_dimmed(&ROOT_ID));
+        assert!(!fg.is_dimmed(&level1_a));
+        assert!(!fg.is_dimmed(&level2_a));
+        assert!(fg.is_dimmed(&level1_b));
+
+        fg.set_focus(&p, FocusMode::Exclude);
+        assert!(!fg.is_dimmed(&ROOT_ID));
+        assert!(fg.is_dimmed(&level1_a));
+        assert!(fg.is_dimmed(&level2_a));
+        assert!(!fg.is_dimmed(&level1_b));
+
+        fg.clear_focus();
+        assert!(!fg.is_dimmed(&level1_a));
+    }
 }
@@ -0,0 +1,321 @@
+//! Importers turning profiler output into the folded stacks format parsed by
+//! [`FlameGraph`](crate::flame::FlameGraph).
+
+use std::borrow::Cow;
+
+/// How to treat the leading process command name (`comm`) frame emitted by tools such as bcc's
+/// `profile -f`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommMode {
+    /// Keep one top-level frame per command. Per-CPU / per-worker kernel thread names such as
+    /// `swapper/0` or `kworker/3:1` are grouped under their base name.
+    Group,
+    /// Drop the command name frame so that all processes are merged.
+    Strip,
+}
+
+/// Options controlling how folded stacks are parsed.
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    /// Whether to sort the stacks by time spent
+    pub sorted: bool,
+    /// Treatment of a leading command name frame, if the input has one
+    pub comm: Option<CommMode>,
+    /// Frames marking a stack that was truncated by the profiler, e.g. at a maximum depth
+    pub truncation_marker: Option<regex::Regex>,
+    /// Whether the input stacks are leaf first (`callee;caller`) instead of the usual root first
+    /// (`caller;callee`) and need to be reversed
+    pub reverse_stacks: bool,
+    /// Names of the values ending each line when there are several, e.g. `cpu` and `wall` for
+    /// `main;parse 12 40`. Empty for the usual single count.
+    pub value_names: Vec<String>,
+    /// Index into `value_names` of the value weighting the frames
+    pub value_column: usize,
+    /// Label of the root frame instead of `all`
+    pub root_label: Option<String>,
+    /// Whether the top-level frame becomes the root when all stacks start with the same one,
+    /// e.g. a single process
+    pub promote_single_root: bool,
+    /// Whether the longest run of frames all stacks start with, e.g. an event loop and runtime
+    /// initialization, is collapsed into the root. Stacks ending within the run are counted as
+    /// the root's own samples.
+    pub collapse_common_prefix: bool,
+}
+
+/// Default marker for truncated stacks: an explicit `[truncated]` frame or an unresolved raw
+/// address such as perf's `0x7f3a2b1c`.
+pub const DEFAULT_TRUNCATION_MARKER: &str = r"^(\[truncated\]|0x[0-9a-fA-F]+)$";
+
+impl ParseOptions {
+    /// Number of values ending each line
+    pub fn num_values(&self) -> usize {
+        self.value_names.len().max(1)
+    }
+
+    pub(crate) fn rewrites_stacks(&self) -> bool {
+        self.comm.is_some() || self.reverse_stacks
+    }
+
+    fn rewrite_stack<'a>(&self, stack: &'a str) -> Cow<'a, str> {
+        if !self.reverse_stacks {
+            return self.rewrite_comm(stack);
+        }
+        let reversed = stack.split(';').rev().collect::<Vec<_>>().join(";");
+        Cow::Owned(self.rewrite_comm(&reversed).into_owned())
+    }
+
+    fn rewrite_comm<'a>(&self, stack: &'a str) -> Cow<'a, str> {
+        match self.comm {
+            Some(CommMode::Strip) => match stack.split_once(';') {
+                Some((_, rest)) => Cow::Borrowed(rest),
+                // Keep lone command frames so that their samples are not lost
+                None => Cow::Borrowed(stack),
+            },
+            Some(CommMode::Group) => {
+                let (comm, rest) = stack
+                    .split_once(';')
+                    .map_or((stack, None), |(comm, rest)| (comm, Some(rest)));
+                match (comm.split_once('/'), rest) {
+                    (Some((base, _)), Some(rest)) if !base.is_empty() => {
+                        Cow::Owned(format!("{};{}", base, rest))
+                    }
+                    (Some((base, _)), None) if !base.is_empty() => Cow::Borrowed(base),
+                    _ => Cow::Borrowed(stack),
+                }
+            }
+            None => Cow::Borrowed(stack),
+        }
+    }
+
+    pub(crate) fn rewrite_content(&self, content: &str) -> String {
+        let mut out = String::with_capacity(content.len());
+        for line in content.lines() {
+            match parse_line_values(line, self.num_values()) {
+                Some((stack, values)) if !line.trim_start().starts_with('#') => {
+                    out.push_str(&self.rewrite_stack(stack));
+                    for value in values {
+                        out.push(' ');
+                        out.push_str(&value.to_string());
+                    }
+                }
+                _ => out.push_str(line),
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Split a folded stacks line into the stack and its count. The count may be separated from the
+/// stack by any whitespace (e.g. a tab or several spaces, depending on the stackcollapse script)
+/// and be followed by trailing whitespace or a `#` comment.
+pub fn parse_line(line: &str) -> Option<(&str, u64)> {
+    fn split_count(line: &str) -> Option<(&str, u64)> {
+        let (stack, count) = line.rsplit_once(char::is_whitespace)?;
+        let count = count.parse::<u64>().ok()?;
+        let stack = stack.trim_end();
+        (!stack.is_empty()).then_some((stack, count))
+    }
+
+    let line = line.trim_end();
+    split_count(line).or_else(|| {
+        line.match_indices('#')
+            .filter(|(i, _)| line[..*i].ends_with(char::is_whitespace))
+            .find_map(|(i, _)| split_count(line[..i].trim_end()))
+    })
+}
+
+/// Split a folded stacks line ending with `num_values` whitespace separated values, e.g.
+/// `main;parse 12 40`, into the stack and the values.
+pub fn parse_line_values(line: &str, num_values: usize) -> Option<(&str, Vec<u64>)> {
+    let mut values = vec![0; num_values];
+    let mut stack = line;
+    for value in values.iter_mut().rev() {
+        (stack, *value) = parse_line(stack)?;
+    }
+    Some((stack, values))
+}
+
+/// Number of malformed lines whose details are kept, the rest are only counted.
+pub const MAX_PARSE_DIAGNOSTICS: usize = 5;
+
+/// Number of characters of a malformed line kept in its [`ParseDiagnostic`].
+const MAX_DIAGNOSTIC_CONTENT: usize = 80;
+
+/// A malformed line skipped while parsing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    /// Line number in the input, starting at 1
+    pub line_number: usize,
+    /// The line, cut short if it is long
+    pub content: String,
+    /// Why the line could not be parsed
+    pub reason: String,
+}
+
+impl ParseDiagnostic {
+    pub(crate) fn new(line_number: usize, line: &str, num_values: usize) -> Self {
+        let line = line.trim_end();
+        let mut content = line
+            .chars()
+            .take(MAX_DIAGNOSTIC_CONTENT)
+            .collect::<String>();
+        if content.len() < line.len() {
+            content.push('…');
+        }
+        Self {
+            line_number,
+            content,
+            reason: describe_malformed_line(line, num_values),
+        }
+    }
+}
+
+impl std::fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}: {}: `{}`",
+            self.line_number, self.reason, self.content
+        )
+    }
+}
+
+/// Why a line is not a stack followed by `num_values` counts.
+fn describe_malformed_line(line: &str, num_values: usize) -> String {
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    let num_counts = tokens
+        .iter()
+        .rev()
+        .take_while(|token| token.parse::<u64>().is_ok())
+        .count();
+    if num_counts >= tokens.len() {
+        "missing stack before the count".to_string()
+    } else if num_counts > 0 {
+        format!("expected {} counts, found {}", num_values, num_counts)
+    } else if tokens.len() > 1 && tokens[tokens.len() - 1].parse::<f64>().is_ok() {
+        "count is not a non-negative integer".to_string()
+    } else {
+        "missing count after the stack".to_string()
+    }
+}
+
+/// Top-level frame shared by all the stacks, if there is a single one.
+pub(crate) fn get_single_top_frame(content: &str, num_values: usize) -> Option<&str> {
+    let mut top_frames = content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .filter_map(|line| parse_line_values(line, num_values))
+        .map(|(stack, _)| stack.split(';').next().unwrap_or(stack));
+    let first = top_frames.next()?;
+    top_frames.all(|frame| frame == first).then_some(first)
+}
+
+/// Longest run of frames all the stacks start with, leaving at least one frame below it. Stacks
+/// that end within the run do not shorten it.
+pub(crate) fn get_common_prefix(content: &str, num_values: usize) -> Option<&str> {
+    let stacks = content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.trim().starts_with('#'))
+        .filter_map(|line| parse_line_values(line, num_values))
+        .map(|(stack, _)| (stack, stack.split(';').collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    // Start from the deepest stack so that stacks ending within the run are not mistaken for
+    // the end of it
+    let (stack, deepest) = stacks.iter().max_by_key(|(_, frames)| frames.len())?;
+    let mut len = deepest.len() - 1;
+    for (_, frames) in &stacks {
+        let shared = deepest
+            .iter()
+            .zip(frames)
+            .take_while(|(a, b)| a == b)
+            .count();
+        if shared < frames.len() {
+            len = len.min(shared);
+        }
+    }
+    if len == 0 {
+        return None;
+    }
+    let prefix_len = deepest[..len]
+        .iter()
+        .map(|frame| frame.len())
+        .sum::<usize>()
+        + len
+        - 1;
+    Some(&stack[..prefix_len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_separators() {
+        assert_eq!(parse_line("a;b 10"), Some(("a;b", 10)));
+        assert_eq!(parse_line("a;b\t10"), Some(("a;b", 10)));
+        assert_eq!(parse_line("a;b    10"), Some(("a;b", 10)));
+        assert_eq!(parse_line("a;b \t 10  \t"), Some(("a;b", 10)));
+        assert_eq!(parse_line("a;b 10\r"), Some(("a;b", 10)));
+        assert_eq!(parse_line("a;b 10 # sampled at 99Hz"), Some(("a;b", 10)));
+        assert_eq!(parse_line("a;b 10\t#note"), Some(("a;b", 10)));
+        assert_eq!(
+            parse_line("<module> (app.py:3);work (app.py:7) 5"),
+            Some(("<module> (app.py:3);work (app.py:7)", 5))
+        );
+        assert_eq!(parse_line("a;b"), None);
+        assert_eq!(parse_line("a;b ten"), None);
+        assert_eq!(parse_line(" 10"), None);
+        assert_eq!(parse_line("a;b -1"), None);
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let reason = |line: &str, num_values| ParseDiagnostic::new(1, line, num_values).reason;
+        assert_eq!(reason("a;b", 1), "missing count after the stack");
+        assert_eq!(reason("a;b ten", 1), "missing count after the stack");
+        assert_eq!(reason("a;b -1", 1), "count is not a non-negative integer");
+        assert_eq!(reason("a;b 1.5", 1), "count is not a non-negative integer");
+        assert_eq!(reason(" 10", 1), "missing stack before the count");
+        assert_eq!(reason("a;b 10", 2), "expected 2 counts, found 1");
+
+        let diagnostic = ParseDiagnostic::new(12, &format!("{} 1.5", "a;".repeat(50)), 1);
+        assert_eq!(
+            diagnostic.content.chars().count(),
+            MAX_DIAGNOSTIC_CONTENT + 1
+        );
+        assert!(diagnostic
+            .to_string()
+            .starts_with("line 12: count is not a non-negative integer: `a;a;"));
+    }
+
+    #[test]
+    fn test_get_common_prefix() {
+        let prefix = |content: &'static str| get_common_prefix(content, 1);
+        assert_eq!(
+            prefix("run;loop;poll;work;a 5\nrun;loop;poll;work;b 3\n"),
+            Some("run;loop;poll;work")
+        );
+        // Samples in the framework frames themselves don't shorten the prefix
+        assert_eq!(
+            prefix("run;loop 2\nrun;loop;poll;a 5\nrun 1\nrun;loop;poll;b 3\n"),
+            Some("run;loop;poll")
+        );
+        // At least one frame is left below the prefix
+        assert_eq!(prefix("run;loop;a 5\nrun;loop 1\n"), Some("run;loop"));
+        assert_eq!(prefix("run;a 5\nmain;b 3\n"), None);
+        assert_eq!(prefix("run 5\n"), None);
+        assert_eq!(prefix("# comment\n"), None);
+    }
+
+    #[test]
+    fn test_parse_line_values() {
+        assert_eq!(parse_line_values("a;b 10", 1), Some(("a;b", vec![10])));
+        assert_eq!(
+            parse_line_values("a;b (x.py:3) 10\t20 # note", 2),
+            Some(("a;b (x.py:3)", vec![10, 20]))
+        );
+        assert_eq!(parse_line_values("a;b 10", 2), None);
+        assert_eq!(parse_line_values("a;b 10 x", 2), None);
+    }
+}
@@ -1,17 +1,37 @@
 use std::time::Instant;
 
+#[cfg(feature = "python")]
+use crate::app::FlameGraphInput;
 use crate::{
-    app::{App, AppResult, InputBuffer},
+    app::{App, AppResult, InputBuffer, InputKind},
+    flame::FocusMode,
     state::ViewKind,
 };
-use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{
+    Event, KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+};
 use tui_input::backend::crossterm::EventHandler;
 
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    if app.confirm_quit {
+        return handle_confirm_quit(key_event, app);
+    }
     if app.log_input_buffer.is_some() {
         return handle_log_input_buffer(key_event, app);
     }
+    if app.duplicates_panel.is_some() {
+        return handle_duplicates_panel(key_event, app);
+    }
+    if app.butterfly_panel.is_some() {
+        return handle_butterfly_panel(key_event, app);
+    }
+    if app.legend_panel.is_some() {
+        return handle_legend_panel(key_event, app);
+    }
+    if app.path_panel.is_some() {
+        return handle_path_panel(key_event, app);
+    }
     if app.input_buffer.is_none() {
         let tic = Instant::now();
         handle_command(key_event, app)?;
@@ -22,6 +42,21 @@ pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
     }
 }
 
+/// Handles the mouse events and updates the state of [`App`]. Mouse events are only captured
+/// while the overview strip or overflow markers are shown.
+pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
+    let (column, row) = (mouse_event.column, mouse_event.row);
+    match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left)
+            if app.flamegraph_view.open_depth_marker_at(column, row) => {}
+        MouseEventKind::Down(MouseButton::Left) | MouseEventKind::Drag(MouseButton::Left) => {
+            app.flamegraph_view.scroll_to_minimap_position(column, row);
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
 /// Handle key events as commands
 pub fn handle_command(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
     let mut key_handled = handle_command_generic(key_event, app)?;
@@ -41,31 +76,44 @@ pub fn handle_command(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
 pub fn handle_command_generic(key_event: KeyEvent, app: &mut App) -> AppResult<bool> {
     let mut key_handled = true;
     match key_event.code {
-        // Exit application on `q`
+        // Exit application on `q`, confirming first if a live session would be lost
         KeyCode::Char('q') => {
-            app.quit();
+            app.request_quit();
         }
         // Exit application on `Ctrl-C`
-        KeyCode::Char('c') | KeyCode::Char('C') => {
-            if key_event.modifiers == KeyModifiers::CONTROL {
-                app.quit();
-            }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
+        }
+        KeyCode::Char('z') | KeyCode::Char(' ') if app.is_live() => {
+            app.toggle_freeze();
+        }
+        #[cfg(feature = "python")]
+        KeyCode::Char('+') if matches!(app.flamegraph_input, FlameGraphInput::Pid(_, _)) => {
+            app.adjust_refresh_interval(true);
         }
-        KeyCode::Char('z') => {
-            app.flamegraph_view.state.toggle_freeze();
+        #[cfg(feature = "python")]
+        KeyCode::Char('-') if matches!(app.flamegraph_input, FlameGraphInput::Pid(_, _)) => {
+            app.adjust_refresh_interval(false);
         }
         KeyCode::Tab => {
             app.flamegraph_view.state.toggle_view_kind();
         }
         KeyCode::Char('/') => {
-            app.input_buffer = Some(InputBuffer {
-                buffer: tui_input::Input::new("".to_string()),
-                cursor: None,
-            });
+            app.open_search_prompt();
+        }
+        KeyCode::Char('o') if !app.is_live() => {
+            app.input_buffer = Some(InputBuffer::new(InputKind::OpenFile));
+        }
+        #[cfg(feature = "python")]
+        KeyCode::Char('A') if !matches!(app.flamegraph_input, FlameGraphInput::Stream(_)) => {
+            app.input_buffer = Some(InputBuffer::new(InputKind::AttachPid));
         }
         KeyCode::Char('?') => {
             app.toggle_debug();
         }
+        KeyCode::Char('H') => {
+            app.toggle_humanize_counts();
+        }
         KeyCode::Char('L') => {
             app.toggle_log_panel();
         }
@@ -89,7 +137,7 @@ pub fn handle_command_generic(key_event: KeyEvent, app: &mut App) -> AppResult<b
                 buffer: tui_input::Input::new(
                     app.log_search_text.clone().unwrap_or_default(),
                 ),
-                cursor: None,
+                ..InputBuffer::new(InputKind::Search)
             });
         }
         KeyCode::Char('n') if key_event.modifiers == KeyModifiers::CONTROL && app.show_log_panel => {
@@ -139,17 +187,110 @@ fn handle_command_flamegraph(key_event: KeyEvent, app: &mut App) -> AppResult<bo
             app.flamegraph_view.to_previous_search_result();
         }
         KeyCode::Enter => {
-            app.flamegraph_view.set_zoom();
+            if app.flamegraph_view.is_depth_marker_selected() {
+                app.flamegraph_view.open_depth_marker();
+            } else if !app.flamegraph_view.expand_selected_other_group() {
+                app.flamegraph_view.set_zoom();
+            }
         }
         KeyCode::Esc => {
             app.flamegraph_view.unset_zoom();
         }
+        KeyCode::Char(']') => {
+            app.flamegraph_view.zoom_in();
+        }
+        KeyCode::Char('[') => {
+            app.flamegraph_view.zoom_out();
+        }
         KeyCode::Char('r') => {
             app.flamegraph_view.reset();
         }
+        KeyCode::Char('R') => {
+            app.reset_view_state();
+        }
         KeyCode::Char('#') => {
             app.search_selected();
         }
+        KeyCode::Char('B') if app.is_live() => {
+            app.flamegraph_view.mark_baseline();
+            app.set_transient_message("Marked baseline; diffing live updates against it");
+        }
+        KeyCode::Char('D') if app.flamegraph_state().baseline.is_some() => {
+            app.flamegraph_view.toggle_diff();
+        }
+        KeyCode::Char('U') if app.flamegraph_state().baseline.is_some() => {
+            app.flamegraph_view.clear_baseline();
+            app.set_transient_message("Cleared baseline");
+        }
+        KeyCode::Char('F') => {
+            app.toggle_focus_selected(FocusMode::Focus);
+        }
+        KeyCode::Char('x') => {
+            app.toggle_focus_selected(FocusMode::Exclude);
+        }
+        KeyCode::Char('m') => {
+            if app.flamegraph().value_names().len() < 2 {
+                app.set_transient_message(
+                    "Lines have a single value. Pass --values to load lines with several",
+                );
+            } else {
+                app.flamegraph_view.to_next_value_column();
+            }
+        }
+        KeyCode::Char('v') => {
+            app.show_butterfly();
+        }
+        KeyCode::Char('t') => {
+            app.toggle_legend_panel();
+        }
+        KeyCode::Char('d') => {
+            app.show_duplicate_subtrees();
+        }
+        KeyCode::Char('s') => {
+            app.flamegraph_view.state.toggle_show_self();
+        }
+        KeyCode::Char('w') => {
+            app.flamegraph_view.toggle_width_metric();
+        }
+        KeyCode::Char('e') => {
+            app.toggle_collapsed_common_prefix();
+        }
+        KeyCode::Char('O') => {
+            let (top_k, min_share) = (app.group_top_k, app.group_min_share);
+            app.flamegraph_view
+                .toggle_sibling_grouping(top_k, min_share);
+        }
+        KeyCode::Char('S') => {
+            app.flamegraph_view.state.toggle_spotlight();
+        }
+        KeyCode::Char('C') if app.is_live() => {
+            let duration = app.churn_updates;
+            app.flamegraph_view.state.toggle_churn(duration);
+        }
+        KeyCode::Char('p') => {
+            app.flamegraph_view.toggle_prune_to_selected();
+        }
+        KeyCode::Char('<') => {
+            app.flamegraph_view.decrease_max_render_depth();
+        }
+        KeyCode::Char('>') => {
+            app.flamegraph_view.increase_max_render_depth();
+        }
+        KeyCode::Char('P') if app.is_live() => {
+            app.flamegraph_view.toggle_pin_selected();
+        }
+        KeyCode::Char('M') => {
+            app.flamegraph_view.state.toggle_minimap();
+        }
+        KeyCode::Char('a') => {
+            app.toggle_path_panel();
+        }
+        KeyCode::Char('&') => {
+            app.input_buffer = Some(InputBuffer::new(InputKind::Highlight));
+        }
+        KeyCode::Backspace => {
+            app.flamegraph_view.clear_highlight_patterns();
+        }
         _ => {
             key_handled = false;
         }
@@ -181,6 +322,9 @@ fn handle_command_table(key_event: KeyEvent, app: &mut App) -> AppResult<bool> {
         KeyCode::Char('r') => {
             app.flamegraph_view.reset();
         }
+        KeyCode::Char('R') => {
+            app.reset_view_state();
+        }
         KeyCode::Enter => {
             app.search_selected_row();
         }
@@ -191,6 +335,107 @@ fn handle_command_table(key_event: KeyEvent, app: &mut App) -> AppResult<bool> {
     Ok(key_handled)
 }
 
+/// Handle key events while asking whether to quit a live session
+fn handle_confirm_quit(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    match key_event.code {
+        KeyCode::Char('q') | KeyCode::Char('y') => {
+            app.quit();
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
+        }
+        KeyCode::Esc | KeyCode::Char('n') => {
+            app.confirm_quit = false;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle key events while the duplicated subtrees panel is open
+fn handle_duplicates_panel(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let Some(panel) = app.duplicates_panel.as_mut() else {
+        return Ok(());
+    };
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            panel.to_next();
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            panel.to_previous();
+        }
+        KeyCode::Enter => {
+            app.highlight_selected_duplicate();
+        }
+        KeyCode::Esc | KeyCode::Char('d') => {
+            app.duplicates_panel = None;
+        }
+        KeyCode::Char('q') => {
+            app.request_quit();
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle key events while the legend of top-level frames is open
+fn handle_legend_panel(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let Some(panel) = app.legend_panel.as_mut() else {
+        return Ok(());
+    };
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            panel.to_next(&app.flamegraph_view.flamegraph);
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            panel.to_previous(&app.flamegraph_view.flamegraph);
+        }
+        KeyCode::Enter => {
+            app.zoom_to_selected_legend_entry();
+        }
+        KeyCode::Esc | KeyCode::Char('t') => {
+            app.legend_panel = None;
+        }
+        KeyCode::Char('q') => {
+            app.request_quit();
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle key events while the callers and callees panel is open
+fn handle_butterfly_panel(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let Some(panel) = app.butterfly_panel.as_mut() else {
+        return Ok(());
+    };
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            panel.scroll_down();
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            panel.scroll_up();
+        }
+        KeyCode::Esc | KeyCode::Char('v') => {
+            app.butterfly_panel = None;
+        }
+        KeyCode::Char('q') => {
+            app.request_quit();
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
 pub fn handle_input_buffer(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
     if let Some(input) = app.input_buffer.as_mut() {
         match key_event.code {
@@ -198,16 +443,51 @@ pub fn handle_input_buffer(key_event: KeyEvent, app: &mut App) -> AppResult<()>
                 app.input_buffer = None;
             }
             KeyCode::Enter => {
-                if input.buffer.value().is_empty() {
-                    app.flamegraph_view.unset_manual_search_pattern();
-                } else {
-                    let re_pattern = input.buffer.value().to_string();
-                    app.set_manual_search_pattern(re_pattern.as_str(), true);
+                let re_pattern = input.buffer.value().to_string();
+                match input.kind {
+                    InputKind::Search => {
+                        if re_pattern.is_empty() {
+                            app.flamegraph_view.unset_manual_search_pattern();
+                        } else {
+                            let (mode, scope) = (input.search_mode, input.search_scope);
+                            app.set_manual_search_pattern(re_pattern.as_str(), mode, scope);
+                        }
+                    }
+                    InputKind::Highlight => {
+                        if !re_pattern.is_empty() {
+                            app.add_highlight_pattern(re_pattern.as_str());
+                        }
+                    }
+                    InputKind::OpenFile => {
+                        if !re_pattern.is_empty() {
+                            app.open_file(re_pattern.as_str());
+                        }
+                    }
+                    #[cfg(feature = "python")]
+                    InputKind::AttachPid => {
+                        if !re_pattern.is_empty() {
+                            app.attach_to_pid(re_pattern.as_str());
+                        }
+                    }
                 }
                 app.input_buffer = None;
             }
+            KeyCode::Tab if input.kind.is_path() => {
+                input.complete_path();
+            }
+            KeyCode::Tab if matches!(input.kind, InputKind::Search) => {
+                input.toggle_search_scope();
+            }
+            KeyCode::Char('r')
+                if key_event.modifiers == KeyModifiers::CONTROL
+                    && matches!(input.kind, InputKind::Search) =>
+            {
+                input.toggle_search_mode();
+            }
             _ => {
+                input.completion = None;
                 input.buffer.handle_event(&Event::Key(key_event));
+                input.validate_search();
             }
         }
     }
@@ -239,3 +519,148 @@ pub fn handle_log_input_buffer(key_event: KeyEvent, app: &mut App) -> AppResult<
     }
     Ok(())
 }
+
+/// Handle key events while the path panel is open
+fn handle_path_panel(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    let Some(panel) = app.path_panel.as_mut() else {
+        return Ok(());
+    };
+    match key_event.code {
+        KeyCode::Down | KeyCode::Char('j') => {
+            panel.to_next(&app.flamegraph_view.flamegraph);
+            app.select_path_panel_entry();
+        }
+        KeyCode::Up | KeyCode::Char('k') => {
+            panel.to_previous();
+            app.select_path_panel_entry();
+        }
+        KeyCode::Enter | KeyCode::Esc | KeyCode::Char('a') => {
+            app.path_panel = None;
+        }
+        KeyCode::Char('q') => {
+            app.request_quit();
+        }
+        KeyCode::Char('c') | KeyCode::Char('C') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.quit();
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flame::FlameGraph;
+
+    fn press(app: &mut App, code: KeyCode) {
+        handle_key_events(KeyEvent::new(code, KeyModifiers::NONE), app).unwrap();
+    }
+
+    fn get_selected_name(app: &App) -> &str {
+        app.flamegraph()
+            .get_stack_short_name(&app.flamegraph_state().selected)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_path_panel_selects_ancestors() {
+        let content = "main;parse;alloc 30\nmain;render 10\nother 40\n";
+        let fg = FlameGraph::from_string(content.to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        let alloc = app
+            .flamegraph()
+            .get_stack_id_by_full_name("main;parse;alloc")
+            .unwrap();
+        app.flamegraph_view.select_id(&alloc);
+
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.path_panel.as_ref().unwrap().selected, 3);
+
+        // Moving along the path re-selects that ancestor in the flamegraph
+        press(&mut app, KeyCode::Char('k'));
+        assert_eq!(get_selected_name(&app), "parse");
+        press(&mut app, KeyCode::Char('k'));
+        assert_eq!(get_selected_name(&app), "main");
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(get_selected_name(&app), "parse");
+        assert_eq!(app.path_panel.as_ref().unwrap().selected, 2);
+
+        // The path stays the one the panel was opened on
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        assert_eq!(get_selected_name(&app), "alloc");
+        assert_eq!(app.path_panel.as_ref().unwrap().selected, 3);
+
+        press(&mut app, KeyCode::Esc);
+        assert!(app.path_panel.is_none());
+        assert_eq!(get_selected_name(&app), "alloc");
+    }
+
+    #[test]
+    fn test_enter_opens_depth_marker() {
+        let fg = FlameGraph::from_string("main;parse;alloc 30\nmain;render 10\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        app.flamegraph_view.state.max_render_depth = Some(1);
+        press(&mut app, KeyCode::Char('j'));
+        press(&mut app, KeyCode::Char('j'));
+        assert!(app.flamegraph_view.is_depth_marker_selected());
+        assert_eq!(get_selected_name(&app), "main");
+
+        press(&mut app, KeyCode::Enter);
+        assert!(!app.flamegraph_view.is_depth_marker_selected());
+        assert_eq!(get_selected_name(&app), "parse");
+        let zoom = app.flamegraph_state().zoom.as_ref().unwrap();
+        assert_eq!(
+            app.flamegraph().get_stack_short_name(&zoom.stack_id),
+            Some("main")
+        );
+    }
+
+    #[test]
+    fn test_toggle_churn() {
+        let mut app = App::with_live_stream("stream");
+        press(&mut app, KeyCode::Char('C'));
+        assert!(app.flamegraph_state().churn.is_some());
+        assert!(app.running);
+        press(&mut app, KeyCode::Char('C'));
+        assert!(app.flamegraph_state().churn.is_none());
+
+        handle_key_events(
+            KeyEvent::new(KeyCode::Char('c'), KeyModifiers::CONTROL),
+            &mut app,
+        )
+        .unwrap();
+        assert!(!app.running);
+    }
+
+    #[test]
+    fn test_confirm_quit_live_session() {
+        for (key, quits) in [
+            (KeyCode::Char('y'), true),
+            (KeyCode::Char('q'), true),
+            (KeyCode::Char('n'), false),
+            (KeyCode::Esc, false),
+        ] {
+            let mut app = App::with_live_stream("stream");
+            press(&mut app, KeyCode::Char('q'));
+            assert!(app.confirm_quit);
+            assert!(app.running);
+
+            press(&mut app, key);
+            assert_eq!(app.running, !quits, "{:?}", key);
+            if !quits {
+                assert!(!app.confirm_quit, "{:?}", key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_quit_static_file() {
+        let fg = FlameGraph::from_string("main;work 5\n".to_string(), true);
+        let mut app = App::with_flamegraph("test", fg);
+        press(&mut app, KeyCode::Char('q'));
+        assert!(!app.confirm_quit);
+        assert!(!app.running);
+    }
+}
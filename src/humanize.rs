@@ -0,0 +1,117 @@
+/// What the counts in the input measure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CountUnit {
+    /// Number of samples
+    #[default]
+    Samples,
+    /// Number of bytes, e.g. from a memory profiler
+    Bytes,
+}
+
+/// Format a count for display. Humanized sample counts use SI suffixes (`14.2k`, `1.42M`) and
+/// humanized byte counts use binary units (`1.4 MiB`).
+pub fn format_count(count: u64, unit: CountUnit, humanized: bool) -> String {
+    match (unit, humanized) {
+        (CountUnit::Samples, false) => count.to_string(),
+        (CountUnit::Samples, true) => format_si(count),
+        (CountUnit::Bytes, false) => format!("{} B", count),
+        (CountUnit::Bytes, true) => format_binary(count),
+    }
+}
+
+/// Label for a count, e.g. `1.42M samples` or `1.4 MiB`.
+pub fn format_count_with_unit(count: u64, unit: CountUnit, humanized: bool) -> String {
+    match unit {
+        CountUnit::Samples => format!("{} samples", format_count(count, unit, humanized)),
+        CountUnit::Bytes => format_count(count, unit, humanized),
+    }
+}
+
+/// Signed change of a count, e.g. `+1.42k` or `-512 B`.
+pub fn format_count_delta(delta: i64, unit: CountUnit, humanized: bool) -> String {
+    let sign = if delta < 0 { '-' } else { '+' };
+    format!(
+        "{}{}",
+        sign,
+        format_count(delta.unsigned_abs(), unit, humanized)
+    )
+}
+
+fn format_si(count: u64) -> String {
+    const SUFFIXES: [&str; 6] = ["", "k", "M", "G", "T", "P"];
+    let mut value = count as f64;
+    let mut i = 0;
+    while value >= 999.5 && i < SUFFIXES.len() - 1 {
+        value /= 1000.0;
+        i += 1;
+    }
+    if i == 0 {
+        count.to_string()
+    } else if value >= 99.95 {
+        format!("{:.0}{}", value, SUFFIXES[i])
+    } else if value >= 9.995 {
+        format!("{:.1}{}", value, SUFFIXES[i])
+    } else {
+        format!("{:.2}{}", value, SUFFIXES[i])
+    }
+}
+
+fn format_binary(count: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut value = count as f64;
+    let mut i = 0;
+    while value >= 1024.0 && i < UNITS.len() - 1 {
+        value /= 1024.0;
+        i += 1;
+    }
+    if i == 0 {
+        format!("{} {}", count, UNITS[i])
+    } else {
+        format!("{:.1} {}", value, UNITS[i])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_samples() {
+        let f = |count| format_count(count, CountUnit::Samples, true);
+        assert_eq!(f(0), "0");
+        assert_eq!(f(999), "999");
+        assert_eq!(f(1000), "1.00k");
+        assert_eq!(f(14200), "14.2k");
+        assert_eq!(f(142_000), "142k");
+        assert_eq!(f(999_999), "1.00M");
+        assert_eq!(f(1_423_905), "1.42M");
+        assert_eq!(
+            format_count(1_423_905, CountUnit::Samples, false),
+            "1423905"
+        );
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        let f = |count| format_count(count, CountUnit::Bytes, true);
+        assert_eq!(f(512), "512 B");
+        assert_eq!(f(1536), "1.5 KiB");
+        assert_eq!(f(1_468_006), "1.4 MiB");
+        assert_eq!(format_count(1536, CountUnit::Bytes, false), "1536 B");
+        assert_eq!(
+            format_count_with_unit(1500, CountUnit::Samples, true),
+            "1.50k samples"
+        );
+    }
+
+    #[test]
+    fn test_format_count_delta() {
+        assert_eq!(format_count_delta(1200, CountUnit::Samples, true), "+1.20k");
+        assert_eq!(format_count_delta(-300, CountUnit::Samples, true), "-300");
+        assert_eq!(format_count_delta(0, CountUnit::Samples, false), "+0");
+        assert_eq!(
+            format_count_delta(-1536, CountUnit::Bytes, true),
+            "-1.5 KiB"
+        );
+    }
+}
@@ -1,3 +1,38 @@
+//! Interactive flamegraph viewer in the terminal.
+//!
+//! Besides the `flamelens` binary, the crate can be used as a library:
+//!
+//! * [`flame`]: parse folded stacks into a [`FlameGraph`] and query it, e.g. counts, search hits
+//!   and ancestors of frames.
+//! * [`formats`]: importers and parse options for the folded stacks format.
+//! * [`view`]: navigation over a flamegraph (selection, zoom, search) independent of rendering.
+//! * [`run_app`], [`run_from_collapsed_stacks`] and [`run_from_live_stream`]: run the terminal
+//!   UI on data from your own tool.
+//!
+//! # Example
+//! ```
+//! use flamelens::flame::{FlameGraph, SearchPattern};
+//! use flamelens::view::FlameGraphView;
+//!
+//! let data = "main;parse;tokenize 30\nmain;parse 10\nmain;render 20\n".to_string();
+//! let mut flamegraph = FlameGraph::from_string(data, false);
+//! assert_eq!(flamegraph.total_count(), 60);
+//!
+//! let parse = flamegraph.get_stack_by_full_name("main;parse").unwrap();
+//! assert_eq!((parse.total_count, parse.self_count), (40, 10));
+//!
+//! // Frames matching a search pattern and the samples they cover
+//! flamegraph.set_hits(&SearchPattern::new("tokenize|render", true, true).unwrap());
+//! assert_eq!(flamegraph.hit_coverage_count(), Some(50));
+//!
+//! // Navigate like the key bindings do
+//! let mut view = FlameGraphView::new(flamegraph);
+//! view.to_child_stack();
+//! view.to_child_stack();
+//! let selected = view.get_selected_stack().unwrap();
+//! assert_eq!(view.flamegraph.get_stack_full_name_from_info(selected), "main;parse");
+//! ```
+
 /// Application.
 pub mod app;
 
@@ -13,10 +48,28 @@ pub mod tui;
 /// Event handler.
 pub mod handler;
 
+/// Search of profile data without the UI, for scripts.
+pub mod check;
+
+/// Filesystem path completion for prompts.
+pub mod completion;
+
+/// Flamegraph parsed from folded stacks.
 pub mod flame;
 
+/// JSON lines summaries of live updates for external monitoring.
+pub mod emit;
+
+/// Parse options and line parsing for the folded stacks format.
+pub mod formats;
+
+/// Human readable formatting of counts.
+pub mod humanize;
+
+/// Navigation and display state of a flamegraph.
 pub mod state;
 
+/// Navigation over a flamegraph.
 pub mod view;
 
 #[cfg(feature = "python")]
@@ -29,7 +82,7 @@ pub mod py_spy_flamegraph;
 use app::{App, AppResult, ParsedFlameGraph};
 use event::{Event, EventHandler};
 use flame::FlameGraph;
-use handler::handle_key_events;
+use handler::{handle_key_events, handle_mouse_events};
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use std::io;
@@ -53,7 +106,7 @@ pub fn run_from_collapsed_stacks(data: String, title: &str, sorted: bool) -> App
     let flamegraph = FlameGraph::from_string(data, sorted);
     let mut app = App::with_flamegraph(title, flamegraph);
 
-    run_tui_loop(&mut app)
+    run_app(&mut app, app::DEFAULT_TICK_MS)
 }
 
 /// Run interactive flamegraph viewer with live updates
@@ -83,15 +136,14 @@ pub fn run_from_collapsed_stacks(data: String, title: &str, sorted: bool) -> App
 /// # }
 pub fn run_from_live_stream(rx: std::sync::mpsc::Receiver<String>, title: &str) -> AppResult<()> {
     // Start with empty flamegraph
-    let flamegraph = FlameGraph::from_string(String::new(), true);
-    let mut app = App::with_flamegraph(title, flamegraph);
+    let mut app = App::with_live_stream(title);
 
     // Channel to send accumulated data to the background parsing thread
     let (parse_tx, parse_rx) = std::sync::mpsc::channel::<String>();
 
-    // Shared state for parsed flamegraph produced by the background thread
-    let next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>> = Arc::new(Mutex::new(None));
-    let next_fg_clone = next_flamegraph.clone();
+    // Parsed flamegraphs produced by the background thread are swapped in by the app on tick
+    let next_fg_clone = app.next_flamegraph_slot();
+    let parse_options = app.parse_options_slot();
 
     // Background thread for parsing flamegraphs without blocking the UI
     std::thread::spawn(move || {
@@ -101,8 +153,9 @@ pub fn run_from_live_stream(rx: std::sync::mpsc::Receiver<String>, title: &str)
             while let Ok(newer) = parse_rx.try_recv() {
                 latest = newer;
             }
+            let options = parse_options.lock().unwrap().clone();
             let tic = std::time::Instant::now();
-            let flamegraph = FlameGraph::from_string(latest, true);
+            let flamegraph = FlameGraph::from_string_with_options(latest, &options);
             let parsed = ParsedFlameGraph {
                 flamegraph,
                 elapsed: tic.elapsed(),
@@ -135,16 +188,11 @@ pub fn run_from_live_stream(rx: std::sync::mpsc::Receiver<String>, title: &str)
             let _ = parse_tx.send(combined);
         }
 
-        // Swap in the latest parsed flamegraph if ready
-        if let Some(parsed) = next_flamegraph.lock().unwrap().take() {
-            app.flamegraph_view.replace_flamegraph(parsed.flamegraph);
-        }
-
         tui.draw(&mut app)?;
         match tui.events.next()? {
             Event::Tick => app.tick(),
             Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app)?,
             Event::Resize(_, _) => {}
         }
     }
@@ -159,16 +207,15 @@ pub fn run_from_live_stream_with_logs(
     title: &str,
     log_capacity: Option<usize>,
 ) -> AppResult<()> {
-    let flamegraph = FlameGraph::from_string(String::new(), true);
-    let mut app = App::with_flamegraph(title, flamegraph);
+    let mut app = App::with_live_stream(title);
     app.has_log_channel = true;
     app.show_log_panel = true;
     app.set_log_max_capacity(log_capacity.unwrap_or(1000));
 
     let (parse_tx, parse_rx) = std::sync::mpsc::channel::<String>();
 
-    let next_flamegraph: Arc<Mutex<Option<ParsedFlameGraph>>> = Arc::new(Mutex::new(None));
-    let next_fg_clone = next_flamegraph.clone();
+    let next_fg_clone = app.next_flamegraph_slot();
+    let parse_options = app.parse_options_slot();
 
     std::thread::spawn(move || {
         while let Ok(combined) = parse_rx.recv() {
@@ -176,8 +223,9 @@ pub fn run_from_live_stream_with_logs(
             while let Ok(newer) = parse_rx.try_recv() {
                 latest = newer;
             }
+            let options = parse_options.lock().unwrap().clone();
             let tic = std::time::Instant::now();
-            let flamegraph = FlameGraph::from_string(latest, true);
+            let flamegraph = FlameGraph::from_string_with_options(latest, &options);
             let parsed = ParsedFlameGraph {
                 flamegraph,
                 elapsed: tic.elapsed(),
@@ -215,10 +263,6 @@ pub fn run_from_live_stream_with_logs(
             let _ = parse_tx.send(combined);
         }
 
-        if let Some(parsed) = next_flamegraph.lock().unwrap().take() {
-            app.flamegraph_view.replace_flamegraph(parsed.flamegraph);
-        }
-
         let drained: Vec<String> = pending_logs.lock().unwrap().drain(..).collect();
         for msg in drained {
             app.push_log_message(msg);
@@ -228,7 +272,7 @@ pub fn run_from_live_stream_with_logs(
         match tui.events.next()? {
             Event::Tick => app.tick(),
             Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app)?,
             Event::Resize(_, _) => {}
         }
     }
@@ -237,20 +281,27 @@ pub fn run_from_live_stream_with_logs(
     Ok(())
 }
 
-// Helper: shared TUI loop logic for static mode
-fn run_tui_loop(app: &mut App) -> AppResult<()> {
+/// Run the terminal UI for an already constructed [`App`] until it quits
+///
+/// # Arguments
+/// * `app` - Application, e.g. from [`App::with_flamegraph`]
+/// * `tick_rate` - Interval in milliseconds between ticks, which swap in live updates. Ticks
+///   slow down to [`App::idle_tick_rate`] without input unless the flamegraph is live
+pub fn run_app(app: &mut App, tick_rate: u64) -> AppResult<()> {
     let backend = CrosstermBackend::new(io::stderr());
     let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(250);
+    let events = EventHandler::with_idle_tick_rate(tick_rate, app.idle_tick_rate);
     let mut tui = tui::Tui::new(terminal, events);
     tui.init()?;
 
     while app.running {
+        // Live updates are swapped in on ticks, so keep ticking at full rate for them
+        tui.events.set_idle_allowed(!app.is_live());
         tui.draw(app)?;
         match tui.events.next()? {
             Event::Tick => app.tick(),
             Event::Key(key_event) => handle_key_events(key_event, app)?,
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, app)?,
             Event::Resize(_, _) => {}
         }
     }
@@ -265,21 +316,10 @@ fn merge_collapsed_stacks(existing: &[String], new_data: &str) -> Vec<String> {
 
     let mut counts: HashMap<String, u64> = HashMap::new();
 
-    // Parse existing
-    for line in existing {
-        if let Some((stack, count_str)) = line.rsplit_once(' ') {
-            if let Ok(count) = count_str.parse::<u64>() {
-                *counts.entry(stack.to_string()).or_insert(0) += count;
-            }
-        }
-    }
-
-    // Add new
-    for line in new_data.lines() {
-        if let Some((stack, count_str)) = line.rsplit_once(' ') {
-            if let Ok(count) = count_str.parse::<u64>() {
-                *counts.entry(stack.to_string()).or_insert(0) += count;
-            }
+    // Parse existing and add new
+    for line in existing.iter().map(String::as_str).chain(new_data.lines()) {
+        if let Some((stack, count)) = formats::parse_line(line) {
+            *counts.entry(stack.to_string()).or_insert(0) += count;
         }
     }
 
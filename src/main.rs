@@ -1,23 +1,159 @@
-use clap::Parser;
-use flamelens::app::{App, AppResult};
-use flamelens::event::{Event, EventHandler};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use flamelens::app::{App, AppResult, ParsedFlameGraph, SearchMode};
+use flamelens::check::{check, CheckOptions, CheckResult};
+use flamelens::emit::JsonEmitter;
 use flamelens::flame::FlameGraph;
-use flamelens::handler::handle_key_events;
-use flamelens::tui::Tui;
-use ratatui::backend::CrosstermBackend;
-use ratatui::Terminal;
-use std::io::{self, Read};
+use flamelens::formats::{CommMode, ParseOptions, DEFAULT_TRUNCATION_MARKER};
+use flamelens::humanize::{format_count_with_unit, CountUnit};
+use flamelens::state::{ColorScheme, LabelTruncation};
+#[cfg(feature = "http")]
+use std::io::Write;
+use std::io::{self, BufRead, Read};
+use std::sync::{Arc, Mutex};
 
 #[derive(Parser, Debug)]
-#[command(version)]
+#[command(version, args_conflicts_with_subcommands = true)]
 struct Args {
-    /// Profile data filename
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Profile data filename, or an http(s):// URL to download it from with `curl` (requires the
+    /// `http` feature)
     filename: Option<String>,
 
-    /// Whether to sort the stacks by time spent
-    #[clap(long, action, value_name = "sorted")]
+    /// Header sent when downloading the profile data from a URL, e.g. "Authorization: Bearer
+    /// <token>". Can be repeated
+    #[clap(long = "header", value_name = "header")]
+    headers: Vec<String>,
+
+    /// Whether to sort the stacks by time spent. Same as `--sort left-heavy`
+    #[clap(long, action, value_name = "sorted", conflicts_with = "sort")]
     sorted: bool,
 
+    /// Order of sibling frames: "input" keeps the order of the profile data, "left-heavy" puts
+    /// those with the most time spent first. Applies to live updates too, and defaults to
+    /// "left-heavy" when sampling a process
+    #[clap(long, global = true, value_enum, value_name = "mode")]
+    sort: Option<SortMode>,
+
+    /// Treatment of the leading process command name frame, e.g. in the output of bcc's
+    /// `profile -f`: "group" keeps one top-level frame per command, "strip" removes it
+    #[clap(long, global = true, value_enum, value_name = "mode")]
+    comm: Option<CommModeArg>,
+
+    /// Regex matching frames that mark a stack truncated by the profiler. Pass an empty string
+    /// to disable detection
+    #[clap(long, global = true, value_name = "regex", default_value = DEFAULT_TRUNCATION_MARKER)]
+    truncation_marker: String,
+
+    /// Treat the input stacks as leaf first (`callee;caller`). By default stacks are expected
+    /// root first (`caller;callee`), as produced by inferno and FlameGraph's stackcollapse tools
+    #[clap(long, global = true, action)]
+    reverse_stacks: bool,
+
+    /// Names of the values ending each line when there are several, e.g. "cpu,wall" for lines
+    /// like `main;parse 12 40`
+    #[clap(long, global = true, value_name = "names", value_delimiter = ',')]
+    values: Vec<String>,
+
+    /// Which of the --values weights the frames initially, by name or position starting at 1.
+    /// Press 'm' to switch at runtime
+    #[clap(long, global = true, value_name = "name", requires = "values")]
+    value: Option<String>,
+
+    /// Label of the root frame instead of "all", e.g. the name of the profiled program
+    #[clap(long, global = true, value_name = "label")]
+    root_label: Option<String>,
+
+    /// When every stack starts with the same frame, e.g. a single process or thread, show that
+    /// frame as the root instead of under "all"
+    #[clap(long, global = true, action)]
+    promote_single_root: bool,
+
+    /// Collapse the longest run of frames all stacks start with, e.g. an event loop and runtime
+    /// initialization, into the root (press 'e' to toggle)
+    #[clap(long, global = true, action)]
+    collapse_prefix: bool,
+
+    /// What the counts in the input measure
+    #[clap(long, global = true, value_enum, value_name = "unit", default_value_t = CountUnitArg::Samples)]
+    count_unit: CountUnitArg,
+
+    /// How frames are colored: "name" hashes the function name so colors stay put across live
+    /// updates and runs, "position" follows the frame's place in the layout
+    #[clap(long, value_enum, value_name = "scheme", default_value_t = ColorSchemeArg::Name)]
+    color_scheme: ColorSchemeArg,
+
+    /// Which part of frame labels that don't fit to elide: "end", "middle" (keeping the last
+    /// `::` or `.` separated segment, i.e. the function name) or "start"
+    #[clap(long, value_enum, value_name = "style", default_value_t = LabelTruncationArg::End)]
+    label_truncation: LabelTruncationArg,
+
+    /// How the '/' search prompt matches by default: "regex", or "text" for frames whose name
+    /// contains the entered text. Press ctrl+r in the prompt to switch
+    #[clap(long, value_enum, value_name = "mode", default_value_t = SearchModeArg::Regex)]
+    search_mode: SearchModeArg,
+
+    /// Number of frames above which frames narrower than 0.1% of the total are hidden to keep the
+    /// UI responsive. Pass 0 to always show the full graph
+    #[clap(long, value_name = "n", default_value_t = flamelens::app::DEFAULT_MAX_FRAMES)]
+    max_frames: usize,
+
+    /// Read a live stream of folded stacks snapshots from stdin, e.g. from an external sampler.
+    /// Each snapshot is a complete set of stacks ended by an empty line or a line holding only a
+    /// form feed, and replaces the previous one
+    #[clap(long, action, conflicts_with = "filename")]
+    #[cfg_attr(feature = "python", clap(conflicts_with = "pid"))]
+    stream: bool,
+
+    /// Interval in milliseconds between redraws of the UI
+    #[clap(
+        long,
+        value_name = "ms",
+        default_value_t = flamelens::app::DEFAULT_TICK_MS,
+        value_parser = clap::value_parser!(u64).range(10..)
+    )]
+    tick_ms: u64,
+
+    /// Interval in milliseconds between redraws after a few seconds without input, unless the
+    /// flamegraph is live. Pass 0 to always redraw at the --tick-ms rate
+    #[clap(long, value_name = "ms", default_value_t = flamelens::app::DEFAULT_IDLE_TICK_MS)]
+    idle_tick_ms: u64,
+
+    /// Number of live updates that frames stay marked for after appearing, once marking them is
+    /// enabled with 'C'
+    #[clap(
+        long,
+        value_name = "n",
+        default_value_t = flamelens::app::DEFAULT_CHURN_UPDATES as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    churn_updates: u64,
+
+    /// Number of widest children of each frame kept when grouping the others into one frame
+    /// with 'O'
+    #[clap(
+        long,
+        value_name = "n",
+        default_value_t = flamelens::state::DEFAULT_GROUP_TOP_K as u64,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    group_top_k: u64,
+
+    /// Share of a frame in percent below which its children are grouped into one frame with 'O',
+    /// even if among the widest
+    #[clap(long, value_name = "pct", default_value_t = 100.0 * flamelens::state::DEFAULT_GROUP_MIN_SHARE)]
+    group_min_share: f64,
+
+    /// Write a JSON line summarizing each live update (top functions by self time, total
+    /// samples, sample rate and elapsed time) to a file or FIFO, or to stdout with "-"
+    #[clap(long, value_name = "path")]
+    emit_json: Option<String>,
+
+    /// Number of functions listed in each --emit-json line
+    #[clap(long, value_name = "n", default_value_t = flamelens::emit::DEFAULT_EMIT_TOP)]
+    emit_json_top: usize,
+
     /// Print data to stdout on exit. Useful when piping to other tools
     #[clap(long, action, value_name = "echo")]
     echo: bool,
@@ -32,12 +168,406 @@ struct Args {
     #[clap(long, value_name = "py-spy-args")]
     py_spy_args: Option<String>,
 
+    /// Interval in milliseconds between live flamegraph refreshes (minimum 50)
+    #[cfg(feature = "python")]
+    #[clap(
+        long,
+        value_name = "ms",
+        default_value_t = flamelens::app::DEFAULT_REFRESH_MS,
+        value_parser = clap::value_parser!(u64).range(flamelens::app::MIN_REFRESH_MS..)
+    )]
+    refresh_ms: u64,
+
+    /// Record the raw samples of a live flamegraph to a folded stacks file for later viewing
+    #[cfg(feature = "python")]
+    #[clap(long, value_name = "filename", requires = "pid")]
+    record: Option<String>,
+
     /// Show debug info
     #[clap(long)]
     debug: bool,
 }
 
-fn get_app_from_filename_or_stdin(args: &Args, echo: bool) -> App {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Search the profile data without the TUI, printing the matching frames. Exits with 1 if
+    /// the matches cover more than --threshold-pct of the total, e.g. to fail CI on regressions,
+    /// and with 3 if the data cannot be read or the pattern is invalid
+    Check {
+        /// Profile data filename. Reads stdin if omitted
+        filename: Option<String>,
+
+        /// Regex matching the frames to look for
+        #[clap(long, value_name = "regex")]
+        pattern: String,
+
+        /// Match the pattern against the full path of frames (`main;serialize;malloc`) rather
+        /// than their name
+        #[clap(long, action)]
+        full_path: bool,
+
+        /// Share of the total in percent the matches may cover without failing. By default any
+        /// match fails
+        #[clap(long, value_name = "pct", default_value_t = 0.0)]
+        threshold_pct: f64,
+
+        /// Fail with exit code 2 if any line of the profile data is malformed, instead of
+        /// skipping it with a warning
+        #[clap(long, action)]
+        strict: bool,
+    },
+}
+
+/// Whether a process is sampled with `--pid`.
+fn is_sampling_pid(_args: &Args) -> bool {
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "python")] {
+            _args.pid.is_some()
+        } else {
+            false
+        }
+    }
+}
+
+/// Command line values of [`CommMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CommModeArg {
+    /// Keep one top-level frame per command. Per-CPU / per-worker kernel thread names such as
+    /// `swapper/0` or `kworker/3:1` are grouped under their base name.
+    Group,
+    /// Drop the command name frame so that all processes are merged.
+    Strip,
+}
+
+impl From<CommModeArg> for CommMode {
+    fn from(arg: CommModeArg) -> Self {
+        match arg {
+            CommModeArg::Group => CommMode::Group,
+            CommModeArg::Strip => CommMode::Strip,
+        }
+    }
+}
+
+/// Command line values of [`CountUnit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CountUnitArg {
+    /// Number of samples
+    Samples,
+    /// Number of bytes, e.g. from a memory profiler
+    Bytes,
+}
+
+impl From<CountUnitArg> for CountUnit {
+    fn from(arg: CountUnitArg) -> Self {
+        match arg {
+            CountUnitArg::Samples => CountUnit::Samples,
+            CountUnitArg::Bytes => CountUnit::Bytes,
+        }
+    }
+}
+
+/// Command line values of [`ColorScheme`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ColorSchemeArg {
+    /// Hash of the function name, so a function keeps its color across live updates and runs
+    Name,
+    /// Position of the frame in the layout, which changes as the graph changes
+    Position,
+}
+
+impl From<ColorSchemeArg> for ColorScheme {
+    fn from(arg: ColorSchemeArg) -> Self {
+        match arg {
+            ColorSchemeArg::Name => ColorScheme::Name,
+            ColorSchemeArg::Position => ColorScheme::Position,
+        }
+    }
+}
+
+/// Command line values of [`LabelTruncation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum LabelTruncationArg {
+    /// Keep the start, e.g. `std::collections::ha…`
+    End,
+    /// Keep the leaf (the last `::` or `.` separated segment) and as much of the start as fits,
+    /// e.g. `std::coll…::insert`
+    Middle,
+    /// Keep the end, e.g. `…hash_map::HashMap::insert`
+    Start,
+}
+
+impl From<LabelTruncationArg> for LabelTruncation {
+    fn from(arg: LabelTruncationArg) -> Self {
+        match arg {
+            LabelTruncationArg::End => LabelTruncation::End,
+            LabelTruncationArg::Middle => LabelTruncation::Middle,
+            LabelTruncationArg::Start => LabelTruncation::Start,
+        }
+    }
+}
+
+/// Command line values of [`SearchMode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum SearchModeArg {
+    /// As a regular expression
+    Regex,
+    /// As plain text, matching the frame names (or paths) that contain it
+    Text,
+}
+
+impl From<SearchModeArg> for SearchMode {
+    fn from(arg: SearchModeArg) -> Self {
+        match arg {
+            SearchModeArg::Regex => SearchMode::Regex,
+            SearchModeArg::Text => SearchMode::Text,
+        }
+    }
+}
+
+/// Order of sibling frames, see `--sort`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum SortMode {
+    Input,
+    LeftHeavy,
+}
+
+/// Parse options from the arguments, or an error describing an invalid argument.
+fn get_parse_options(args: &Args) -> Result<ParseOptions, String> {
+    let truncation_marker = if args.truncation_marker.is_empty() {
+        None
+    } else {
+        Some(
+            regex::Regex::new(&args.truncation_marker)
+                .map_err(|e| format!("invalid --truncation-marker regex: {}", e))?,
+        )
+    };
+    let value_column = match &args.value {
+        Some(value) => args
+            .values
+            .iter()
+            .position(|name| name == value)
+            .or_else(|| {
+                value
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|position| (1..=args.values.len()).contains(position))
+                    .map(|position| position - 1)
+            })
+            .ok_or_else(|| {
+                format!(
+                    "invalid --value '{}': must be one of the --values names or a position \
+                     starting at 1",
+                    value
+                )
+            })?,
+        None => 0,
+    };
+    Ok(ParseOptions {
+        sorted: match args.sort {
+            Some(sort) => sort == SortMode::LeftHeavy,
+            None => args.sorted || is_sampling_pid(args),
+        },
+        comm: args.comm.map(CommMode::from),
+        truncation_marker,
+        reverse_stacks: args.reverse_stacks,
+        value_names: args.values.clone(),
+        value_column,
+        root_label: args.root_label.clone(),
+        promote_single_root: args.promote_single_root,
+        collapse_common_prefix: args.collapse_prefix,
+    })
+}
+
+/// Whether the line ends a snapshot in `--stream` mode.
+fn is_stream_delimiter(line: &str) -> bool {
+    matches!(line.trim_end_matches('\r'), "" | "\x0c")
+}
+
+fn get_app_from_stdin_stream(options: ParseOptions) -> App {
+    let mut app = App::with_live_stream("stdin [stream]");
+    app.set_parse_options(options);
+    let parse_options = app.parse_options_slot();
+    let next_flamegraph = app.next_flamegraph_slot();
+    let next_error = app.next_error_slot();
+    std::thread::spawn(move || {
+        let publish = |snapshot: String| {
+            let options = parse_options.lock().unwrap().clone();
+            let tic = std::time::Instant::now();
+            let flamegraph = FlameGraph::from_string_with_options(snapshot, &options);
+            let parsed = ParsedFlameGraph {
+                flamegraph,
+                elapsed: tic.elapsed(),
+            };
+            // Snapshots not swapped in yet are superseded
+            *next_flamegraph.lock().unwrap() = Some(parsed);
+        };
+        if let Err(e) = read_stream_snapshots(io::stdin().lock(), publish) {
+            *next_error.lock().unwrap() = Some(format!("Stopped reading the stream: {}", e));
+        }
+    });
+    app
+}
+
+/// Publish each snapshot of a live stream once its delimiter has been read, and the last one at
+/// the end of the stream. A snapshot cut short by a read error is dropped.
+fn read_stream_snapshots(reader: impl BufRead, mut publish: impl FnMut(String)) -> io::Result<()> {
+    let mut snapshot = String::new();
+    for line in reader.lines() {
+        let line = line?;
+        if !is_stream_delimiter(&line) {
+            snapshot.push_str(&line);
+            snapshot.push('\n');
+        } else if !snapshot.is_empty() {
+            publish(std::mem::take(&mut snapshot));
+        }
+    }
+    // The last snapshot may not be followed by a delimiter
+    if !snapshot.is_empty() {
+        publish(snapshot);
+    }
+    Ok(())
+}
+
+/// Whether the argument is a URL to download the profile data from rather than a filename.
+fn is_url(filename: &str) -> bool {
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+/// Profile data downloaded from a URL, kept to be echoed once the UI has exited.
+type DownloadedContent = Arc<Mutex<Option<String>>>;
+
+/// Minimum interval between previews of a download in progress.
+#[cfg(feature = "http")]
+const DOWNLOAD_PUBLISH_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Whether the stacks downloaded so far are parsed again for a preview. Waiting for the content
+/// to double since the last preview keeps the total parsing work linear in the download size.
+#[cfg(feature = "http")]
+fn should_publish_download(
+    len: usize,
+    published_len: usize,
+    since_published: std::time::Duration,
+) -> bool {
+    since_published >= DOWNLOAD_PUBLISH_INTERVAL && len > 0 && len >= 2 * published_len
+}
+
+/// Reason for a failed download from curl's error output, or its exit status if there is none.
+#[cfg(feature = "http")]
+fn get_download_error(stderr: &str, status: std::process::ExitStatus) -> String {
+    stderr
+        .trim()
+        .strip_prefix("curl: ")
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| status.to_string())
+}
+
+/// Start downloading the profile data with `curl` in the background. The flamegraph of the
+/// stacks received so far is swapped in periodically so that large downloads can be looked at
+/// before they complete. Once complete, the content is stored in `downloaded` if given.
+#[cfg(feature = "http")]
+fn get_app_from_url(
+    url: &str,
+    args: &Args,
+    options: ParseOptions,
+    downloaded: Option<DownloadedContent>,
+) -> App {
+    let mut app = App::with_flamegraph(url, FlameGraph::from_string(String::new(), args.sorted));
+    app.set_parse_options(options);
+    let parse_options = app.parse_options_slot();
+    let next_flamegraph = app.next_flamegraph_slot();
+    let next_error = app.next_error_slot();
+    let url = url.to_string();
+    let headers = args.headers.clone();
+    std::thread::spawn(move || {
+        let publish = |content: &str| {
+            let options = parse_options.lock().unwrap().clone();
+            let tic = std::time::Instant::now();
+            let flamegraph = FlameGraph::from_string_with_options(content.to_string(), &options);
+            *next_flamegraph.lock().unwrap() = Some(ParsedFlameGraph {
+                flamegraph,
+                elapsed: tic.elapsed(),
+            });
+        };
+        let report = |error: String| {
+            *next_error.lock().unwrap() = Some(format!("Could not download {}: {}", url, error));
+        };
+        // Headers are passed through stdin so that tokens don't show up in the process list
+        let child = std::process::Command::new("curl")
+            .args([
+                "--silent",
+                "--show-error",
+                "--fail",
+                "--location",
+                "--compressed",
+            ])
+            .args(["--header", "@-", "--", &url])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => return report(format!("could not run curl ({})", e)),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(headers.join("\n").as_bytes());
+        }
+        let mut content = String::new();
+        let mut published_len = 0;
+        let mut published_at = std::time::Instant::now();
+        if let Some(stdout) = child.stdout.take() {
+            for line in io::BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                content.push_str(&line);
+                content.push('\n');
+                if should_publish_download(content.len(), published_len, published_at.elapsed()) {
+                    publish(&content);
+                    published_len = content.len();
+                    published_at = std::time::Instant::now();
+                }
+            }
+        }
+        let mut stderr = String::new();
+        if let Some(mut child_stderr) = child.stderr.take() {
+            let _ = child_stderr.read_to_string(&mut stderr);
+        }
+        publish(&content);
+        match child.wait() {
+            Ok(status) if status.success() => {
+                if let Some(downloaded) = downloaded {
+                    *downloaded.lock().unwrap() = Some(content);
+                }
+            }
+            Ok(status) => report(get_download_error(&stderr, status)),
+            Err(e) => report(e.to_string()),
+        }
+    });
+    app
+}
+
+fn get_app_from_filename_or_stdin(
+    args: &Args,
+    echo: bool,
+    options: ParseOptions,
+    downloaded: &DownloadedContent,
+) -> App {
+    if let Some(url) = args.filename.as_deref().filter(|filename| is_url(filename)) {
+        cfg_if::cfg_if! {
+            if #[cfg(feature = "http")] {
+                return get_app_from_url(url, args, options, echo.then(|| downloaded.clone()));
+            } else {
+                let _ = downloaded;
+                Args::command()
+                    .error(
+                        ErrorKind::InvalidValue,
+                        format!("cannot download {}: built without the `http` feature", url),
+                    )
+                    .exit();
+            }
+        }
+    }
     let (filename, content) = if let Some(filename) = &args.filename {
         (
             filename.as_str(),
@@ -55,53 +585,371 @@ fn get_app_from_filename_or_stdin(args: &Args, echo: bool) -> App {
         println!("{}", content);
     }
     let tic = std::time::Instant::now();
-    let flamegraph = FlameGraph::from_string(content, args.sorted);
+    let flamegraph = FlameGraph::from_string_with_options(content, &options);
     let mut app = App::with_flamegraph(filename, flamegraph);
     app.add_elapsed("flamegraph", tic.elapsed());
+    app.set_parse_options(options);
+    app.log_flamegraph_warnings();
     app
 }
 
+/// Exit code of `flamelens check` when the profile data cannot be read or the pattern is invalid.
+const CHECK_ERROR_EXIT_CODE: i32 = 3;
+
+/// Print the frames matching the pattern and whether they cover more than the threshold, and
+/// return the exit code. Malformed lines are reported on stderr and fail the check if `strict`.
+fn run_check(
+    args: &Args,
+    filename: Option<&str>,
+    parse_options: &ParseOptions,
+    options: &CheckOptions,
+) -> i32 {
+    let content = match filename {
+        Some(filename) => std::fs::read_to_string(filename)
+            .map_err(|e| format!("Could not read {}: {}", filename, e)),
+        None => {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .map(|_| content)
+                .map_err(|e| format!("Could not read stdin: {}", e))
+        }
+    };
+    let report = content.and_then(|content| {
+        check(content, parse_options, options)
+            .map_err(|e| format!("Invalid --pattern regex: {}", e))
+    });
+    let report = match report {
+        Ok(report) => report,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return CHECK_ERROR_EXIT_CODE;
+        }
+    };
+    if !report.diagnostics.is_empty() {
+        let severity = if options.strict { "error" } else { "warning" };
+        for diagnostic in report.diagnostics.iter() {
+            eprintln!("{}: {}", severity, diagnostic);
+        }
+        if report.num_more_malformed > 0 {
+            eprintln!(
+                "{}: ... and {} more malformed line(s)",
+                severity, report.num_more_malformed
+            );
+        }
+    }
+    if report.result == CheckResult::Malformed {
+        return report.result as i32;
+    }
+    for m in report.matches.iter() {
+        println!(
+            "{:>8.2}%  {}  {}",
+            m.share_pct,
+            format_count_with_unit(m.total_count, args.count_unit.into(), false),
+            m.full_name
+        );
+    }
+    println!(
+        "{} frame(s) matched, covering {} ({:.2}% of the total)",
+        report.matches.len(),
+        format_count_with_unit(report.coverage_count, args.count_unit.into(), false),
+        report.coverage_pct
+    );
+    report.result as i32
+}
+
 fn main() -> AppResult<()> {
     let args = Args::parse();
+    let parse_options = get_parse_options(&args)
+        .unwrap_or_else(|e| Args::command().error(ErrorKind::InvalidValue, e).exit());
+
+    if let Some(Command::Check {
+        filename,
+        pattern,
+        full_path,
+        threshold_pct,
+        strict,
+    }) = &args.command
+    {
+        let options = CheckOptions {
+            pattern: pattern.clone(),
+            full_path: *full_path,
+            threshold_pct: *threshold_pct,
+            strict: *strict,
+        };
+        std::process::exit(run_check(
+            &args,
+            filename.as_deref(),
+            &parse_options,
+            &options,
+        ));
+    }
 
+    let json_emitter = match args.emit_json.as_ref() {
+        Some(path) => match JsonEmitter::start(path, args.emit_json_top) {
+            Ok(emitter) => Some(emitter),
+            Err(e) => {
+                eprintln!("error: Could not create --emit-json file {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let downloaded = DownloadedContent::default();
     // Create an application.
     cfg_if::cfg_if! {
         if #[cfg(feature = "python")] {
-            let mut app = if let Some(_pid) = args.pid {
-                App::with_pid(
+            let mut app = if let Some(_pid) = &args.pid {
+                let recorder = match &args.record {
+                    Some(filename) => {
+                        match flamelens::py_spy::Recorder::start(std::path::Path::new(filename)) {
+                            Ok(recorder) => Some(recorder),
+                            Err(e) => {
+                                eprintln!("error: Could not create --record file {}: {}", filename, e);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                let mut app = App::with_pid(
                     _pid.parse().expect("Could not parse pid"),
                     args.py_spy_args.clone(),
-                )
+                    args.refresh_ms,
+                    recorder,
+                );
+                app.set_parse_options(parse_options);
+                app
+            } else if args.stream {
+                get_app_from_stdin_stream(parse_options)
             } else {
-                get_app_from_filename_or_stdin(&args, args.echo)
+                get_app_from_filename_or_stdin(&args, args.echo, parse_options, &downloaded)
             };
         } else {
-            let mut app = get_app_from_filename_or_stdin(&args, args.echo);
+            let mut app = if args.stream {
+                get_app_from_stdin_stream(parse_options)
+            } else {
+                get_app_from_filename_or_stdin(&args, args.echo, parse_options, &downloaded)
+            };
         }
     }
     app.debug = args.debug;
+    app.count_unit = args.count_unit.into();
+    app.color_scheme = args.color_scheme.into();
+    app.label_truncation = args.label_truncation.into();
+    app.default_search_mode = args.search_mode.into();
+    app.flamegraph_view.state.collapse_common_prefix = args.collapse_prefix;
+    app.max_frames = (args.max_frames > 0).then_some(args.max_frames);
+    app.churn_updates = args.churn_updates as usize;
+    app.group_top_k = args.group_top_k as usize;
+    app.group_min_share = args.group_min_share / 100.0;
+    app.json_emitter = json_emitter;
+    app.apply_frame_cap();
+    // Idling never ticks faster than the base rate
+    app.idle_tick_rate = (args.idle_tick_ms > 0).then_some(args.idle_tick_ms.max(args.tick_ms));
 
-    // Initialize the terminal user interface.
-    let backend = CrosstermBackend::new(io::stderr());
-    let terminal = Terminal::new(backend)?;
-    let events = EventHandler::new(250);
-    let mut tui = Tui::new(terminal, events);
-    tui.init()?;
+    // Tick at least as often as the live refresh so that new samples are swapped in promptly
+    cfg_if::cfg_if! {
+        if #[cfg(feature = "python")] {
+            let tick_rate = args.refresh_ms.min(args.tick_ms);
+        } else {
+            let tick_rate = args.tick_ms;
+        }
+    }
+
+    let result = flamelens::run_app(&mut app, tick_rate);
+    // Echoed once the terminal is restored, as the download finishes while the UI is shown
+    if let Some(content) = downloaded.lock().unwrap().take() {
+        println!("{}", content);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Args {
+        Args::try_parse_from(std::iter::once("flamelens").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn test_get_parse_options() {
+        let options = get_parse_options(&parse_args(&["--values", "cpu,wall", "--value", "wall"]));
+        assert_eq!(options.unwrap().value_column, 1);
+        let options = get_parse_options(&parse_args(&["--values", "cpu,wall", "--value", "1"]));
+        assert_eq!(options.unwrap().value_column, 0);
 
-    // Start the main loop.
-    while app.running {
-        // Render the user interface.
-        tui.draw(&mut app)?;
-        // Handle events.
-        match tui.events.next()? {
-            Event::Tick => app.tick(),
-            Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
-            Event::Resize(_, _) => {}
+        for value in ["disk", "0", "3"] {
+            let args = parse_args(&["--values", "cpu,wall", "--value", value]);
+            assert!(get_parse_options(&args).is_err(), "{}", value);
         }
+        assert!(get_parse_options(&parse_args(&["--truncation-marker", "("])).is_err());
     }
 
-    // Exit the user interface.
-    tui.exit()?;
-    Ok(())
+    #[test]
+    fn test_get_parse_options_sort() {
+        let sorted = |args: &[&str]| get_parse_options(&parse_args(args)).unwrap().sorted;
+        assert!(!sorted(&[]));
+        assert!(sorted(&["--sorted"]));
+        assert!(sorted(&["--sort", "left-heavy"]));
+        assert!(!sorted(&["--sort", "input"]));
+        assert!(Args::try_parse_from(["flamelens", "--sorted", "--sort", "input"]).is_err());
+        #[cfg(feature = "python")]
+        {
+            assert!(sorted(&["--pid", "1"]));
+            assert!(!sorted(&["--pid", "1", "--sort", "input"]));
+        }
+    }
+
+    /// Reader handing out the data a few bytes at a time, then failing if asked to.
+    struct ChunkedReader {
+        data: Vec<u8>,
+        position: usize,
+        chunk_len: usize,
+        fail_at_end: bool,
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.position == self.data.len() && self.fail_at_end {
+                return Err(io::Error::other("connection reset"));
+            }
+            let len = self
+                .chunk_len
+                .min(buf.len())
+                .min(self.data.len() - self.position);
+            buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+            self.position += len;
+            Ok(len)
+        }
+    }
+
+    fn read_chunked(data: &str, fail_at_end: bool) -> (Vec<String>, io::Result<()>) {
+        let reader = ChunkedReader {
+            data: data.as_bytes().to_vec(),
+            position: 0,
+            chunk_len: 3,
+            fail_at_end,
+        };
+        let mut snapshots = vec![];
+        let result = read_stream_snapshots(io::BufReader::with_capacity(4, reader), |snapshot| {
+            snapshots.push(snapshot)
+        });
+        (snapshots, result)
+    }
+
+    #[test]
+    fn test_read_stream_snapshots() {
+        // Lines arriving in pieces are put back together
+        let (snapshots, result) = read_chunked(
+            "main;parse 12\nmain;render 3\n\nmain;parse 20\n\x0c\n\nmain;io 1",
+            false,
+        );
+        assert!(result.is_ok());
+        assert_eq!(
+            snapshots,
+            vec![
+                "main;parse 12\nmain;render 3\n",
+                "main;parse 20\n",
+                "main;io 1\n"
+            ]
+        );
+
+        // A read error ends the stream with the error, dropping the snapshot it cut short
+        let (snapshots, result) = read_chunked("main;parse 12\n\nmain;par", true);
+        assert_eq!(result.unwrap_err().to_string(), "connection reset");
+        assert_eq!(snapshots, vec!["main;parse 12\n"]);
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_stream_conflicts_with_pid() {
+        assert!(Args::try_parse_from(["flamelens", "--stream", "--pid", "1"]).is_err());
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_record_requires_pid() {
+        assert!(Args::try_parse_from(["flamelens", "--record", "samples.folded"]).is_err());
+        let args = parse_args(&["--pid", "1", "--record", "samples.folded"]);
+        assert_eq!(args.record.as_deref(), Some("samples.folded"));
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_should_publish_download() {
+        let interval = DOWNLOAD_PUBLISH_INTERVAL;
+        assert!(!should_publish_download(100, 0, interval / 2));
+        assert!(should_publish_download(100, 0, interval));
+        // Only once the content has doubled since the last preview
+        assert!(!should_publish_download(150, 100, interval * 10));
+        assert!(should_publish_download(200, 100, interval));
+        assert!(!should_publish_download(0, 0, interval));
+    }
+
+    /// Serve a single HTTP response on a local port and return the URL to request it from.
+    #[cfg(feature = "http")]
+    fn serve_once(status: &'static str, body: &'static str) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/profile.folded", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    /// Tick the app until `done` or a few seconds have passed.
+    #[cfg(feature = "http")]
+    fn tick_until(app: &mut App, done: impl Fn(&App) -> bool) {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while !done(app) && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            app.tick();
+        }
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_get_app_from_url() {
+        let url = serve_once("200 OK", "main;parse 30\nmain;render 10\n");
+        let args = parse_args(&[&url]);
+        let downloaded = DownloadedContent::default();
+        let mut app = get_app_from_url(
+            &url,
+            &args,
+            get_parse_options(&args).unwrap(),
+            Some(downloaded.clone()),
+        );
+        tick_until(&mut app, |_| downloaded.lock().unwrap().is_some());
+        app.tick();
+        assert_eq!(app.flamegraph().total_count(), 40);
+        // Kept for --echo until the UI exits rather than printed over it
+        assert_eq!(
+            downloaded.lock().unwrap().as_deref(),
+            Some("main;parse 30\nmain;render 10\n")
+        );
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_get_app_from_url_error() {
+        let url = serve_once("404 Not Found", "");
+        let args = parse_args(&[&url]);
+        let mut app = get_app_from_url(&url, &args, get_parse_options(&args).unwrap(), None);
+        tick_until(&mut app, |app| app.transient_message.is_some());
+        let message = app.transient_message.clone().unwrap();
+        assert!(message.starts_with(&format!("Could not download {}: ", url)));
+        assert!(message.contains("404"), "{}", message);
+    }
 }
@@ -24,6 +24,7 @@ LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
 OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 SOFTWARE.
 */
+use crate::emit::FINISH_TIMEOUT;
 use crate::py_spy_flamegraph::Flamegraph as PySpyFlamegraph;
 use anyhow::Error;
 use py_spy::config::RecordDuration;
@@ -31,9 +32,17 @@ use py_spy::sampler;
 use py_spy::Config;
 use py_spy::Frame;
 use remoteprocess;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
+/// How often a sampler waiting for its next sample checks whether it was asked to stop.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Debug, Clone, Default)]
 pub enum SamplerStatus {
     #[default]
@@ -47,9 +56,21 @@ pub struct SamplerState {
     pub status: SamplerStatus,
     pub total_sampled_duration: Duration,
     pub late: Option<Duration>,
+    /// Set to ask the sampler and poller threads to exit, e.g. when switching to another process
+    pub stop_requested: bool,
+    /// Whether idle threads are sampled too (py-spy's `--idle`), making the flamegraph show
+    /// wall-clock time rather than on-CPU time
+    pub include_idle: bool,
 }
 
 impl SamplerState {
+    pub fn new(include_idle: bool) -> Self {
+        Self {
+            include_idle,
+            ..Default::default()
+        }
+    }
+
     pub fn set_status(&mut self, status: SamplerStatus) {
         self.status = status;
     }
@@ -65,6 +86,63 @@ impl SamplerState {
     pub fn unset_late(&mut self) {
         self.late = None;
     }
+
+    pub fn request_stop(&mut self) {
+        self.stop_requested = true;
+    }
+}
+
+/// Whether the additional "py-spy record" arguments ask to sample idle threads too.
+pub fn includes_idle(py_spy_args: Option<&str>) -> bool {
+    py_spy_args.is_some_and(|args| {
+        args.split_whitespace().any(|arg| {
+            arg == "--idle"
+                || (arg.starts_with('-') && !arg.starts_with("--") && arg[1..].contains('i'))
+        })
+    })
+}
+
+/// Which sampler runs with which settings, for the log. py-spy is built in rather than run as
+/// an external binary, so its arguments are the whole story.
+pub fn describe_sampler(config: &Config, args: &[String]) -> String {
+    format!(
+        "Sampling with the built-in py-spy library at {} Hz ({}), as `{}`",
+        config.sampling_rate,
+        if config.include_idle {
+            "wall-clock"
+        } else {
+            "on-CPU"
+        },
+        args.join(" ")
+    )
+}
+
+/// Command line running flamelens again on the process with elevated privileges.
+fn get_sudo_invocation(pid: remoteprocess::Pid) -> String {
+    let quote = |arg: &str| {
+        if !arg.is_empty()
+            && arg
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c))
+        {
+            arg.to_string()
+        } else {
+            format!("'{}'", arg.replace('\'', r"'\''"))
+        }
+    };
+    let mut args = vec!["sudo".to_string()];
+    let mut original_args = std::env::args();
+    args.extend(original_args.next().map(|program| quote(&program)));
+    while let Some(arg) = original_args.next() {
+        // The PID may have been switched to since starting
+        if arg == "--pid" {
+            original_args.next();
+        } else if !arg.starts_with("--pid=") {
+            args.push(quote(&arg));
+        }
+    }
+    args.push(format!("--pid {}", pid));
+    args.join(" ")
 }
 
 #[derive(Debug)]
@@ -72,14 +150,147 @@ pub struct ProfilerOutput {
     pub data: String,
 }
 
+#[derive(Debug)]
+pub enum RecorderMessage {
+    Data(String),
+    Finish,
+}
+
+/// Keeps a folded stacks file on disk up to date with the latest raw py-spy samples.
+///
+/// The samples are always the full accumulated counts, so each write replaces the file content.
+/// Snapshots are written to a temporary file next to it that is renamed over it, so that the file
+/// always holds a complete snapshot, even if flamelens is killed while writing. Writing happens
+/// on a dedicated thread: senders never block, and intermediate snapshots are skipped if the disk
+/// cannot keep up.
+#[derive(Debug)]
+pub struct Recorder {
+    path: PathBuf,
+    sender: mpsc::Sender<RecorderMessage>,
+    handle: Option<thread::JoinHandle<()>>,
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Recorder {
+    pub fn start(path: &Path) -> std::io::Result<Self> {
+        // Fail early if the file cannot be created at all
+        std::fs::File::create(path)?;
+        let (sender, receiver) = mpsc::channel::<RecorderMessage>();
+        let error = Arc::new(Mutex::new(None));
+        let handle = {
+            let path = path.to_path_buf();
+            let error = error.clone();
+            thread::spawn(move || {
+                while let Ok(message) = receiver.recv() {
+                    // Only the latest snapshot matters
+                    let mut finished = false;
+                    let mut latest = None;
+                    for message in std::iter::once(message).chain(receiver.try_iter()) {
+                        match message {
+                            RecorderMessage::Data(data) => latest = Some(data),
+                            RecorderMessage::Finish => finished = true,
+                        }
+                    }
+                    if let Some(data) = latest {
+                        if let Err(e) = Self::write(&path, &data) {
+                            *error.lock().unwrap() = Some(e.to_string());
+                            break;
+                        }
+                    }
+                    if finished {
+                        break;
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            sender,
+            handle: Some(handle),
+            error,
+        })
+    }
+
+    fn write(path: &Path, data: &str) -> std::io::Result<()> {
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(".tmp");
+        let temp_path = path.with_file_name(temp_name);
+        let mut file = std::fs::File::create(&temp_path)?;
+        file.write_all(data.as_bytes())?;
+        file.write_all(b"\n")?;
+        std::fs::rename(&temp_path, path)
+    }
+
+    pub fn sender(&self) -> mpsc::Sender<RecorderMessage> {
+        self.sender.clone()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    /// Write out any pending snapshot in the background, without waiting for the writer thread.
+    pub fn finish_in_background(mut self) {
+        let _ = self.sender.send(RecorderMessage::Finish);
+        self.handle = None;
+    }
+
+    /// Write out any pending snapshot and wait for the writer thread to exit, for at most
+    /// [`FINISH_TIMEOUT`] as a stalled disk must not hold up quitting.
+    pub fn finish(&mut self) {
+        let _ = self.sender.send(RecorderMessage::Finish);
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        let deadline = Instant::now() + FINISH_TIMEOUT;
+        while !handle.is_finished() && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        if handle.is_finished() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// File that the samples of a process attached to later in the session are recorded to, next to
+/// the one given with `--record`, e.g. `samples.1234.folded` for `samples.folded`.
+pub fn get_recording_path(path: &Path, pid: remoteprocess::Pid) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, pid, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, pid),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Wait for the next sample, or `None` once a stop is requested or the sampler is gone. A stop
+/// is noticed within [`STOP_POLL_INTERVAL`], even while no samples arrive.
+fn next_sample<T>(samples: &mpsc::Receiver<T>, state: &Mutex<SamplerState>) -> Option<T> {
+    loop {
+        if state.lock().unwrap().stop_requested {
+            return None;
+        }
+        match samples.recv_timeout(STOP_POLL_INTERVAL) {
+            Ok(sample) => return Some(sample),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+}
+
 pub fn record_samples(
     pid: remoteprocess::Pid,
     config: &Config,
     output_data: Arc<Mutex<Option<ProfilerOutput>>>,
     state: Arc<Mutex<SamplerState>>,
+    refresh_interval_ms: Arc<AtomicU64>,
 ) {
     state.lock().unwrap().set_status(SamplerStatus::Running);
-    let result = run(pid, config, output_data, state.clone());
+    let result = run(pid, config, output_data, state.clone(), refresh_interval_ms);
     match result {
         Ok(_) => {
             state.lock().unwrap().set_status(SamplerStatus::Done);
@@ -88,16 +299,48 @@ pub fn record_samples(
             state
                 .lock()
                 .unwrap()
-                .set_status(SamplerStatus::Error(format!("{:?}", e)));
+                .set_status(SamplerStatus::Error(describe_error(pid, &e)));
         }
     }
 }
 
+/// User facing explanation of why sampling the process failed.
+fn describe_error(pid: remoteprocess::Pid, e: &Error) -> String {
+    let permission_denied = e.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::PermissionDenied)
+    });
+    let not_python = e.chain().any(|cause| {
+        let cause = cause.to_string().to_lowercase();
+        cause.contains("python version")
+            || cause.contains("python interpreter")
+            || cause.contains("python binary")
+    });
+    if permission_denied {
+        format!(
+            "Permission denied sampling PID {}. You likely need to rerun this program with sudo: {}",
+            pid,
+            get_sudo_invocation(pid)
+        )
+    } else if remoteprocess::Process::new(pid)
+        .and_then(|p| p.exe())
+        .is_err()
+    {
+        format!("PID {} is not a running process", pid)
+    } else if not_python {
+        format!("PID {} is not a running Python process", pid)
+    } else {
+        format!("py-spy sampler exited with error: {:?}", e)
+    }
+}
+
 pub fn run(
     pid: remoteprocess::Pid,
     config: &Config,
     output_data: Arc<Mutex<Option<ProfilerOutput>>>,
     state: Arc<Mutex<SamplerState>>,
+    refresh_interval_ms: Arc<AtomicU64>,
 ) -> Result<(), Error> {
     let mut output = PySpyFlamegraph::new(config.show_line_numbers);
 
@@ -116,7 +359,18 @@ pub fn run(
     let mut last_late_message = std::time::Instant::now();
     let mut last_data_dump: Option<Instant> = None;
 
-    for mut sample in sampler {
+    // py-spy's sampler blocks until the next sample, e.g. forever on a stopped process, so it is
+    // drained on its own thread. That thread exits on its next sample once this one stops.
+    let (sample_sender, samples) = mpsc::channel();
+    thread::spawn(move || {
+        for sample in sampler {
+            if sample_sender.send(sample).is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(mut sample) = next_sample(&samples, &state) {
         if let Some(delay) = sample.late {
             if delay > Duration::from_secs(1) {
                 let now = std::time::Instant::now();
@@ -190,7 +444,7 @@ pub fn run(
         let should_dump = match last_data_dump {
             Some(last_data_dump) => {
                 let elapsed = Instant::now() - last_data_dump;
-                elapsed.as_millis() >= 250
+                elapsed.as_millis() >= refresh_interval_ms.load(Ordering::Relaxed) as u128
             }
             None => true,
         };
@@ -210,3 +464,140 @@ pub fn run(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_includes_idle() {
+        assert!(!includes_idle(None));
+        assert!(!includes_idle(Some("")));
+        assert!(includes_idle(Some("--idle")));
+        assert!(includes_idle(Some("--rate 100 -i")));
+        // Combined short flags
+        assert!(includes_idle(Some("-ri")));
+        assert!(!includes_idle(Some("--rate 100 --native -r")));
+        assert!(!includes_idle(Some("--idle-ish")));
+    }
+
+    #[test]
+    fn test_get_sudo_invocation() {
+        let invocation = get_sudo_invocation(42);
+        assert!(invocation.starts_with("sudo "));
+        assert!(invocation.ends_with(" --pid 42"));
+    }
+
+    #[test]
+    fn test_describe_error() {
+        let pid = std::process::id() as remoteprocess::Pid;
+        let permission_denied =
+            Error::from(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+                .context("failed to open process");
+        let message = describe_error(pid, &permission_denied);
+        assert!(message.starts_with(&format!(
+            "Permission denied sampling PID {}. You likely need to rerun this program with sudo: sudo ",
+            pid
+        )));
+
+        // Beyond the largest PID Linux hands out
+        let missing_pid = 1 << 30;
+        assert_eq!(
+            describe_error(missing_pid, &anyhow::anyhow!("failed to open process")),
+            format!("PID {} is not a running process", missing_pid)
+        );
+
+        assert_eq!(
+            describe_error(pid, &anyhow::anyhow!("Failed to find python version")),
+            format!("PID {} is not a running Python process", pid)
+        );
+        assert!(describe_error(pid, &anyhow::anyhow!("something else"))
+            .starts_with("py-spy sampler exited with error: "));
+    }
+    #[test]
+    fn test_next_sample_stops_without_samples() {
+        let state = Arc::new(Mutex::new(SamplerState::default()));
+        let (sender, samples) = mpsc::channel::<u32>();
+        sender.send(1).unwrap();
+        assert_eq!(next_sample(&samples, &state), Some(1));
+
+        // A sampler that never produces another sample still stops when asked to
+        let handle = {
+            let state = state.clone();
+            thread::spawn(move || next_sample(&samples, &state))
+        };
+        thread::sleep(STOP_POLL_INTERVAL);
+        let tic = Instant::now();
+        state.lock().unwrap().request_stop();
+        assert_eq!(handle.join().unwrap(), None);
+        assert!(tic.elapsed() < 5 * STOP_POLL_INTERVAL);
+        drop(sender);
+    }
+
+    #[test]
+    fn test_next_sample_ends_with_sampler() {
+        let state = Mutex::new(SamplerState::default());
+        let (sender, samples) = mpsc::channel::<u32>();
+        drop(sender);
+        assert_eq!(next_sample(&samples, &state), None);
+    }
+
+    #[test]
+    fn test_get_recording_path() {
+        assert_eq!(
+            get_recording_path(Path::new("/tmp/samples.folded"), 1234),
+            PathBuf::from("/tmp/samples.1234.folded")
+        );
+        assert_eq!(
+            get_recording_path(Path::new("samples"), 1234),
+            PathBuf::from("samples.1234")
+        );
+    }
+
+    #[test]
+    fn test_recorder_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("flamelens-record-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("samples.folded");
+        let mut recorder = Recorder::start(&path).unwrap();
+        let sender = recorder.sender();
+        sender
+            .send(RecorderMessage::Data("main;work 1".to_string()))
+            .unwrap();
+        sender
+            .send(RecorderMessage::Data("main;work 2".to_string()))
+            .unwrap();
+        recorder.finish();
+        assert_eq!(recorder.error(), None);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "main;work 2\n");
+        // Only the recording itself is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(Recorder::start(&dir.join("missing").join("samples.folded")).is_err());
+    }
+
+    #[test]
+    fn test_recorder_finish_in_background() {
+        let dir = std::env::temp_dir().join(format!("flamelens-record-bg-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("samples.folded");
+        let recorder = Recorder::start(&path).unwrap();
+        recorder
+            .sender()
+            .send(RecorderMessage::Data("main;work 3".to_string()))
+            .unwrap();
+        recorder.finish_in_background();
+        // The writer thread still writes out the pending snapshot
+        let deadline = Instant::now() + FINISH_TIMEOUT;
+        while std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .is_empty()
+            && Instant::now() < deadline
+        {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "main;work 3\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,107 @@
-use crate::flame::{FlameGraph, SearchPattern, StackIdentifier, ROOT_ID};
+use crate::flame::{
+    Baseline, FlameGraph, FocusMode, SearchPattern, StackIdentifier, StackInfo, WidthMetric,
+    ROOT_ID,
+};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+/// How frames are assigned their base color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorScheme {
+    /// Hash of the function name, so a function keeps its color across live updates and runs
+    #[default]
+    Name,
+    /// Position of the frame in the layout, which changes as the graph changes
+    Position,
+}
+
+impl ColorScheme {
+    /// Value between 0 and 1 that the base color of the frame is derived from.
+    pub fn get_color_value(&self, flamegraph: &FlameGraph, stack: &StackInfo) -> f64 {
+        // FNV-1a rather than std's DefaultHasher, whose output may change between Rust releases,
+        // so that colors are also stable between runs
+        fn hash_bytes(bytes: &[u8]) -> f64 {
+            let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+            });
+            hash as f64 / u64::MAX as f64
+        }
+        match self {
+            ColorScheme::Name => {
+                hash_bytes(flamegraph.get_stack_short_name_from_info(stack).as_bytes())
+            }
+            ColorScheme::Position => hash_bytes(&stack.id.to_le_bytes()),
+        }
+    }
+}
+
+/// Which part of a frame label that doesn't fit is elided with `…`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LabelTruncation {
+    /// Keep the start, e.g. `std::collections::ha…`
+    #[default]
+    End,
+    /// Keep the leaf (the last `::` or `.` separated segment) and as much of the start as fits,
+    /// e.g. `std::coll…::insert`
+    Middle,
+    /// Keep the end, e.g. `…hash_map::HashMap::insert`
+    Start,
+}
+
+impl LabelTruncation {
+    /// Fit a frame label into `width` characters, eliding the chosen part with `…`.
+    pub fn truncate<'a>(&self, name: &'a str, width: usize) -> Cow<'a, str> {
+        let len = name.chars().count();
+        if len <= width {
+            return Cow::Borrowed(name);
+        }
+        if width == 0 {
+            return Cow::Borrowed("");
+        }
+        let keep = width - 1;
+        let (head_len, tail_len) = match self {
+            LabelTruncation::End => (keep, 0),
+            LabelTruncation::Start => (0, keep),
+            LabelTruncation::Middle => {
+                let leaf_len = name
+                    .rfind("::")
+                    .or_else(|| name.rfind('.'))
+                    .map_or(0, |i| name[i..].chars().count());
+                let tail_len = if leaf_len > 0 && leaf_len <= keep {
+                    leaf_len
+                } else {
+                    keep - keep / 2
+                };
+                (keep - tail_len, tail_len)
+            }
+        };
+        let head_end = name
+            .char_indices()
+            .nth(head_len)
+            .map_or(name.len(), |(i, _)| i);
+        let tail_start = name
+            .char_indices()
+            .nth(len - tail_len)
+            .map_or(name.len(), |(i, _)| i);
+        Cow::Owned(format!("{}…{}", &name[..head_end], &name[tail_start..]))
+    }
+}
+
+/// Area of the terminal in cells, independent of the UI library rendering it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenArea {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl ScreenArea {
+    pub fn contains(&self, column: u16, row: u16) -> bool {
+        (self.x..self.x.saturating_add(self.width)).contains(&column)
+            && (self.y..self.y.saturating_add(self.height)).contains(&row)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct ZoomState {
@@ -14,6 +117,201 @@ impl ZoomState {
     }
 }
 
+/// Frames that appeared or vanished in recent flamegraph replacements
+#[derive(Debug, Clone, Default)]
+pub struct Churn {
+    /// Number of replacements new frames stay marked for
+    pub duration: usize,
+    /// Replacements left to mark each recently appeared frame for, by full name
+    ages: HashMap<String, usize>,
+    /// Recently appeared frames in the current flamegraph
+    pub new_ids: HashSet<StackIdentifier>,
+    /// Number of frames that appeared in the last replacement
+    pub appeared: usize,
+    /// Number of frames that vanished in the last replacement
+    pub vanished: usize,
+}
+
+impl Churn {
+    pub fn new(duration: usize) -> Self {
+        Self {
+            duration: duration.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Age the marked frames and mark the frames of `new` missing from `old`.
+    fn update(&mut self, old: &FlameGraph, new: &FlameGraph) {
+        let old_names = old
+            .stack_full_names()
+            .map(|(_, name)| name)
+            .collect::<HashSet<_>>();
+        self.new_ids.clear();
+        self.appeared = 0;
+        self.vanished = 0;
+        // Everything is new once the first flamegraph arrives, which isn't churn
+        if old_names.is_empty() {
+            return;
+        }
+        let mut ages = HashMap::new();
+        let mut num_kept = 0;
+        for (stack_id, name) in new.stack_full_names() {
+            let age = if old_names.contains(name) {
+                num_kept += 1;
+                self.ages.get(name).map_or(0, |age| age - 1)
+            } else {
+                self.appeared += 1;
+                self.duration
+            };
+            if age > 0 {
+                ages.insert(name.to_string(), age);
+                self.new_ids.insert(stack_id);
+            }
+        }
+        self.vanished = old_names.len() - num_kept;
+        self.ages = ages;
+    }
+}
+
+/// Default number of widest children of each parent kept when grouping siblings
+pub const DEFAULT_GROUP_TOP_K: usize = 8;
+/// Default share of the parent below which children are grouped
+pub const DEFAULT_GROUP_MIN_SHARE: f64 = 0.02;
+
+/// Narrow children of a parent shown as a single "other" frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtherGroup {
+    /// Grouped children, widest first. The first one stands for the group when navigating.
+    pub stack_ids: Vec<StackIdentifier>,
+    pub total_count: u64,
+    pub width_factor: f64,
+}
+
+/// Grouping of the narrowest children of each parent into an "other" frame, so that wide
+/// fan-outs stay readable. Applied when laying out and navigating, the flamegraph is unchanged.
+#[derive(Debug, Clone)]
+pub struct SiblingGrouping {
+    /// Number of widest children of each parent kept
+    pub top_k: usize,
+    /// Share of the parent below which children are grouped even if among the widest
+    pub min_share: f64,
+    /// Full names of the parents whose children are all shown
+    expanded: HashSet<String>,
+    /// Groups by the child standing for them
+    groups: HashMap<StackIdentifier, OtherGroup>,
+    /// Grouped children other than the ones standing for a group, and descendants of grouped
+    /// children
+    hidden: HashSet<StackIdentifier>,
+}
+
+impl SiblingGrouping {
+    pub fn new(top_k: usize, min_share: f64) -> Self {
+        Self {
+            top_k,
+            min_share,
+            expanded: HashSet::new(),
+            groups: HashMap::new(),
+            hidden: HashSet::new(),
+        }
+    }
+
+    /// Group of the stack, if it stands for one.
+    pub fn get_group(&self, stack_id: &StackIdentifier) -> Option<&OtherGroup> {
+        self.groups.get(stack_id)
+    }
+
+    pub fn is_hidden(&self, stack_id: &StackIdentifier) -> bool {
+        self.hidden.contains(stack_id)
+    }
+
+    /// Show all the children of the parent of the group the stack stands for.
+    fn expand(&mut self, stack_id: &StackIdentifier, flamegraph: &FlameGraph) {
+        let Some(parent) = flamegraph
+            .get_stack(stack_id)
+            .and_then(|stack| stack.parent)
+        else {
+            return;
+        };
+        if let Some(parent) = flamegraph.get_stack(&parent) {
+            self.expanded
+                .insert(flamegraph.get_stack_full_name_from_info(parent).to_string());
+            self.update(flamegraph);
+        }
+    }
+
+    /// Recompute the groups, e.g. after changing the settings.
+    pub fn update(&mut self, flamegraph: &FlameGraph) {
+        self.groups.clear();
+        self.hidden.clear();
+        let parents =
+            std::iter::once(ROOT_ID).chain(flamegraph.stack_full_names().map(|(id, _)| id));
+        for parent in parents.filter_map(|stack_id| flamegraph.get_stack(&stack_id)) {
+            if parent.children.len() < 2
+                || self
+                    .expanded
+                    .contains(flamegraph.get_stack_full_name_from_info(parent))
+            {
+                continue;
+            }
+            let mut children = parent
+                .children
+                .iter()
+                .filter_map(|stack_id| flamegraph.get_stack(stack_id))
+                .collect::<Vec<_>>();
+            children.sort_by(|a, b| b.total_count.cmp(&a.total_count).then(a.id.cmp(&b.id)));
+            let grouped = children
+                .iter()
+                .enumerate()
+                .filter(|(i, child)| {
+                    *i >= self.top_k
+                        || (child.total_count as f64) < self.min_share * parent.total_count as f64
+                })
+                .map(|(_, child)| *child)
+                .collect::<Vec<_>>();
+            // Grouping a single child would not save any room
+            if grouped.len() < 2 {
+                continue;
+            }
+            for (i, child) in grouped.iter().enumerate() {
+                let descendants = flamegraph.get_descendants(&child.id);
+                self.hidden.extend(
+                    descendants
+                        .into_iter()
+                        .filter(|id| i > 0 || *id != child.id),
+                );
+            }
+            self.groups.insert(
+                grouped[0].id,
+                OtherGroup {
+                    stack_ids: grouped.iter().map(|child| child.id).collect(),
+                    total_count: grouped.iter().map(|child| child.total_count).sum(),
+                    width_factor: grouped.iter().map(|child| child.width_factor).sum(),
+                },
+            );
+        }
+    }
+}
+
+/// Change of the selected stack since the previous flamegraph replacement
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SelectedDelta {
+    /// Stack the change applies to, which is no longer the selected one after moving away
+    pub stack_id: StackIdentifier,
+    pub count: i64,
+    /// Change of the share of the total, in percentage points
+    pub share: f64,
+}
+
+/// Selection and scroll from before a resize moved them to fit the view port, restored by a
+/// later resize as long as they weren't moved otherwise in between
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ResizeMemory {
+    pub selected: StackIdentifier,
+    pub level_offset: usize,
+    /// Selection and scroll the resize moved to
+    pub moved_to: (StackIdentifier, usize),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ViewKind {
     FlameGraph,
@@ -40,8 +338,53 @@ pub struct FlameGraphState {
     pub frame_height: Option<u16>,
     pub frame_width: Option<u16>,
     pub zoom: Option<ZoomState>,
+    /// Zoom roots left by zooming out one level at a time, innermost last, so that zooming in one
+    /// level at a time walks back down the same path
+    pub zoom_stack: Vec<StackIdentifier>,
     pub search_pattern: Option<SearchPattern>,
+    /// Additional patterns highlighted simultaneously, each with its own color picked by its
+    /// index
+    pub highlight_patterns: Vec<SearchPattern>,
+    /// Frame name whose subtrees are focused on or excluded, dimming the rest or them respectively
+    pub focus: Option<(SearchPattern, FocusMode)>,
     pub freeze: bool,
+    /// Full name of the stack to keep selected across flamegraph replacements, even while it
+    /// is missing from them
+    pub pinned: Option<String>,
+    /// Change of the selected stack in the last replacement, if it was selected in both
+    pub selected_delta: Option<SelectedDelta>,
+    /// Frames that appeared or vanished recently, if marking them is enabled
+    pub churn: Option<Churn>,
+    /// Grouping of narrow siblings into an "other" frame, if enabled
+    pub sibling_grouping: Option<SiblingGrouping>,
+    /// Where the last resize moved the selection and scroll from, if it did
+    pub resize_memory: Option<ResizeMemory>,
+    /// Snapshot to diff live updates against
+    pub baseline: Option<Baseline>,
+    /// Whether to color frames by their change against the baseline
+    pub show_diff: bool,
+    /// Whether to shade the self (exclusive) portion of each frame
+    pub show_self: bool,
+    /// What frame widths are proportional to, applied to every replacing flamegraph
+    pub width_metric: WidthMetric,
+    /// Whether the frames all stacks start with are collapsed into the root. Live updates are
+    /// parsed accordingly
+    pub collapse_common_prefix: bool,
+    /// Whether to dim the frames that are neither ancestors nor descendants of the selected frame
+    pub spotlight: bool,
+    /// Frames with a smaller share of the total are collapsed (hidden)
+    pub prune_threshold: Option<f64>,
+    /// Maximum number of levels rendered below the zoom root. Deeper frames collapse into a
+    /// marker.
+    pub max_render_depth: Option<usize>,
+    /// Whether to show the overview strip of the whole graph when it doesn't fit
+    pub show_minimap: bool,
+    /// Where the overview strip was last rendered, if it is visible
+    pub minimap_area: Option<ScreenArea>,
+    /// Whether the overflow marker below the selected stack is selected rather than the stack
+    pub depth_marker_selected: bool,
+    /// Where the overflow markers were last rendered, with the stack whose children each collapses
+    pub depth_marker_areas: Vec<(ScreenArea, StackIdentifier)>,
     pub view_kind: ViewKind,
     pub table_state: TableState,
 }
@@ -54,8 +397,28 @@ impl Default for FlameGraphState {
             frame_height: None,
             frame_width: None,
             zoom: None,
+            zoom_stack: vec![],
             search_pattern: None,
+            highlight_patterns: vec![],
+            focus: None,
             freeze: false,
+            pinned: None,
+            selected_delta: None,
+            churn: None,
+            sibling_grouping: None,
+            resize_memory: None,
+            baseline: None,
+            show_diff: false,
+            show_self: false,
+            width_metric: WidthMetric::Total,
+            collapse_common_prefix: false,
+            spotlight: false,
+            prune_threshold: None,
+            max_render_depth: None,
+            show_minimap: false,
+            minimap_area: None,
+            depth_marker_selected: false,
+            depth_marker_areas: vec![],
             view_kind: ViewKind::FlameGraph,
             table_state: TableState::default(),
         }
@@ -68,6 +431,9 @@ impl FlameGraphState {
     }
 
     pub fn select_id(&mut self, stack_id: &StackIdentifier) {
+        if self.selected != *stack_id {
+            self.depth_marker_selected = false;
+        }
         self.selected.clone_from(stack_id);
     }
 
@@ -87,10 +453,86 @@ impl FlameGraphState {
         self.search_pattern = None;
     }
 
+    pub fn add_highlight_pattern(&mut self, pattern: SearchPattern) {
+        self.highlight_patterns.push(pattern);
+    }
+
+    pub fn clear_highlight_patterns(&mut self) {
+        self.highlight_patterns.clear();
+    }
+
+    pub fn get_highlight_patterns(&self) -> Vec<SearchPattern> {
+        self.highlight_patterns.clone()
+    }
+
     pub fn toggle_freeze(&mut self) {
         self.freeze = !self.freeze;
     }
 
+    pub fn is_pruned(&self, stack: &StackInfo) -> bool {
+        // Tolerate rounding so that frames as wide as the threshold are kept
+        self.prune_threshold
+            .is_some_and(|threshold| stack.width_factor + 1e-9 < threshold)
+    }
+
+    /// Group the narrow children of each parent into an "other" frame, or show them all again if
+    /// they already are. See [`SiblingGrouping`] for the thresholds.
+    pub fn toggle_sibling_grouping(
+        &mut self,
+        flamegraph: &FlameGraph,
+        top_k: usize,
+        min_share: f64,
+    ) {
+        self.sibling_grouping = match self.sibling_grouping {
+            Some(_) => None,
+            None => {
+                let mut grouping = SiblingGrouping::new(top_k, min_share);
+                grouping.update(flamegraph);
+                Some(grouping)
+            }
+        };
+    }
+
+    /// Show the children grouped with the stack, if it stands for a group.
+    pub fn expand_other_group(&mut self, stack_id: &StackIdentifier, flamegraph: &FlameGraph) {
+        if let Some(grouping) = &mut self.sibling_grouping {
+            grouping.expand(stack_id, flamegraph);
+        }
+    }
+
+    /// Group the stack stands for, if siblings are grouped.
+    pub fn get_other_group(&self, stack_id: &StackIdentifier) -> Option<&OtherGroup> {
+        self.sibling_grouping.as_ref()?.get_group(stack_id)
+    }
+
+    /// Whether the stack is hidden in an "other" frame.
+    pub fn is_grouped_away(&self, stack_id: &StackIdentifier) -> bool {
+        self.sibling_grouping
+            .as_ref()
+            .is_some_and(|grouping| grouping.is_hidden(stack_id))
+    }
+
+    pub fn toggle_minimap(&mut self) {
+        self.show_minimap = !self.show_minimap;
+    }
+
+    pub fn toggle_show_self(&mut self) {
+        self.show_self = !self.show_self;
+    }
+
+    /// Mark frames appearing in later replacements for `duration` replacements, or stop marking
+    /// them if they already are.
+    pub fn toggle_churn(&mut self, duration: usize) {
+        self.churn = match self.churn {
+            Some(_) => None,
+            None => Some(Churn::new(duration)),
+        };
+    }
+
+    pub fn toggle_spotlight(&mut self) {
+        self.spotlight = !self.spotlight;
+    }
+
     pub fn toggle_view_kind(&mut self) {
         self.view_kind = match self.view_kind {
             ViewKind::FlameGraph => ViewKind::Table,
@@ -100,13 +542,36 @@ impl FlameGraphState {
 
     /// Update StackIdentifiers to point to the correct ones in the new flamegraph
     pub fn handle_flamegraph_replacement(&mut self, old: &FlameGraph, new: &mut FlameGraph) {
-        if self.selected != ROOT_ID {
+        let old_selected = self.selected;
+        if let Some(pinned) = &self.pinned {
+            self.selected = Self::get_pinned_stack_id(pinned, new);
+        } else if self.selected != ROOT_ID {
             if let Some(new_stack_id) = Self::get_new_stack_id(&self.selected, old, new) {
                 self.selected = new_stack_id;
             } else {
                 self.select_root();
             }
         }
+        self.selected_delta = Self::get_selected_delta(&old_selected, old, &self.selected, new);
+        if let Some(churn) = &mut self.churn {
+            churn.update(old, new);
+        }
+        if new.width_metric() != self.width_metric {
+            new.set_width_metric(self.width_metric);
+        }
+        if let Some(grouping) = &mut self.sibling_grouping {
+            grouping.update(new);
+        }
+        self.resize_memory = self.resize_memory.and_then(|memory| {
+            Some(ResizeMemory {
+                selected: Self::get_new_stack_id(&memory.selected, old, new)?,
+                moved_to: (
+                    Self::get_new_stack_id(&memory.moved_to.0, old, new)?,
+                    memory.moved_to.1,
+                ),
+                ..memory
+            })
+        });
         if let Some(zoom) = &mut self.zoom {
             if let Some(new_stack_id) = Self::get_new_stack_id(&zoom.stack_id, old, new) {
                 zoom.stack_id = new_stack_id;
@@ -114,11 +579,68 @@ impl FlameGraphState {
                 self.unset_zoom();
             }
         }
+        self.zoom_stack = self
+            .zoom_stack
+            .iter()
+            .filter_map(|stack_id| Self::get_new_stack_id(stack_id, old, new))
+            .collect();
         // Preserve search pattern. If expensive, can move this to next flamegraph construction
         // thread and share SearchPattern via Arc but let's keep it simple for now.
         if let Some(p) = &self.search_pattern {
             new.set_hits(p);
         }
+        new.set_highlights(&self.get_highlight_patterns());
+        if let Some((p, mode)) = &self.focus {
+            new.set_focus(p, *mode);
+        }
+        if let (true, Some(baseline)) = (self.show_diff, &self.baseline) {
+            new.set_diff(baseline);
+        }
+    }
+
+    /// Change of a stack between flamegraphs, if it is the same path in both
+    fn get_selected_delta(
+        old_id: &StackIdentifier,
+        old: &FlameGraph,
+        new_id: &StackIdentifier,
+        new: &FlameGraph,
+    ) -> Option<SelectedDelta> {
+        let old_stack = old.get_stack(old_id)?;
+        let new_stack = new.get_stack(new_id)?;
+        if old.get_stack_full_name_from_info(old_stack)
+            != new.get_stack_full_name_from_info(new_stack)
+        {
+            return None;
+        }
+        let share = |stack: &StackInfo, flamegraph: &FlameGraph| {
+            100.0 * stack.total_count as f64 / flamegraph.total_count().max(1) as f64
+        };
+        Some(SelectedDelta {
+            stack_id: *new_id,
+            count: new_stack.total_count as i64 - old_stack.total_count as i64,
+            share: share(new_stack, new) - share(old_stack, old),
+        })
+    }
+
+    /// Whether the pinned stack is present in the flamegraph
+    pub fn is_pinned_sampled(&self, flamegraph: &FlameGraph) -> bool {
+        self.pinned
+            .as_ref()
+            .is_some_and(|pinned| flamegraph.get_stack_by_full_name(pinned).is_some())
+    }
+
+    /// The pinned stack, or its deepest ancestor present in the flamegraph if it isn't sampled
+    fn get_pinned_stack_id(pinned: &str, new: &FlameGraph) -> StackIdentifier {
+        let mut full_name = pinned;
+        loop {
+            if let Some(stack_id) = new.get_stack_id_by_full_name(full_name) {
+                return stack_id;
+            }
+            match full_name.rsplit_once(';') {
+                Some((parent, _)) => full_name = parent,
+                None => return ROOT_ID,
+            }
+        }
     }
 
     fn get_new_stack_id(
@@ -132,3 +654,94 @@ impl FlameGraphState {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_truncation() {
+        let name = "std::collections::HashMap::insert";
+        assert_eq!(LabelTruncation::End.truncate(name, 40), name);
+        assert_eq!(LabelTruncation::End.truncate(name, 12), "std::collec…");
+        assert_eq!(LabelTruncation::Start.truncate(name, 12), "…Map::insert");
+        // The leaf is kept whole when it fits
+        assert_eq!(LabelTruncation::Middle.truncate(name, 12), "std…::insert");
+        assert_eq!(
+            LabelTruncation::Middle.truncate(name, 16),
+            "std::co…::insert"
+        );
+        // Otherwise the label is elided in the middle
+        assert_eq!(LabelTruncation::Middle.truncate(name, 6), "st…ert");
+    }
+
+    #[test]
+    fn test_label_truncation_narrow_widths() {
+        for truncation in [
+            LabelTruncation::End,
+            LabelTruncation::Middle,
+            LabelTruncation::Start,
+        ] {
+            assert_eq!(truncation.truncate("parse", 0), "");
+            assert_eq!(truncation.truncate("parse", 1), "…");
+            assert_eq!(truncation.truncate("", 0), "");
+        }
+        assert_eq!(LabelTruncation::End.truncate("parse", 2), "p…");
+        assert_eq!(LabelTruncation::Middle.truncate("parse", 2), "…e");
+        assert_eq!(LabelTruncation::Start.truncate("parse", 2), "…e");
+    }
+
+    #[test]
+    fn test_label_truncation_without_separator() {
+        let name = "render_frame_contents";
+        assert_eq!(LabelTruncation::Middle.truncate(name, 9), "rend…ents");
+        assert_eq!(LabelTruncation::End.truncate(name, 9), "render_f…");
+        assert_eq!(LabelTruncation::Start.truncate(name, 9), "…contents");
+        // A `.` separated leaf is kept like a `::` separated one
+        assert_eq!(
+            LabelTruncation::Middle.truncate("http.server.handle", 10),
+            "ht….handle"
+        );
+    }
+
+    #[test]
+    fn test_label_truncation_multi_byte() {
+        let name = "données::größe::файл";
+        assert_eq!(LabelTruncation::End.truncate(name, 5), "donn…");
+        assert_eq!(LabelTruncation::Start.truncate(name, 5), "…файл");
+        assert_eq!(LabelTruncation::Middle.truncate(name, 10), "don…::файл");
+        assert_eq!(LabelTruncation::Middle.truncate("größe", 4), "g…ße");
+        for width in 0..name.chars().count() {
+            assert_eq!(
+                LabelTruncation::Middle
+                    .truncate(name, width)
+                    .chars()
+                    .count(),
+                width
+            );
+        }
+    }
+
+    #[test]
+    fn test_color_scheme_name_is_stable_across_graphs() {
+        let fg1 = FlameGraph::from_string("main;parse 10\nmain;render 5\n".to_string(), false);
+        let fg2 = FlameGraph::from_string(
+            "idle 3\nworker;io 7\nworker;parse 2\nmain;parse 1\n".to_string(),
+            false,
+        );
+        let get_value = |fg: &FlameGraph, full_name: &str, scheme: ColorScheme| {
+            scheme.get_color_value(fg, fg.get_stack_by_full_name(full_name).unwrap())
+        };
+        let parse = get_value(&fg1, "main;parse", ColorScheme::Name);
+        assert_eq!(parse, get_value(&fg2, "main;parse", ColorScheme::Name));
+        // Frames are colored by their name rather than their path
+        assert_eq!(parse, get_value(&fg2, "worker;parse", ColorScheme::Name));
+        assert_ne!(parse, get_value(&fg1, "main;render", ColorScheme::Name));
+        assert!((0.0..=1.0).contains(&parse));
+
+        assert_ne!(
+            get_value(&fg1, "main;parse", ColorScheme::Position),
+            get_value(&fg2, "main;parse", ColorScheme::Position)
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use crate::app::{App, AppResult};
 use crate::event::EventHandler;
 use crate::ui;
-use crossterm::event::DisableMouseCapture;
+use crossterm::event::{DisableMouseCapture, EnableMouseCapture};
 use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
 use ratatui::backend::Backend;
 use ratatui::Terminal;
@@ -18,12 +18,18 @@ pub struct Tui<B: Backend> {
     terminal: Terminal<B>,
     /// Terminal event handler.
     pub events: EventHandler,
+    /// Whether mouse events are captured, which disables the terminal's own text selection
+    mouse_captured: bool,
 }
 
 impl<B: Backend> Tui<B> {
     /// Constructs a new instance of [`Tui`].
     pub fn new(terminal: Terminal<B>, events: EventHandler) -> Self {
-        Self { terminal, events }
+        Self {
+            terminal,
+            events,
+            mouse_captured: false,
+        }
     }
 
     /// Initializes the terminal interface.
@@ -63,6 +69,17 @@ impl<B: Backend> Tui<B> {
                 }
             }
         })?;
+        // Only capture the mouse while the overview strip or overflow markers can be clicked
+        let capture_mouse = app.flamegraph_state().minimap_area.is_some()
+            || !app.flamegraph_state().depth_marker_areas.is_empty();
+        if capture_mouse != self.mouse_captured {
+            if capture_mouse {
+                crossterm::execute!(io::stderr(), EnableMouseCapture)?;
+            } else {
+                crossterm::execute!(io::stderr(), DisableMouseCapture)?;
+            }
+            self.mouse_captured = capture_mouse;
+        }
         Ok(())
     }
 
@@ -1,31 +1,54 @@
 #[cfg(feature = "python")]
-use crate::py_spy::SamplerStatus;
+use crate::py_spy::{SamplerState, SamplerStatus};
 use crate::{
-    app::{App, FlameGraphInput},
-    flame::{SortColumn, StackIdentifier, StackInfo},
-    state::ViewKind,
+    app::{App, FlameGraphInput, InputKind, LegendPanel, SearchMode},
+    flame::{
+        FocusMode, MergedCallTree, SearchScope, SortColumn, StackIdentifier, StackInfo, WidthMetric,
+    },
+    humanize::{format_count, format_count_delta, format_count_with_unit},
+    state::{LabelTruncation, OtherGroup, ScreenArea, ViewKind},
 };
 use ratatui::{
     buffer::Buffer,
     layout::{Alignment, Constraint, Direction, Layout, Offset, Rect},
     style::{Color, Modifier, Style, Stylize},
+    symbols,
     text::{Line, Span, Text},
     widgets::{
-        block::Position, Block, Borders, Paragraph, Row, StatefulWidget, Table, TableState, Widget,
-        Wrap,
+        block::Position, Block, Borders, Cell, Clear, Paragraph, Row, Scrollbar,
+        ScrollbarOrientation, ScrollbarState, StatefulWidget, Table, TableState, Widget, Wrap,
     },
     Frame,
 };
+use std::borrow::Cow;
+use std::collections::HashSet;
 use std::time::Duration;
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
 
 const SEARCH_PREFIX: &str = "";
 const COLOR_SELECTED_STACK: Color = Color::Rgb(250, 250, 250);
 const COLOR_MATCHED_BACKGROUND: Color = Color::Rgb(10, 35, 150);
 const COLOR_TABLE_SELECTED_ROW: Color = Color::Rgb(65, 65, 65);
+const COLOR_TRUNCATED_LEAF: Color = Color::Rgb(120, 120, 140);
+const COLOR_NEW_FRAME: Color = Color::Rgb(40, 200, 90);
+const COLOR_OTHER_GROUP: Color = Color::Rgb(150, 150, 150);
+const MINIMAP_WIDTH: u16 = 4;
+const STYLE_DEPTH_MARKER: Style = Style::new()
+    .fg(Color::White)
+    .bg(Color::Rgb(70, 70, 70))
+    .add_modifier(Modifier::ITALIC);
+/// Colors assigned in turn to additional highlight patterns
+const HIGHLIGHT_COLORS: [Color; 6] = [
+    Color::Rgb(20, 140, 60),
+    Color::Rgb(130, 40, 160),
+    Color::Rgb(0, 130, 140),
+    Color::Rgb(200, 60, 140),
+    Color::Rgb(150, 100, 20),
+    Color::Rgb(90, 90, 90),
+];
+const STYLE_FROZEN_BADGE: Style = Style::new()
+    .fg(Color::Black)
+    .bg(Color::LightCyan)
+    .add_modifier(Modifier::BOLD);
 
 #[derive(Debug, Clone, Default)]
 pub struct FlamelensWidgetState {
@@ -34,6 +57,8 @@ pub struct FlamelensWidgetState {
     render_time: Duration,
     cursor_position: Option<(u16, u16)>,
     log_visible_lines: usize,
+    minimap_area: Option<Rect>,
+    depth_marker_areas: Vec<(Rect, StackIdentifier)>,
 }
 
 pub struct ZoomState {
@@ -43,11 +68,14 @@ pub struct ZoomState {
 
 pub struct FlamelensWidget<'a> {
     pub app: &'a App,
+    /// Ancestors and descendants of the selected frame, including itself, in spotlight mode
+    spotlight: Option<HashSet<StackIdentifier>>,
 }
 
 impl<'a> FlamelensWidget<'a> {
     pub fn new(app: &'a App) -> Self {
-        Self { app }
+        let spotlight = app.flamegraph_view.get_spotlight_path();
+        Self { app, spotlight }
     }
 }
 
@@ -108,6 +136,28 @@ impl<'a> FlamelensWidget<'a> {
             0
         };
 
+        let duplicates_panel_height = match &self.app.duplicates_panel {
+            Some(panel) => (panel.entries.len() as u16 + 2).min((area.height as f32 * 0.4) as u16),
+            None => 0,
+        };
+
+        let butterfly_panel_height = match &self.app.butterfly_panel {
+            Some(panel) => (panel.num_lines() as u16 + 1).min((area.height as f32 * 0.4) as u16),
+            None => 0,
+        };
+
+        let legend_panel_height = match &self.app.legend_panel {
+            Some(_) => (self.app.flamegraph().root().children.len() as u16 + 2)
+                .min((area.height as f32 * 0.4) as u16),
+            None => 0,
+        };
+
+        let path_panel_height = match &self.app.path_panel {
+            Some(panel) => (panel.entries(self.app.flamegraph()).len() as u16 + 2)
+                .min((area.height as f32 * 0.4) as u16),
+            None => 0,
+        };
+
         let mut constraints = vec![
             Constraint::Length(header_line_count_with_borders),
             Constraint::Fill(1),
@@ -116,7 +166,23 @@ impl<'a> FlamelensWidget<'a> {
         if log_panel_height > 0 {
             constraints.push(Constraint::Length(log_panel_height));
         }
+        let duplicates_panel_index = constraints.len();
+        if duplicates_panel_height > 0 {
+            constraints.push(Constraint::Length(duplicates_panel_height));
+        }
+        let butterfly_panel_index = constraints.len();
+        if butterfly_panel_height > 0 {
+            constraints.push(Constraint::Length(butterfly_panel_height));
+        }
+        let legend_panel_index = constraints.len();
+        if legend_panel_height > 0 {
+            constraints.push(Constraint::Length(legend_panel_height));
+        }
 
+        let path_panel_index = constraints.len();
+        if path_panel_height > 0 {
+            constraints.push(Constraint::Length(path_panel_height));
+        }
         // Constraints for context bars
         let context_bar_index_start = constraints.len();
         for bar in context_bars.iter() {
@@ -151,12 +217,26 @@ impl<'a> FlamelensWidget<'a> {
         // Main area for flamegraph / top view
         let tic = std::time::Instant::now();
         let main_area = layout[1];
+        let minimap_area = self.get_minimap_area(main_area);
+        let flamegraph_area = match minimap_area {
+            Some(minimap_area) => Rect {
+                width: main_area.width - minimap_area.width,
+                ..main_area
+            },
+            None => main_area,
+        };
         if self.is_flamegraph_view() {
-            self.render_flamegraph(main_area, buf)
+            let has_more_rows_to_render =
+                self.render_flamegraph(flamegraph_area, buf, &mut state.depth_marker_areas);
+            self.render_scrollbars(flamegraph_area, minimap_area.is_none(), buf);
+            has_more_rows_to_render
         } else {
             self.render_table(main_area, buf);
             false
         };
+        if let Some(minimap_area) = minimap_area {
+            self.render_minimap(minimap_area, buf);
+        }
         let flamegraph_render_time = tic.elapsed();
 
         if log_panel_height > 0 {
@@ -166,6 +246,22 @@ impl<'a> FlamelensWidget<'a> {
                 layout[2].height.saturating_sub(border_overhead) as usize;
         }
 
+        if duplicates_panel_height > 0 {
+            self.render_duplicates_panel(layout[duplicates_panel_index], buf);
+        }
+
+        if butterfly_panel_height > 0 {
+            self.render_butterfly_panel(layout[butterfly_panel_index], buf);
+        }
+
+        if legend_panel_height > 0 {
+            self.render_legend_panel(layout[legend_panel_index], buf);
+        }
+
+        if path_panel_height > 0 {
+            self.render_path_panel(layout[path_panel_index], buf);
+        }
+
         // Context bars
         for (i, bar) in context_bars.iter().enumerate() {
             bar.render(layout[context_bar_index_start + i], buf);
@@ -174,33 +270,132 @@ impl<'a> FlamelensWidget<'a> {
         // Help bar
         help_bar.render(layout[help_bar_index], buf);
 
+        // Completion candidates popup, anchored above the prompt
+        self.render_completion_popup(main_area, buf);
+
         // Update widget state
         state.frame_height = main_area.height;
-        state.frame_width = main_area.width;
+        state.frame_width = flamegraph_area.width;
+        state.minimap_area = minimap_area;
         state.render_time = flamegraph_render_time;
         state.cursor_position = self.get_cursor_position(layout[help_bar_index - 1]);
     }
 
     fn get_help_tags(&self) -> HelpTags {
         let mut help_tags = HelpTags::new();
+        if self.app.confirm_quit {
+            help_tags.add("q/y", "quit");
+            help_tags.add("esc/n", "cancel");
+            return help_tags;
+        }
+        if self.app.butterfly_panel.is_some() {
+            help_tags.add("j/k", "scroll");
+            help_tags.add("esc", "close");
+            return help_tags;
+        }
+        if self.app.legend_panel.is_some() {
+            help_tags.add("j/k", "select");
+            help_tags.add("enter", "zoom into group");
+            help_tags.add("esc", "close");
+            return help_tags;
+        }
+        if self.app.duplicates_panel.is_some() {
+            help_tags.add("j/k", "select");
+            help_tags.add("enter", "highlight occurrences");
+            help_tags.add("esc", "close");
+            return help_tags;
+        }
+        if self.app.path_panel.is_some() {
+            help_tags.add("j/k", "select frame");
+            help_tags.add("enter/esc", "close");
+            return help_tags;
+        }
         if self.is_flamegraph_view() {
             help_tags.add("hjkl", "move cursor");
             help_tags.add("f/b", "scroll");
             help_tags.add("enter/esc", "zoom");
+            help_tags.add("[/]", "zoom out/in a level");
             help_tags.add("/", "search");
             help_tags.add("#", "search like cursor");
+            help_tags.add("a", "path costs");
+            help_tags.add("F/x", "focus/exclude");
+            help_tags.add("&", "add highlight");
+            help_tags.add("d", "duplicates");
+            help_tags.add("v", "callers/callees");
+            help_tags.add("t", "legend");
+            if self.app.flamegraph().value_names().len() > 1 {
+                help_tags.add("m", "switch weight");
+            }
+            if self.app.flamegraph_state().prune_threshold.is_some() {
+                help_tags.add("p", "restore collapsed");
+            } else {
+                help_tags.add("p", "collapse narrower");
+            }
+            help_tags.add("</>", "depth");
+            if self.app.flamegraph_state().sibling_grouping.is_some() {
+                help_tags.add("O", "show all siblings");
+            } else {
+                help_tags.add("O", "group narrow siblings");
+            }
+            if self.app.flamegraph_state().show_minimap {
+                help_tags.add("M", "hide overview");
+            } else {
+                help_tags.add("M", "show overview");
+            }
+            if self.app.flamegraph_state().width_metric == WidthMetric::Own {
+                help_tags.add("w", "width by total");
+            } else {
+                help_tags.add("w", "width by self");
+            }
+            if self.app.flamegraph_state().collapse_common_prefix {
+                help_tags.add("e", "expand common prefix");
+            } else {
+                help_tags.add("e", "collapse common prefix");
+            }
+            if self.app.flamegraph_state().show_self {
+                help_tags.add("s", "hide self time");
+            } else {
+                help_tags.add("s", "show self time");
+            }
+            if self.app.flamegraph_state().spotlight {
+                help_tags.add("S", "spotlight off");
+            } else {
+                help_tags.add("S", "spotlight path");
+            }
+            if !self.app.flamegraph_state().highlight_patterns.is_empty() {
+                help_tags.add("bksp", "clear highlights");
+            }
             if let Some(p) = &self.app.flamegraph_state().search_pattern {
                 if p.is_manual {
                     help_tags.add("n/N", "next/prev search");
                 }
             }
+            if self.app.is_live() {
+                if self.app.flamegraph_state().freeze {
+                    help_tags.add("z/space", "unfreeze");
+                } else {
+                    help_tags.add("z/space", "freeze");
+                }
+            }
             #[cfg(feature = "python")]
             if let FlameGraphInput::Pid(_, _) = self.app.flamegraph_input {
-                if self.app.flamegraph_state().freeze {
-                    help_tags.add("z", "unfreeze");
+                help_tags.add("+/-", "refresh rate");
+            }
+            if self.app.is_live() {
+                if self.app.flamegraph_state().pinned.is_some() {
+                    help_tags.add("P", "unpin");
+                } else {
+                    help_tags.add("P", "pin selected");
+                }
+                if self.app.flamegraph_state().churn.is_some() {
+                    help_tags.add("C", "unmark new frames");
                 } else {
-                    help_tags.add("z", "freeze");
+                    help_tags.add("C", "mark new frames");
                 }
+                help_tags.add("B", "mark baseline");
+            }
+            if self.app.flamegraph_state().baseline.is_some() {
+                help_tags.add("D/U", "toggle/clear diff");
             }
         } else {
             help_tags.add("j/k", "move cursor");
@@ -209,6 +404,13 @@ impl<'a> FlamelensWidget<'a> {
             help_tags.add("2", "sort by own");
             help_tags.add("/", "filter");
         }
+        if !self.app.is_live() {
+            help_tags.add("o", "open file");
+        }
+        #[cfg(feature = "python")]
+        if !matches!(self.app.flamegraph_input, FlameGraphInput::Stream(_)) {
+            help_tags.add("A", "attach to PID");
+        }
         if self.app.has_log_channel {
             if self.app.show_log_panel {
                 help_tags.add("L", "hide logs");
@@ -224,7 +426,12 @@ impl<'a> FlamelensWidget<'a> {
         help_tags
     }
 
-    fn render_flamegraph(&self, area: Rect, buf: &mut Buffer) -> bool {
+    fn render_flamegraph(
+        &self,
+        area: Rect,
+        buf: &mut Buffer,
+        depth_marker_areas: &mut Vec<(Rect, StackIdentifier)>,
+    ) -> bool {
         let zoom_state = self
             .app
             .flamegraph_state()
@@ -240,11 +447,12 @@ impl<'a> FlamelensWidget<'a> {
             .search_pattern
             .as_ref()
             .and_then(|p| {
-                if p.is_manual {
+                if p.matches_frame_names() {
                     Some(&p.re)
                 } else {
                     // Don't highlight if the whole stack is expected to be matched (this is
-                    // when auto-searching while navigating between stacks)
+                    // when auto-searching while navigating between stacks) or if the pattern is
+                    // matched against full paths
                     None
                 }
             });
@@ -257,10 +465,95 @@ impl<'a> FlamelensWidget<'a> {
             area.bottom(),
             &zoom_state,
             &re,
+            depth_marker_areas,
         );
         has_more_rows_to_render
     }
 
+    /// Render scrollbars over the right edge if the graph is deeper than the view port, unless
+    /// the overview strip shows that already, and over the bottom edge if zoomed in.
+    fn render_scrollbars(&self, area: Rect, vertical: bool, buf: &mut Buffer) {
+        let style = Style::default().fg(Color::DarkGray);
+        let num_levels = self.app.flamegraph().get_num_levels();
+        let height = area.height as usize;
+        if vertical && num_levels > height {
+            let mut scrollbar_state = ScrollbarState::new(num_levels - height + 1)
+                .position(self.app.flamegraph_state().level_offset)
+                .viewport_content_length(height);
+            Scrollbar::new(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(None)
+                .end_symbol(None)
+                .style(style)
+                .render(area, buf, &mut scrollbar_state);
+        }
+        if let Some((start, width)) = self.app.flamegraph_view.get_zoom_extent() {
+            // Whole width of the graph at the zoom level, in columns
+            let full_width = (area.width as f64 / width).round() as usize;
+            let viewport_width = area.width as usize;
+            if full_width > viewport_width {
+                let mut scrollbar_state = ScrollbarState::new(full_width - viewport_width + 1)
+                    .position((start * full_width as f64).round() as usize)
+                    .viewport_content_length(viewport_width);
+                Scrollbar::new(ScrollbarOrientation::HorizontalBottom)
+                    .symbols(symbols::scrollbar::HORIZONTAL)
+                    .begin_symbol(None)
+                    .end_symbol(None)
+                    .style(style)
+                    .render(area, buf, &mut scrollbar_state);
+            }
+        }
+    }
+
+    /// Area of the overview strip at the right edge, shown only if the graph is deeper than the
+    /// view port.
+    fn get_minimap_area(&self, area: Rect) -> Option<Rect> {
+        if !self.app.flamegraph_state().show_minimap
+            || !self.is_flamegraph_view()
+            || area.width < MINIMAP_WIDTH * 4
+            || self.app.flamegraph().get_num_levels() <= area.height as usize
+        {
+            return None;
+        }
+        Some(Rect {
+            x: area.right() - MINIMAP_WIDTH,
+            width: MINIMAP_WIDTH,
+            ..area
+        })
+    }
+
+    /// Render the whole graph's depth squeezed into the strip, each row colored by how much of the
+    /// width its levels cover, with a marker for the rows in the view port.
+    fn render_minimap(&self, area: Rect, buf: &mut Buffer) {
+        let coverage = self.app.flamegraph().level_coverage();
+        let num_levels = coverage.len();
+        let height = area.height as usize;
+        let level_offset = self.app.flamegraph_state().level_offset;
+        for row in 0..height {
+            let start = row * num_levels / height;
+            let end = ((row + 1) * num_levels / height).clamp(start + 1, num_levels);
+            let heat = coverage[start..end].iter().copied().fold(0.0, f64::max);
+            let in_view_port = start < level_offset + height && end > level_offset;
+            let y = area.y + row as u16;
+            let (marker, marker_style) = if in_view_port {
+                ("┃", Style::default().fg(Color::White).bold())
+            } else {
+                ("│", Style::default().fg(Color::DarkGray))
+            };
+            buf.set_string(area.x, y, marker, marker_style);
+            let heat_area = Rect::new(area.x + 1, y, area.width - 1, 1);
+            buf.set_style(heat_area, Style::default().bg(Self::get_heat_color(heat)));
+        }
+    }
+
+    fn get_heat_color(heat: f64) -> Color {
+        let heat = heat.clamp(0.0, 1.0);
+        Color::Rgb(
+            (50.0 + 205.0 * heat) as u8,
+            (50.0 + 90.0 * heat) as u8,
+            (50.0 - 40.0 * heat) as u8,
+        )
+    }
+
     fn render_table(&self, area: Rect, buf: &mut Buffer) {
         let ordered_stacks_table = self.get_ordered_stacks_table();
         let mut table_state = TableState::default()
@@ -269,6 +562,254 @@ impl<'a> FlamelensWidget<'a> {
         StatefulWidget::render(ordered_stacks_table, area, buf, &mut table_state);
     }
 
+    fn render_completion_popup(&self, area: Rect, buf: &mut Buffer) {
+        let Some(completion) = self
+            .app
+            .input_buffer
+            .as_ref()
+            .and_then(|input| input.completion.as_ref())
+        else {
+            return;
+        };
+        const MAX_ROWS: usize = 10;
+        let selected = completion.selected.unwrap_or(0);
+        let skip = (selected + 1).saturating_sub(MAX_ROWS);
+        let lines = completion
+            .candidates
+            .iter()
+            .enumerate()
+            .skip(skip)
+            .take(MAX_ROWS)
+            .map(|(i, candidate)| {
+                if completion.selected == Some(i) {
+                    Line::styled(candidate.as_str(), Style::default().reversed())
+                } else {
+                    Line::from(candidate.as_str())
+                }
+            })
+            .collect::<Vec<_>>();
+        let width = lines.iter().map(|line| line.width()).max().unwrap_or(0) as u16 + 2;
+        let height = lines.len() as u16 + 2;
+        let popup = Rect {
+            x: area.x,
+            y: area.bottom().saturating_sub(height),
+            width: width.min(area.width),
+            height: height.min(area.height),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} candidates ", completion.candidates.len()));
+        Clear.render(popup, buf);
+        Paragraph::new(lines).block(block).render(popup, buf);
+    }
+
+    fn render_duplicates_panel(&self, area: Rect, buf: &mut Buffer) {
+        let Some(panel) = &self.app.duplicates_panel else {
+            return;
+        };
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .title(format!(" Duplicated subtrees ({}) ", panel.entries.len()))
+            .title_style(Style::default().add_modifier(Modifier::BOLD).yellow())
+            .title_position(Position::Top);
+        let header = Row::new(vec!["Combined", "Places", "Name"]).style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+        );
+        let rows = panel
+            .entries
+            .iter()
+            .map(|entry| {
+                Row::new(vec![
+                    format!(
+                        "{} ({:.2}%)  ",
+                        format_count(
+                            entry.total_count,
+                            self.app.count_unit,
+                            self.app.humanize_counts
+                        ),
+                        100.0 * entry.total_count as f64 / panel.total_count as f64
+                    ),
+                    format!("{}  ", entry.occurrences),
+                    entry.name.clone(),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let widths = [
+            Constraint::Length(24),
+            Constraint::Length(8),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::default().bg(COLOR_TABLE_SELECTED_ROW));
+        let mut table_state = TableState::default().with_selected(panel.selected);
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+
+    /// Render the top-level frames with their color and share of the total, heaviest first.
+    fn render_legend_panel(&self, area: Rect, buf: &mut Buffer) {
+        let Some(panel) = &self.app.legend_panel else {
+            return;
+        };
+        let flamegraph = self.app.flamegraph();
+        let entries = LegendPanel::entries(flamegraph);
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .title(format!(" Legend ({}) ", entries.len()))
+            .title_style(Style::default().add_modifier(Modifier::BOLD).yellow())
+            .title_position(Position::Top);
+        let header = Row::new(vec!["", "Share", "Name"]).style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+        );
+        let total_count = flamegraph.total_count().max(1);
+        let rows = entries
+            .iter()
+            .map(|stack| {
+                Row::new(vec![
+                    Cell::from("██").style(Style::default().fg(self.get_stack_color(stack, &None))),
+                    Cell::from(format!(
+                        "{} ({:.2}%)  ",
+                        format_count(
+                            stack.total_count,
+                            self.app.count_unit,
+                            self.app.humanize_counts
+                        ),
+                        100.0 * stack.total_count as f64 / total_count as f64
+                    )),
+                    Cell::from(flamegraph.get_stack_short_name_from_info(stack)),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let widths = [
+            Constraint::Length(3),
+            Constraint::Length(24),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::default().bg(COLOR_TABLE_SELECTED_ROW));
+        let selected = (!entries.is_empty()).then(|| panel.selected_index(flamegraph));
+        let mut table_state = TableState::default().with_selected(selected);
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+
+    /// Render the merged callers and callees of a function side by side.
+    fn render_butterfly_panel(&self, area: Rect, buf: &mut Buffer) {
+        let Some(panel) = &self.app.butterfly_panel else {
+            return;
+        };
+        let halves = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let trees = [
+            ("Callers of", &panel.butterfly.callers),
+            ("Callees of", &panel.butterfly.callees),
+        ];
+        for ((title, tree), half) in trees.iter().zip(halves.iter()) {
+            let mut lines = vec![];
+            self.get_tree_lines(tree, 0, panel.total_count, &mut lines);
+            let block = Block::new()
+                .borders(Borders::TOP)
+                .title(format!(" {} {} ", title, tree.name))
+                .title_style(Style::default().add_modifier(Modifier::BOLD).yellow())
+                .title_position(Position::Top);
+            Paragraph::new(lines)
+                .block(block)
+                .scroll((panel.offset.min(u16::MAX as usize) as u16, 0))
+                .render(*half, buf);
+        }
+    }
+
+    fn get_tree_lines(
+        &self,
+        tree: &MergedCallTree,
+        depth: usize,
+        total_count: u64,
+        lines: &mut Vec<Line<'_>>,
+    ) {
+        lines.push(Line::from(format!(
+            "{:indent$}{}  {} ({:.2}%)",
+            "",
+            tree.name,
+            format_count(
+                tree.total_count,
+                self.app.count_unit,
+                self.app.humanize_counts
+            ),
+            100.0 * tree.total_count as f64 / total_count as f64,
+            indent = depth * 2,
+        )));
+        for child in tree.children.iter() {
+            self.get_tree_lines(child, depth + 1, total_count, lines);
+        }
+    }
+
+    /// Render the frames from the root down to the one the panel was opened on, each with its
+    /// share of the total and the share of it the next level down takes.
+    fn render_path_panel(&self, area: Rect, buf: &mut Buffer) {
+        let Some(panel) = &self.app.path_panel else {
+            return;
+        };
+        let flamegraph = self.app.flamegraph();
+        let levels = panel.levels(flamegraph);
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .title(format!(" Path ({} levels) ", levels.len()))
+            .title_style(Style::default().add_modifier(Modifier::BOLD).yellow())
+            .title_position(Position::Top);
+        let header = Row::new(vec!["Total", "Next level", "Name"]).style(
+            Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+        );
+        let rows = levels
+            .iter()
+            .enumerate()
+            .map(|(i, level)| {
+                let next_level = match level.next_level_share {
+                    Some(share) => format!("{:.2}%  ", 100.0 * share),
+                    None => String::new(),
+                };
+                Row::new(vec![
+                    format!(
+                        "{} ({:.2}%)  ",
+                        format_count(
+                            level.stack.total_count,
+                            self.app.count_unit,
+                            self.app.humanize_counts
+                        ),
+                        100.0 * level.share_of_total
+                    ),
+                    next_level,
+                    format!(
+                        "{}{}",
+                        " ".repeat(i.min(20)),
+                        flamegraph.get_stack_short_name_from_info(level.stack)
+                    ),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let widths = [
+            Constraint::Length(24),
+            Constraint::Length(12),
+            Constraint::Fill(1),
+        ];
+        let table = Table::new(rows, widths)
+            .header(header)
+            .block(block)
+            .row_highlight_style(Style::default().bg(COLOR_TABLE_SELECTED_ROW));
+        let selected = panel.selected.min(levels.len().saturating_sub(1));
+        let mut table_state = TableState::default().with_selected(selected);
+        StatefulWidget::render(table, area, buf, &mut table_state);
+    }
+
     fn render_log_panel(&self, area: Rect, buf: &mut Buffer) {
         let offset = self.app.log_scroll_offset;
         let title = if let Some(text) = &self.app.log_search_text {
@@ -349,7 +890,11 @@ impl<'a> FlamelensWidget<'a> {
         y_max: u16,
         zoom_state: &Option<ZoomState>,
         re: &Option<&regex::Regex>,
+        depth_marker_areas: &mut Vec<(Rect, StackIdentifier)>,
     ) -> bool {
+        if self.app.flamegraph_state().is_pruned(stack) {
+            return false;
+        }
         let after_level_offset = stack.level >= self.app.flamegraph_state().level_offset;
 
         // Only render if the stack is visible
@@ -361,6 +906,32 @@ impl<'a> FlamelensWidget<'a> {
                 let style = Style::default().fg(text_color).bg(stack_color);
                 let line = self.get_line_for_stack(stack, effective_x_budget, style, re);
                 buf.set_line(x, y, &line, effective_x_budget);
+                if self.app.flamegraph_state().show_self && stack.total_count > 0 {
+                    self.shade_self_portion(stack, buf, x, y, x_budget, stack_color);
+                }
+            }
+            if self.app.flamegraph_view.get_max_render_level() == Some(stack.level) {
+                // Collapse everything below into a marker instead of rendering children
+                if !stack.children.is_empty() && after_level_offset && y + 1 < y_max {
+                    let depth_below = self.app.flamegraph().get_depth_below(&stack.id);
+                    let marker = format!(
+                        "{:width$}",
+                        format!(" ▼ {} more level(s) below", depth_below),
+                        width = effective_x_budget as usize
+                    );
+                    let view = &self.app.flamegraph_view;
+                    let style =
+                        if view.is_depth_marker_selected() && view.state.selected == stack.id {
+                            STYLE_DEPTH_MARKER
+                                .fg(FlamelensWidget::get_text_color(COLOR_SELECTED_STACK))
+                                .bg(COLOR_SELECTED_STACK)
+                        } else {
+                            STYLE_DEPTH_MARKER
+                        };
+                    buf.set_stringn(x, y + 1, marker, effective_x_budget as usize, style);
+                    depth_marker_areas.push((Rect::new(x, y + 1, effective_x_budget, 1), stack.id));
+                }
+                return false;
             }
         } else {
             // Can skip rendering children if the stack is already not visible
@@ -385,6 +956,29 @@ impl<'a> FlamelensWidget<'a> {
         let mut has_more_rows_to_render = false;
         for child in &stack.children {
             let child_stack = self.app.flamegraph().get_stack(child).unwrap();
+            let child_y = y + if after_level_offset { 1 } else { 0 };
+            if zoomed_child.is_none() {
+                let state = self.app.flamegraph_state();
+                if state.is_grouped_away(child) {
+                    continue;
+                }
+                if let Some(group) = state.get_other_group(child) {
+                    let group_x_budget = x_budget * (group.width_factor / stack.width_factor);
+                    if child_y < y_max && group_x_budget as u16 > 0 {
+                        self.render_other_group(
+                            child_stack,
+                            group,
+                            buf,
+                            x + x_offset,
+                            child_y,
+                            group_x_budget,
+                        );
+                    }
+                    has_more_rows_to_render |= child_y >= y_max && group_x_budget as u16 > 0;
+                    x_offset += group_x_budget as u16;
+                    continue;
+                }
+            }
             let child_x_budget = if let Some(zoomed_child_id) = zoomed_child {
                 // Zoomer takes all
                 if zoomed_child_id == *child {
@@ -393,17 +987,21 @@ impl<'a> FlamelensWidget<'a> {
                     0.0
                 }
             } else {
-                x_budget * (child_stack.total_count as f64 / stack.total_count as f64)
+                let flamegraph = self.app.flamegraph();
+                x_budget
+                    * (flamegraph.width_count(child_stack) as f64
+                        / flamegraph.width_count(stack) as f64)
             };
             has_more_rows_to_render |= self.render_stacks(
                 child_stack,
                 buf,
                 x + x_offset,
-                y + if after_level_offset { 1 } else { 0 },
+                child_y,
                 child_x_budget,
                 y_max,
                 zoom_state,
                 re,
+                depth_marker_areas,
             );
             x_offset += child_x_budget as u16;
         }
@@ -411,6 +1009,68 @@ impl<'a> FlamelensWidget<'a> {
         has_more_rows_to_render
     }
 
+    /// Render the narrow siblings grouped with `stack` as a single "other" frame.
+    fn render_other_group(
+        &self,
+        stack: &StackInfo,
+        group: &OtherGroup,
+        buf: &mut Buffer,
+        x: u16,
+        y: u16,
+        x_budget: f64,
+    ) {
+        let width = x_budget as u16;
+        let color = if self.app.flamegraph_state().selected == stack.id {
+            COLOR_SELECTED_STACK
+        } else {
+            COLOR_OTHER_GROUP
+        };
+        let style = Style::default()
+            .fg(FlamelensWidget::get_text_color(color))
+            .bg(color);
+        let label = format!("other ({})", group.stack_ids.len());
+        let label = LabelTruncation::End.truncate(&label, width.saturating_sub(1) as usize);
+        let text = format!(
+            "{}{:width$}",
+            if width > 1 { " " } else { "." },
+            label,
+            width = width.saturating_sub(1) as usize
+        );
+        buf.set_stringn(x, y, text, width as usize, style);
+    }
+
+    /// Darken the part of the frame not covered by its children, i.e. its self time. Children
+    /// are laid out from the left so this is the right end of the frame.
+    fn shade_self_portion(
+        &self,
+        stack: &StackInfo,
+        buf: &mut Buffer,
+        x: u16,
+        y: u16,
+        x_budget: f64,
+        stack_color: Color,
+    ) {
+        let width = x_budget as u16;
+        let self_width =
+            ((x_budget * self.app.flamegraph().self_width_share(stack)).round() as u16).min(width);
+        if self_width == 0 {
+            return;
+        }
+        let (r, g, b) = FlamelensWidget::get_rgb(stack_color);
+        let shade = Color::Rgb(
+            (r as f64 * 0.65) as u8,
+            (g as f64 * 0.65) as u8,
+            (b as f64 * 0.65) as u8,
+        );
+        let area = Rect::new(x + width - self_width, y, self_width, 1).intersection(buf.area);
+        buf.set_style(
+            area,
+            Style::default()
+                .bg(shade)
+                .fg(FlamelensWidget::get_text_color(shade)),
+        );
+    }
+
     fn get_ordered_stacks_table(&self) -> Table<'_> {
         let add_sorted_indicator = |label: &str, sort_column: SortColumn| {
             let suffix = if sort_column == self.app.flamegraph().ordered_stacks.sorted_column {
@@ -436,21 +1096,21 @@ impl<'a> FlamelensWidget<'a> {
         let mut total_max_width: u16 = 0;
         let mut own_max_width: u16 = 0;
 
-        fn format_count(count: u64, total_count: u64) -> String {
+        let format_entry_count = |count: u64| {
             format!(
                 "{} ({:.2}%)  ",
-                count,
+                format_count(count, self.app.count_unit, self.app.humanize_counts),
                 100.0 * count as f64 / total_count as f64
             )
-        }
+        };
 
         for entry in counts.iter().filter(|entry| entry.visible) {
-            let total_formatted = Line::from(format_count(entry.count.total, total_count));
-            let own_formatted = Line::from(format_count(entry.count.own, total_count));
+            let total_formatted = Line::from(format_entry_count(entry.count.total));
+            let own_formatted = Line::from(format_entry_count(entry.count.own));
             total_max_width = total_max_width.max(total_formatted.width() as u16);
             own_max_width = own_max_width.max(own_formatted.width() as u16);
             let name_formatted = if let Some(p) = &self.app.flamegraph_state().search_pattern {
-                if p.is_manual {
+                if p.matches_frame_names() {
                     Line::from(self.get_highlighted_spans(
                         entry.name.as_str(),
                         &p.re,
@@ -502,6 +1162,18 @@ impl<'a> FlamelensWidget<'a> {
         spans
     }
 
+    fn get_label_spans<'b>(
+        &self,
+        label: &'b str,
+        re: Option<&regex::Regex>,
+        style: Style,
+    ) -> Vec<Span<'b>> {
+        match re {
+            Some(re) => self.get_highlighted_spans(label, re, style),
+            None => vec![Span::styled(label, style)],
+        }
+    }
+
     fn get_line_for_stack(
         &self,
         stack: &StackInfo,
@@ -510,22 +1182,28 @@ impl<'a> FlamelensWidget<'a> {
         re: &Option<&regex::Regex>,
     ) -> Line<'_> {
         let short_name = self.app.flamegraph().get_stack_short_name_from_info(stack);
+        let label = self
+            .app
+            .label_truncation
+            .truncate(short_name, width.saturating_sub(1) as usize);
+        let label_len = label.chars().count();
 
         // Empty space separator at the beginning
         let mut spans = vec![Span::styled(if width > 1 { " " } else { "." }, style)];
 
         // Stack name with highlighted search terms if needed
-        let short_name_spans = if let (true, &Some(re)) = (stack.hit, re) {
-            self.get_highlighted_spans(short_name, re, style)
-        } else {
-            vec![Span::styled(short_name, style)]
-        };
-        spans.extend(short_name_spans);
+        let re = re.filter(|_| stack.hit);
+        match label {
+            Cow::Borrowed(label) => spans.extend(self.get_label_spans(label, re, style)),
+            Cow::Owned(label) => spans.extend(
+                self.get_label_spans(&label, re, style)
+                    .into_iter()
+                    .map(|span| Span::styled(span.content.into_owned(), span.style)),
+            ),
+        }
 
         // Padding to fill the rest of the width
-        let pad_length = width
-            .saturating_sub(short_name.len() as u16)
-            .saturating_sub(1) as usize;
+        let pad_length = width.saturating_sub(label_len as u16).saturating_sub(1) as usize;
         spans.push(Span::styled(
             format!("{:width$}", "", width = pad_length),
             style,
@@ -539,38 +1217,82 @@ impl<'a> FlamelensWidget<'a> {
             return COLOR_SELECTED_STACK;
         }
         // Roughly based on flamegraph.pl
-        fn hash_name(name: &str) -> f64 {
-            let mut hasher = DefaultHasher::new();
-            name.hash(&mut hasher);
-            hasher.finish() as f64 / u64::MAX as f64
-        }
-        let full_name = self.app.flamegraph().get_stack_full_name_from_info(stack);
-        let v1 = hash_name(full_name);
-        let v2 = hash_name(full_name);
+        let v = self
+            .app
+            .color_scheme
+            .get_color_value(self.app.flamegraph(), stack);
         let mut r;
         let mut g;
         let mut b;
-        if !stack.hit {
-            r = 205 + (50.0 * v2) as u8;
-            g = (230.0 * v1) as u8;
-            b = (55.0 * v2) as u8;
-        } else if let Color::Rgb(r_, g_, b_) = COLOR_MATCHED_BACKGROUND {
-            r = r_;
-            g = g_;
-            b = b_;
+        let diff_color = self.get_diff_color(stack);
+        let highlight_color = self
+            .app
+            .flamegraph()
+            .get_highlight(&stack.id)
+            .map(get_highlight_color);
+        if stack.hit {
+            (r, g, b) = FlamelensWidget::get_rgb(COLOR_MATCHED_BACKGROUND);
+        } else if let Some(color) = highlight_color {
+            (r, g, b) = FlamelensWidget::get_rgb(color);
+        } else if self
+            .app
+            .flamegraph_state()
+            .churn
+            .as_ref()
+            .is_some_and(|churn| churn.new_ids.contains(&stack.id))
+        {
+            (r, g, b) = FlamelensWidget::get_rgb(COLOR_NEW_FRAME);
+        } else if let Some(color) = diff_color {
+            (r, g, b) = FlamelensWidget::get_rgb(color);
+        } else if self.app.flamegraph().is_truncated_leaf(&stack.id) {
+            (r, g, b) = FlamelensWidget::get_rgb(COLOR_TRUNCATED_LEAF);
         } else {
-            unreachable!();
+            r = 205 + (50.0 * v) as u8;
+            g = (230.0 * v) as u8;
+            b = (55.0 * v) as u8;
         }
-        if let Some(zoom_state) = zoom_state {
-            if zoom_state.ancestors.contains(&stack.id) {
-                r = (r as f64 / 2.5) as u8;
-                g = (g as f64 / 2.5) as u8;
-                b = (b as f64 / 2.5) as u8;
-            }
+        let off_spotlight = self
+            .spotlight
+            .as_ref()
+            .is_some_and(|on_path| self.app.flamegraph_view.is_off_spotlight(on_path, stack));
+        let dimmed = self.app.flamegraph().is_dimmed(&stack.id)
+            || off_spotlight
+            || zoom_state
+                .as_ref()
+                .is_some_and(|zoom_state| zoom_state.ancestors.contains(&stack.id));
+        if dimmed {
+            r = (r as f64 / 2.5) as u8;
+            g = (g as f64 / 2.5) as u8;
+            b = (b as f64 / 2.5) as u8;
         }
         Color::Rgb(r, g, b)
     }
 
+    /// Red if the stack's share grew since the baseline, blue if it shrank, white if unchanged.
+    fn get_diff_color(&self, stack: &StackInfo) -> Option<Color> {
+        let diff = self.app.flamegraph().get_diff(&stack.id)?;
+        let max_abs_diff = self.app.flamegraph().max_abs_diff();
+        let intensity = if max_abs_diff > 0.0 {
+            (200.0 * diff.abs() / max_abs_diff) as u8
+        } else {
+            0
+        };
+        let faded = 255 - intensity;
+        if diff > 0.0 {
+            Some(Color::Rgb(255, faded, faded))
+        } else {
+            Some(Color::Rgb(faded, faded, 255))
+        }
+    }
+
+    fn get_rgb(c: Color) -> (u8, u8, u8) {
+        if let Color::Rgb(r, g, b) = c {
+            (r, g, b)
+        } else {
+            unreachable!();
+        }
+    }
+
     fn get_text_color(c: Color) -> Color {
         match c {
             Color::Rgb(r, g, b) => {
@@ -624,26 +1346,34 @@ impl<'a> FlamelensWidget<'a> {
     fn get_header_text(&self, _width: u16) -> Line<'_> {
         let header_text = match &self.app.flamegraph_input {
             FlameGraphInput::File(path) => path.to_string(),
-            FlameGraphInput::Pid(pid, info) => {
-                let mut out = format!("Process: {}", pid);
-                if let Some(info) = info {
-                    out += format!(" [{}]", info).as_str();
+            FlameGraphInput::Stream(title) => {
+                let mut out = title.to_string();
+                if self.app.flamegraph_state().freeze {
+                    out += " [Frozen; press 'z' or space again to unfreeze]";
                 }
+                out
+            }
+            FlameGraphInput::Pid(pid, info) => {
+                #[cfg_attr(not(feature = "python"), allow(unused_mut))]
+                let mut out = get_process_header(*pid, info.as_deref());
                 #[cfg(feature = "python")]
                 if let Some(state) = &self.app.sampler_state() {
-                    out += match state.status {
-                        SamplerStatus::Running => " [Running]".to_string(),
-                        _ => " [Exited]".to_string(),
-                    }
-                    .as_str();
-                    let duration = state.total_sampled_duration;
-                    let seconds = duration.as_secs() % 60;
-                    let minutes = (duration.as_secs() / 60) % 60;
-                    let hours = (duration.as_secs() / 60) / 60;
-                    out += format!(" [Duration: {:0>2}:{:0>2}:{:0>2}]", hours, minutes, seconds)
+                    let total_count = format_count_with_unit(
+                        self.app.flamegraph().total_count(),
+                        self.app.count_unit,
+                        self.app.humanize_counts,
+                    );
+                    out += get_sampler_header(state, &total_count).as_str();
+                    out += format!(" [Refresh: {}ms]", self.app.refresh_interval_ms()).as_str();
+                    if let Some(recorder) = self.app.recorder() {
+                        out += match recorder.error() {
+                            Some(e) => format!(" [Recording failed: {}]", e),
+                            None => format!(" [Recording: {}]", recorder.path().display()),
+                        }
                         .as_str();
+                    }
                     if self.app.flamegraph_state().freeze {
-                        out += " [Frozen; press 'z' again to unfreeze]";
+                        out += " [Frozen; press 'z' or space again to unfreeze]";
                     }
                 }
                 out
@@ -653,7 +1383,15 @@ impl<'a> FlamelensWidget<'a> {
     }
 
     fn get_status_text(&self, width: u16) -> Vec<(&'static str, Line<'_>)> {
-        if self.app.log_input_buffer.is_some() {
+        if self.app.confirm_quit {
+            vec![(
+                "Quit",
+                Line::from(
+                    "The live samples will be lost. Press 'q' again to quit or Esc to cancel",
+                )
+                .style(Style::default().bold()),
+            )]
+        } else if self.app.log_input_buffer.is_some() {
             self.get_status_text_log_buffer()
         } else if self.app.input_buffer.is_some() {
             self.get_status_text_buffer()
@@ -665,7 +1403,34 @@ impl<'a> FlamelensWidget<'a> {
     fn get_status_text_buffer(&self) -> Vec<(&'static str, Line<'_>)> {
         let input_buffer = self.app.input_buffer.as_ref().unwrap();
         let status_text = format!("{}{}", SEARCH_PREFIX, input_buffer.buffer);
-        vec![("Search", Line::from(status_text))]
+        let title = match input_buffer.kind {
+            InputKind::Search => match (input_buffer.search_scope, input_buffer.search_mode) {
+                (SearchScope::Frame, SearchMode::Regex) => {
+                    "Search [regex] (tab: match full paths, ctrl+r: plain text)"
+                }
+                (SearchScope::Frame, SearchMode::Text) => {
+                    "Search [text] (tab: match full paths, ctrl+r: regex)"
+                }
+                (SearchScope::Path, SearchMode::Regex) => {
+                    "Search full paths [regex] (tab: match frames, ctrl+r: plain text)"
+                }
+                (SearchScope::Path, SearchMode::Text) => {
+                    "Search full paths [text] (tab: match frames, ctrl+r: regex)"
+                }
+            },
+            InputKind::Highlight => "Add Highlight",
+            InputKind::OpenFile => "Open File (tab to complete)",
+            #[cfg(feature = "python")]
+            InputKind::AttachPid => "Attach to PID",
+        };
+        let mut line = Line::from(status_text);
+        if let Some(error) = &input_buffer.search_error {
+            line.push_span(Span::styled(
+                format!("  Invalid regex: {}", error),
+                Style::default().fg(Color::Red),
+            ));
+        }
+        vec![(title, line)]
     }
 
     fn get_status_text_log_buffer(&self) -> Vec<(&'static str, Line<'_>)> {
@@ -720,10 +1485,15 @@ impl<'a> FlamelensWidget<'a> {
                     if let (true, Some(hit_coverage_count)) =
                         (p.is_manual, self.app.flamegraph().hit_coverage_count())
                     {
+                        let scope = match p.scope {
+                            SearchScope::Frame => "",
+                            SearchScope::Path => " in paths",
+                        };
                         let mut match_text = format!(
-                            "\"{}\" {}",
+                            "\"{}\"{} {}",
                             p.re.as_str(),
-                            FlamelensWidget::get_count_stats_str(
+                            scope,
+                            self.get_count_stats_str(
                                 None,
                                 hit_coverage_count,
                                 root_total_count,
@@ -743,22 +1513,160 @@ impl<'a> FlamelensWidget<'a> {
                         lines.push(("Match", Line::from(match_text)));
                     }
                 }
-                let selected_text = format!(
-                    "{} {}",
-                    self.app.flamegraph().get_stack_short_name_from_info(stack),
-                    FlamelensWidget::get_count_stats_str(
-                        None,
-                        stack.total_count,
-                        root_total_count,
-                        zoom_total_count
+                let highlight_patterns = &self.app.flamegraph_state().highlight_patterns;
+                if !highlight_patterns.is_empty() {
+                    let mut spans = vec![];
+                    for (i, p) in highlight_patterns.iter().enumerate() {
+                        let color = get_highlight_color(i);
+                        let style = Style::default()
+                            .bg(color)
+                            .fg(FlamelensWidget::get_text_color(color));
+                        spans.push(Span::styled(format!(" {} ", p.pattern), style));
+                        spans.push(Span::from(" "));
+                    }
+                    lines.push(("Highlight", Line::from(spans)));
+                }
+                if self.app.flamegraph_state().width_metric == WidthMetric::Own {
+                    lines.push((
+                        "Width",
+                        Line::from(
+                            "self time, frames span their children where wider (press 'w' for total time)",
+                        )
+                        .style(Style::default().bold()),
+                    ));
+                }
+                if self.app.flamegraph_state().collapse_common_prefix {
+                    if let Some(prefix) = self.app.flamegraph().root_prefix() {
+                        lines.push((
+                            "Collapsed",
+                            Line::from(format!(
+                                "{} frames into the root: {} (press 'e' to expand)",
+                                prefix.split(';').count(),
+                                prefix
+                            )),
+                        ));
+                    }
+                }
+                let value_names = self.app.flamegraph().value_names();
+                if value_names.len() > 1 {
+                    let value_column = self.app.flamegraph().value_column();
+                    lines.push((
+                        "Weight",
+                        Line::from(format!(
+                            "{} ({} of {}: {}; press 'm' to switch)",
+                            value_names[value_column],
+                            value_column + 1,
+                            value_names.len(),
+                            value_names.join(", ")
+                        )),
+                    ));
+                }
+                if let Some(depth) = self.app.flamegraph_state().max_render_depth {
+                    lines.push((
+                        "Depth",
+                        Line::from(format!(
+                            "rendering {} level(s) below the zoom root; select the marker below \
+                             the last level and press enter to drill in",
+                            depth
+                        )),
+                    ));
+                }
+                if let Some(threshold) = self.app.flamegraph_state().prune_threshold {
+                    lines.push((
+                        "Collapsed",
+                        Line::from(format!(
+                            "frames below {:.2}% of all are hidden (press 'p' to restore)",
+                            threshold * 100.0
+                        )),
+                    ));
+                }
+                if let Some((p, mode)) = &self.app.flamegraph_state().focus {
+                    let (label, description) = match mode {
+                        FocusMode::Focus => ("Focus", "other frames dimmed"),
+                        FocusMode::Exclude => ("Exclude", "its subtrees dimmed"),
+                    };
+                    lines.push((
+                        label,
+                        Line::from(format!("{} ({})", p.pattern, description)),
+                    ));
+                }
+                let selected_text = match self.app.flamegraph_state().get_other_group(&stack.id) {
+                    Some(group) => format!(
+                        "other ({} frames, enter to expand) {}",
+                        group.stack_ids.len(),
+                        self.get_count_stats_str(
+                            None,
+                            group.total_count,
+                            root_total_count,
+                            zoom_total_count
+                        ),
+                    ),
+                    None => format!(
+                        "{} {}",
+                        self.app.flamegraph().get_stack_short_name_from_info(stack),
+                        self.get_count_stats_str(
+                            None,
+                            stack.total_count,
+                            root_total_count,
+                            zoom_total_count
+                        ),
+                    ),
+                };
+                let selected_text = match self.app.flamegraph_state().selected_delta {
+                    Some(delta) if self.app.is_live() && delta.stack_id == stack.id => format!(
+                        "{} ({}, {:+.2}%)",
+                        selected_text,
+                        format_count_delta(
+                            delta.count,
+                            self.app.count_unit,
+                            self.app.humanize_counts
+                        ),
+                        delta.share
                     ),
-                );
+                    _ => selected_text,
+                };
+                let selected_text = match self.app.flamegraph().get_diff(&stack.id) {
+                    Some(diff) => format!("{} [{:+.2}pp vs baseline]", selected_text, diff),
+                    None => selected_text,
+                };
                 let status_text = format!("{:width$}", selected_text, width = width as usize,);
                 if self.is_flamegraph_view() {
                     lines.push(("Selected", Line::from(status_text)));
                 }
+                if let Some(pinned) = &self.app.flamegraph_state().pinned {
+                    let pinned_text = if self
+                        .app
+                        .flamegraph_state()
+                        .is_pinned_sampled(self.app.flamegraph())
+                    {
+                        pinned.clone()
+                    } else {
+                        format!("{} (not currently sampled)", pinned)
+                    };
+                    lines.push(("Pinned", Line::from(pinned_text)));
+                }
+                if let (true, Some(churn)) =
+                    (self.app.is_live(), &self.app.flamegraph_state().churn)
+                {
+                    lines.push((
+                        "Churn",
+                        Line::from(format!(
+                            "{} frames appeared (green for {} updates), {} vanished in the last update",
+                            churn.appeared, churn.duration, churn.vanished
+                        )),
+                    ));
+                }
+                if self.app.is_live() && self.app.flamegraph_state().freeze {
+                    lines.push((
+                        "Live",
+                        Line::from(vec![
+                            Span::styled(" FROZEN ", STYLE_FROZEN_BADGE),
+                            Span::from(" live updates paused; navigation and search still work"),
+                        ]),
+                    ));
+                }
                 if self.app.debug {
-                    let elapsed_str = format!(
+                    let mut elapsed_str = format!(
                         "Debug: {}",
                         self.app
                             .elapsed
@@ -767,8 +1675,46 @@ impl<'a> FlamelensWidget<'a> {
                             .collect::<Vec<String>>()
                             .join(" ")
                     );
+                    elapsed_str += match self.app.rss_bytes {
+                        Some(rss) => format!(" rss:{:.1}MiB", rss as f64 / (1024.0 * 1024.0)),
+                        None => " rss:n/a".to_string(),
+                    }
+                    .as_str();
                     lines.push(("Debug", Line::from(elapsed_str)));
                 }
+                if let Some(baseline) = &self.app.flamegraph_state().baseline {
+                    let status = if self.app.flamegraph_state().show_diff {
+                        "red: share grew, blue: share shrank"
+                    } else {
+                        "hidden"
+                    };
+                    lines.push((
+                        "Diff",
+                        Line::from(format!(
+                            "vs baseline marked {}s ago ({})",
+                            baseline.created_at.elapsed().as_secs(),
+                            status
+                        )),
+                    ));
+                }
+                let truncated_count = self.app.flamegraph().truncated_count();
+                if truncated_count > 0 {
+                    lines.push((
+                        "Warning",
+                        Line::styled(
+                            format!(
+                                "{} ({:.2}%) have truncated stacks (shown in grey)",
+                                format_count_with_unit(
+                                    truncated_count,
+                                    self.app.count_unit,
+                                    self.app.humanize_counts
+                                ),
+                                100.0 * truncated_count as f64 / root_total_count as f64
+                            ),
+                            Style::default().yellow(),
+                        ),
+                    ));
+                }
                 if let Some(transient_message) = &self.app.transient_message {
                     lines.push(("Info", Line::from(transient_message.as_str())));
                 }
@@ -779,15 +1725,16 @@ impl<'a> FlamelensWidget<'a> {
     }
 
     fn get_count_stats_str(
+        &self,
         name: Option<&str>,
         count: u64,
         total_count: u64,
         zoomed_total_count: Option<u64>,
     ) -> String {
         format!(
-            "[{}{} samples, {:.2}% of all{}]",
+            "[{}{}, {:.2}% of all{}]",
             name.map(|n| format!("{}: ", n)).unwrap_or_default(),
-            count,
+            format_count_with_unit(count, self.app.count_unit, self.app.humanize_counts),
             (count as f64 / total_count as f64) * 100.0,
             if let Some(zoomed_total_count) = zoomed_total_count {
                 format!(
@@ -822,7 +1769,11 @@ impl HelpTags {
     fn new() -> Self {
         Self {
             tags: vec![],
-            default: vec![("r", "reset"), ("tab", "switch view"), ("q", "quit")],
+            default: vec![
+                ("r/R", "reset/reset all"),
+                ("tab", "switch view"),
+                ("q", "quit"),
+            ],
         }
     }
 
@@ -845,6 +1796,20 @@ impl HelpTags {
     }
 }
 
+/// Color of the additional highlight pattern with the given index.
+fn get_highlight_color(index: usize) -> Color {
+    HIGHLIGHT_COLORS[index % HIGHLIGHT_COLORS.len()]
+}
+
+fn to_screen_area(area: Rect) -> ScreenArea {
+    ScreenArea {
+        x: area.x,
+        y: area.y,
+        width: area.width,
+        height: area.height,
+    }
+}
+
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame) {
     // This is where you add new widgets.
@@ -858,6 +1823,12 @@ pub fn render(app: &mut App, frame: &mut Frame) {
         .set_frame_height(flamelens_state.frame_height);
     app.flamegraph_view
         .set_frame_width(flamelens_state.frame_width);
+    app.flamegraph_view.state.minimap_area = flamelens_state.minimap_area.map(to_screen_area);
+    app.flamegraph_view.state.depth_marker_areas = flamelens_state
+        .depth_marker_areas
+        .into_iter()
+        .map(|(area, stack_id)| (to_screen_area(area), stack_id))
+        .collect();
     app.add_elapsed("render", flamelens_state.render_time);
     if flamelens_state.log_visible_lines > 0 {
         app.log_visible_lines = flamelens_state.log_visible_lines;
@@ -868,3 +1839,76 @@ pub fn render(app: &mut App, frame: &mut Frame) {
         input_buffer.cursor = flamelens_state.cursor_position;
     }
 }
+
+/// Header for a live process with its command line first so that it stays visible when the
+/// header is cut off.
+fn get_process_header(pid: u64, info: Option<&str>) -> String {
+    match info {
+        Some(info) => format!("{} [PID {}]", info, pid),
+        None => format!("Process: {}", pid),
+    }
+}
+
+/// Status of the sampler for the header: whether it is still running, or the final count once
+/// the process exited, what is sampled and for how long.
+#[cfg(feature = "python")]
+fn get_sampler_header(state: &SamplerState, total_count: &str) -> String {
+    let mut out = match state.status {
+        SamplerStatus::Running => " [Running]".to_string(),
+        SamplerStatus::Error(_) => " [Failed]".to_string(),
+        SamplerStatus::Done => format!(" (exited; {} in total)", total_count),
+    };
+    out += if state.include_idle {
+        " [wall-clock]"
+    } else {
+        " [on-CPU]"
+    };
+    let duration = state.total_sampled_duration;
+    let seconds = duration.as_secs() % 60;
+    let minutes = (duration.as_secs() / 60) % 60;
+    let hours = (duration.as_secs() / 60) / 60;
+    out += format!(
+        " [Sampling for {:0>2}:{:0>2}:{:0>2}]",
+        hours, minutes, seconds
+    )
+    .as_str();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_process_header() {
+        assert_eq!(
+            get_process_header(1234, Some("python app.py")),
+            "python app.py [PID 1234]"
+        );
+        assert_eq!(get_process_header(1234, None), "Process: 1234");
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn test_get_sampler_header() {
+        let mut state = SamplerState::new(false);
+        state.set_total_sampled_duration(Duration::from_secs(3725));
+        assert_eq!(
+            get_sampler_header(&state, "42 samples"),
+            " [Running] [on-CPU] [Sampling for 01:02:05]"
+        );
+
+        state.include_idle = true;
+        state.set_status(SamplerStatus::Done);
+        assert_eq!(
+            get_sampler_header(&state, "42 samples"),
+            " (exited; 42 samples in total) [wall-clock] [Sampling for 01:02:05]"
+        );
+
+        state.set_status(SamplerStatus::Error("Process 1234 exited".to_string()));
+        assert_eq!(
+            get_sampler_header(&state, "42 samples"),
+            " [Failed] [wall-clock] [Sampling for 01:02:05]"
+        );
+    }
+}
@@ -1,8 +1,12 @@
 use std::cmp::min;
+use std::collections::HashSet;
 
 use crate::{
-    flame::{FlameGraph, SearchPattern, SortColumn, StackIdentifier, StackInfo, ROOT_ID},
-    state::{FlameGraphState, ZoomState},
+    flame::{
+        FlameGraph, FocusMode, SearchPattern, SortColumn, StackIdentifier, StackInfo, WidthMetric,
+        ROOT_ID,
+    },
+    state::{FlameGraphState, ResizeMemory, ZoomState},
 };
 
 #[derive(Debug)]
@@ -36,6 +40,8 @@ impl FlameGraphView {
     }
 
     pub fn replace_flamegraph(&mut self, mut new_flamegraph: FlameGraph) {
+        // Keep weighting by the value switched to at runtime
+        new_flamegraph.set_value_column(self.flamegraph.value_column());
         self.state
             .handle_flamegraph_replacement(&self.flamegraph, &mut new_flamegraph);
         // Preserve the sort column
@@ -52,12 +58,61 @@ impl FlameGraphView {
     }
 
     pub fn set_frame_height(&mut self, frame_height: u16) {
+        let resized = self
+            .state
+            .frame_height
+            .is_some_and(|height| height != frame_height);
         self.state.frame_height = Some(frame_height);
-        self.keep_selected_stack_in_view_port();
+        if resized {
+            self.handle_resize();
+        } else {
+            self.keep_selected_stack_in_view_port();
+        }
     }
 
     pub fn set_frame_width(&mut self, frame_width: u16) {
+        let resized = self
+            .state
+            .frame_width
+            .is_some_and(|width| width != frame_width);
         self.state.frame_width = Some(frame_width);
+        if resized {
+            self.handle_resize();
+        }
+    }
+
+    /// Keep the selected stack visible in a resized view port by scrolling to it, or by selecting
+    /// its deepest ancestor still wide enough to show. Selection and scroll moved this way are
+    /// restored once a later resize makes room for them again.
+    fn handle_resize(&mut self) {
+        if let Some(memory) = self.state.resize_memory.take() {
+            if memory.moved_to == (self.state.selected, self.state.level_offset) {
+                self.state.select_id(&memory.selected);
+                self.state.level_offset = memory.level_offset;
+            }
+        }
+        let before = (self.state.selected, self.state.level_offset);
+        self.select_visibly_wide_ancestor();
+        self.set_level_offset(self.state.level_offset);
+        if let (Some(level), Some(frame_height)) = (
+            self.get_selected_stack().map(|stack| stack.level),
+            self.state.frame_height,
+        ) {
+            let frame_height = (frame_height as usize).max(1);
+            if level < self.state.level_offset {
+                self.state.level_offset = level;
+            } else if level >= self.state.level_offset + frame_height {
+                self.state.level_offset = level + 1 - frame_height;
+            }
+        }
+        let after = (self.state.selected, self.state.level_offset);
+        if after != before {
+            self.state.resize_memory = Some(ResizeMemory {
+                selected: before.0,
+                level_offset: before.1,
+                moved_to: after,
+            });
+        }
     }
 
     pub fn set_level_offset(&mut self, level_offset: usize) {
@@ -69,6 +124,24 @@ impl FlameGraphView {
     }
 
     pub fn to_child_stack(&mut self) {
+        // Moving past the render depth limit selects the overflow marker, see
+        // `open_depth_marker`
+        if let (Some(max_level), Some(stack)) =
+            (self.get_max_render_level(), self.get_selected_stack())
+        {
+            if stack.level >= max_level && !stack.children.is_empty() {
+                let marker_level = stack.level + 1;
+                if !self.is_depth_marker_selected()
+                    && self.state.frame_height.is_some_and(|frame_height| {
+                        marker_level >= self.state.level_offset + frame_height as usize
+                    })
+                {
+                    self.state.level_offset += 1;
+                }
+                self.state.depth_marker_selected = true;
+                return;
+            }
+        }
         if let Some(stack) = self.flamegraph.get_stack(&self.state.selected) {
             let mut children_stacks = stack
                 .children
@@ -96,6 +169,10 @@ impl FlameGraphView {
     }
 
     pub fn to_parent_stack(&mut self) {
+        if self.is_depth_marker_selected() {
+            self.state.depth_marker_selected = false;
+            return;
+        }
         // TODO: maybe also check parent visibility to handle resizing / edge cases
         if let Some(parent) = self
             .flamegraph
@@ -126,8 +203,22 @@ impl FlameGraphView {
     }
 
     fn is_stack_visibly_wide(&self, stack: &StackInfo, zoom_factor: Option<f64>) -> bool {
+        if self.state.is_pruned(stack) || self.state.is_grouped_away(&stack.id) {
+            return false;
+        }
+        // A stack standing for an "other" frame is as wide as the whole group
+        let width_factor = self
+            .state
+            .get_other_group(&stack.id)
+            .map_or(stack.width_factor, |group| group.width_factor);
+        if self
+            .get_max_render_level()
+            .is_some_and(|max_level| stack.level > max_level)
+        {
+            return false;
+        }
         if let Some(frame_width) = self.state.frame_width {
-            let mut expected_frame_width = stack.width_factor * frame_width as f64;
+            let mut expected_frame_width = width_factor * frame_width as f64;
             if let Some(zoom_factor) = zoom_factor {
                 // Use manually specified zoom factor as the descendants / ancentors logic are
                 // handled by the caller
@@ -149,6 +240,23 @@ impl FlameGraphView {
         }
     }
 
+    /// Select the deepest ancestor of the selected stack, itself included, wide enough to show.
+    fn select_visibly_wide_ancestor(&mut self) {
+        let visible_ancestor = self
+            .flamegraph
+            .get_ancestors(&self.state.selected)
+            .into_iter()
+            .find(|stack_id| {
+                self.flamegraph
+                    .get_stack(stack_id)
+                    .is_some_and(|stack| self.is_stack_visibly_wide(stack, None))
+            })
+            .unwrap_or(ROOT_ID);
+        if visible_ancestor != self.state.selected {
+            self.select_id(&visible_ancestor);
+        }
+    }
+
     fn select_stack_in_view_port(&mut self) {
         if let Some(stacks) = self.flamegraph.get_stacks_at_level(self.state.level_offset) {
             for stack_id in stacks {
@@ -341,6 +449,27 @@ impl FlameGraphView {
         self.keep_selected_stack_in_view_port();
     }
 
+    /// Scroll so that the given level is in the middle of the view port.
+    pub fn scroll_to_level(&mut self, level: usize) {
+        let half_height = self.state.frame_height.unwrap_or(0) as usize / 2;
+        self.set_level_offset(level.saturating_sub(half_height));
+        self.keep_selected_stack_in_view_port();
+    }
+
+    /// Scroll to the part of the graph shown at the given terminal position of the overview
+    /// strip, if it is there.
+    pub fn scroll_to_minimap_position(&mut self, column: u16, row: u16) {
+        let Some(area) = self.state.minimap_area else {
+            return;
+        };
+        if !area.contains(column, row) {
+            return;
+        }
+        let level =
+            (row - area.y) as usize * self.flamegraph.get_num_levels() / area.height as usize;
+        self.scroll_to_level(level);
+    }
+
     pub fn scroll_to_selected(&mut self) {
         if let Some(stack) = self.get_selected_stack() {
             if !self.is_stack_in_view_port(stack) {
@@ -374,8 +503,7 @@ impl FlameGraphView {
 
     pub fn set_zoom_for_id(&mut self, stack_id: StackIdentifier) {
         if let Some(selected_stack) = self.flamegraph.get_stack(&stack_id) {
-            let zoom_factor =
-                self.flamegraph.total_count() as f64 / selected_stack.total_count as f64;
+            let zoom_factor = 1.0 / selected_stack.width_factor;
             let ancestors = self.flamegraph.get_ancestors(&stack_id);
             let descendants = self.flamegraph.get_descendants(&stack_id);
             if stack_id == ROOT_ID {
@@ -392,18 +520,201 @@ impl FlameGraphView {
         }
     }
 
+    /// Deepest level rendered given the render depth limit, if there is one.
+    pub fn get_max_render_level(&self) -> Option<usize> {
+        let max_render_depth = self.state.max_render_depth?;
+        Some(self.get_zoom_root_level() + max_render_depth)
+    }
+
+    /// Where the zoom root starts and how wide it is, as shares of the whole width. `None` when
+    /// not zoomed, as the whole width is shown then.
+    pub fn get_zoom_extent(&self) -> Option<(f64, f64)> {
+        let zoom_root = self.state.zoom.as_ref()?.stack_id;
+        let mut ancestors = self.flamegraph.get_ancestors(&zoom_root);
+        ancestors.reverse();
+        let mut start = 0.0;
+        for (parent, child) in ancestors.iter().zip(ancestors.iter().skip(1)) {
+            let parent = self.flamegraph.get_stack(parent)?;
+            start += parent
+                .children
+                .iter()
+                .take_while(|sibling| *sibling != child)
+                .filter_map(|sibling| self.flamegraph.get_stack(sibling))
+                .map(|sibling| sibling.width_factor)
+                .sum::<f64>();
+        }
+        let width = self.flamegraph.get_stack(&zoom_root)?.width_factor;
+        Some((start, width))
+    }
+
+    fn get_zoom_root_level(&self) -> usize {
+        self.state
+            .zoom
+            .as_ref()
+            .and_then(|zoom| self.flamegraph.get_stack(&zoom.stack_id))
+            .map_or(0, |stack| stack.level)
+    }
+
+    /// Whether the overflow marker below the selected stack is selected, as long as the stack is
+    /// still at the render depth limit.
+    pub fn is_depth_marker_selected(&self) -> bool {
+        self.state.depth_marker_selected
+            && self.get_selected_stack().is_some_and(|stack| {
+                !stack.children.is_empty() && self.get_max_render_level() == Some(stack.level)
+            })
+    }
+
+    /// Re-root the zoom one level deeper to reveal the next level below the selected stack, the
+    /// one its overflow marker collapses, and select its widest child there.
+    pub fn open_depth_marker(&mut self) {
+        self.state.depth_marker_selected = false;
+        self.zoom_one_level_deeper();
+        self.to_child_stack();
+    }
+
+    /// Open the overflow marker at the screen position, if one was rendered there.
+    pub fn open_depth_marker_at(&mut self, column: u16, row: u16) -> bool {
+        let Some(stack_id) = self
+            .state
+            .depth_marker_areas
+            .iter()
+            .find(|(area, _)| area.contains(column, row))
+            .map(|(_, stack_id)| *stack_id)
+        else {
+            return false;
+        };
+        self.select_id(&stack_id);
+        self.open_depth_marker();
+        true
+    }
+
+    /// Zoom into the child of the zoom root that leads to the selected stack.
+    fn zoom_one_level_deeper(&mut self) {
+        let target_level = self.get_zoom_root_level() + 1;
+        let mut stack = self.get_selected_stack();
+        while let Some(s) = stack {
+            if s.level <= target_level {
+                break;
+            }
+            stack = s
+                .parent
+                .and_then(|parent| self.flamegraph.get_stack(&parent));
+        }
+        if let Some(stack_id) = stack.filter(|s| s.level == target_level).map(|s| s.id) {
+            self.set_zoom_for_id(stack_id);
+        }
+    }
+
+    /// Render fewer levels below the zoom root. Starts from what fits on the screen if there is
+    /// no limit yet.
+    pub fn decrease_max_render_depth(&mut self) {
+        self.state.depth_marker_selected = false;
+        let depth_below = self.get_zoom_root_depth_below();
+        let current = self.state.max_render_depth.unwrap_or_else(|| {
+            depth_below.min(self.state.frame_height.unwrap_or(u16::MAX) as usize)
+        });
+        self.state.max_render_depth = Some(current.saturating_sub(1).max(1));
+        self.keep_selected_stack_visible();
+    }
+
+    /// Render more levels below the zoom root, removing the limit once everything fits.
+    pub fn increase_max_render_depth(&mut self) {
+        self.state.depth_marker_selected = false;
+        let depth_below = self.get_zoom_root_depth_below();
+        self.state.max_render_depth = self
+            .state
+            .max_render_depth
+            .map(|depth| depth + 1)
+            .filter(|depth| *depth < depth_below);
+    }
+
+    fn get_zoom_root_depth_below(&self) -> usize {
+        let zoom_root = self
+            .state
+            .zoom
+            .as_ref()
+            .map_or(ROOT_ID, |zoom| zoom.stack_id);
+        self.flamegraph.get_depth_below(&zoom_root)
+    }
+
+    /// Select the ancestor at the deepest rendered level if the selected stack is below it.
+    fn keep_selected_stack_visible(&mut self) {
+        let Some(max_level) = self.get_max_render_level() else {
+            return;
+        };
+        let mut stack = self.get_selected_stack();
+        while let Some(s) = stack {
+            if s.level <= max_level {
+                break;
+            }
+            stack = s
+                .parent
+                .and_then(|parent| self.flamegraph.get_stack(&parent));
+        }
+        if let Some(stack_id) = stack.map(|s| s.id) {
+            self.select_id(&stack_id);
+        }
+    }
+
     pub fn set_zoom(&mut self) {
+        self.state.depth_marker_selected = false;
+        self.state.zoom_stack.clear();
         self.set_zoom_for_id(self.state.selected);
     }
 
     pub fn unset_zoom(&mut self) {
+        self.state.depth_marker_selected = false;
         if let Some(zoom_stack_id) = self.state.zoom.as_ref().map(|z| z.stack_id) {
             // Restore selected to previous zoom point
             self.select_id(&zoom_stack_id);
         }
+        self.state.zoom_stack.clear();
         self.state.unset_zoom();
     }
 
+    /// Move the zoom root up to its parent. The selection is kept as it stays in scope.
+    pub fn zoom_out(&mut self) {
+        let Some(zoom_root) = self.state.zoom.as_ref().map(|z| z.stack_id) else {
+            return;
+        };
+        self.state.zoom_stack.push(zoom_root);
+        let parent = self
+            .flamegraph
+            .get_stack(&zoom_root)
+            .and_then(|stack| stack.parent)
+            .unwrap_or(ROOT_ID);
+        self.set_zoom_for_id(parent);
+        self.keep_selected_stack_visible();
+    }
+
+    /// Move the zoom root down one level, towards the selected stack if it is below the next
+    /// level, or else back to where the zoom was before zooming out.
+    pub fn zoom_in(&mut self) {
+        let previous_root = self.state.zoom.as_ref().map(|z| z.stack_id);
+        self.zoom_one_level_deeper();
+        let zoom_root = self.state.zoom.as_ref().map(|z| z.stack_id);
+        if zoom_root != previous_root {
+            // Moved down towards the selection; the path zoomed out of is still valid only if it
+            // is the same one
+            if self.state.zoom_stack.last().copied() == zoom_root {
+                self.state.zoom_stack.pop();
+            } else {
+                self.state.zoom_stack.clear();
+            }
+        } else if let Some(stack_id) = self.state.zoom_stack.pop() {
+            self.set_zoom_for_id(stack_id);
+            let in_scope = self.state.selected == stack_id
+                || self
+                    .state
+                    .zoom
+                    .as_ref()
+                    .is_some_and(|zoom| zoom.is_ancestor_or_descendant(&self.state.selected));
+            if !in_scope {
+                self.select_id(&stack_id);
+            }
+        }
+    }
+
     pub fn set_search_pattern(&mut self, search_pattern: SearchPattern) {
         self.flamegraph.set_hits(&search_pattern);
         self.state.set_search_pattern(search_pattern);
@@ -414,6 +725,146 @@ impl FlameGraphView {
         self.state.unset_search_pattern();
     }
 
+    pub fn add_highlight_pattern(&mut self, pattern: SearchPattern) {
+        self.state.add_highlight_pattern(pattern);
+        self.flamegraph
+            .set_highlights(&self.state.get_highlight_patterns());
+    }
+
+    pub fn clear_highlight_patterns(&mut self) {
+        self.state.clear_highlight_patterns();
+        self.flamegraph.set_highlights(&[]);
+    }
+
+    /// Ancestors and descendants of the selected frame, including itself, in spotlight mode.
+    pub fn get_spotlight_path(&self) -> Option<HashSet<StackIdentifier>> {
+        self.state.spotlight.then(|| {
+            let selected = self.state.selected;
+            let mut on_path = HashSet::from_iter(self.flamegraph.get_ancestors(&selected));
+            on_path.extend(self.flamegraph.get_descendants(&selected));
+            on_path
+        })
+    }
+
+    /// Whether the stack is dimmed for being off the spotlight path. Matches stay bright so that
+    /// they can still be found off the path.
+    pub fn is_off_spotlight(&self, on_path: &HashSet<StackIdentifier>, stack: &StackInfo) -> bool {
+        !on_path.contains(&stack.id)
+            && !stack.hit
+            && self.flamegraph.get_highlight(&stack.id).is_none()
+    }
+
+    /// Collapse all frames with a smaller share of the total than the selected frame, or restore
+    /// them if already collapsed.
+    pub fn toggle_prune_to_selected(&mut self) {
+        if self.state.prune_threshold.take().is_some() {
+            return;
+        }
+        let Some(stack) = self.get_selected_stack() else {
+            return;
+        };
+        if stack.id != ROOT_ID {
+            self.state.prune_threshold = Some(stack.width_factor);
+        }
+    }
+
+    /// Focus on or exclude the subtrees of frames named like the selected one. Toggles off if the
+    /// selected frame is already focused on or excluded in the same mode.
+    ///
+    /// Fails if no pattern can be built for the name, e.g. as it is too long for the regex engine.
+    pub fn toggle_focus_selected(&mut self, mode: FocusMode) -> Result<(), regex::Error> {
+        let Some(stack) = self.flamegraph.get_stack(&self.state.selected) else {
+            return Ok(());
+        };
+        if stack.id == ROOT_ID {
+            return Ok(());
+        }
+        let short_name = self.flamegraph.get_stack_short_name_from_info(stack);
+        if let Some((p, current_mode)) = &self.state.focus {
+            if p.pattern == short_name && *current_mode == mode {
+                self.clear_focus();
+                return Ok(());
+            }
+        }
+        let pattern = SearchPattern::new(short_name, false, false)?;
+        self.flamegraph.set_focus(&pattern, mode);
+        self.state.focus = Some((pattern, mode));
+        Ok(())
+    }
+
+    pub fn clear_focus(&mut self) {
+        self.state.focus = None;
+        self.flamegraph.clear_focus();
+    }
+
+    /// Weight the frames by the next of the values of each line, if lines have several.
+    pub fn to_next_value_column(&mut self) {
+        let num_values = self.flamegraph.value_names().len();
+        if num_values < 2 {
+            return;
+        }
+        let column = (self.flamegraph.value_column() + 1) % num_values;
+        self.flamegraph.set_value_column(column);
+        if let Some(p) = &self.state.search_pattern {
+            self.flamegraph.set_hits(p);
+        }
+        if let (true, Some(baseline)) = (self.state.show_diff, &self.state.baseline) {
+            self.flamegraph.set_diff(baseline);
+        }
+        // Which children are narrow depends on the weights
+        if let Some(grouping) = &mut self.state.sibling_grouping {
+            grouping.update(&self.flamegraph);
+        }
+        if self.state.is_grouped_away(&self.state.selected) {
+            self.select_visible_ancestor();
+        }
+        // The zoom factor depends on the count of the zoomed in stack
+        if let Some(zoom) = &self.state.zoom {
+            self.set_zoom_for_id(zoom.stack_id);
+        }
+    }
+
+    /// Pin the selected stack so that it stays selected across live updates, or unpin it if a
+    /// stack is already pinned.
+    pub fn toggle_pin_selected(&mut self) {
+        if self.state.pinned.take().is_some() {
+            return;
+        }
+        if let Some(stack) = self.flamegraph.get_stack(&self.state.selected) {
+            if stack.id != ROOT_ID {
+                let full_name = self.flamegraph.get_stack_full_name_from_info(stack);
+                self.state.pinned = Some(full_name.to_string());
+            }
+        }
+    }
+
+    /// Mark the current flamegraph as the baseline and start diffing against it.
+    pub fn mark_baseline(&mut self) {
+        self.state.baseline = Some(self.flamegraph.snapshot_baseline());
+        self.state.show_diff = true;
+        if let Some(baseline) = &self.state.baseline {
+            self.flamegraph.set_diff(baseline);
+        }
+    }
+
+    pub fn clear_baseline(&mut self) {
+        self.state.baseline = None;
+        self.state.show_diff = false;
+        self.flamegraph.clear_diff();
+    }
+
+    pub fn toggle_diff(&mut self) {
+        let Some(baseline) = &self.state.baseline else {
+            return;
+        };
+        self.state.show_diff = !self.state.show_diff;
+        if self.state.show_diff {
+            self.flamegraph.set_diff(baseline);
+        } else {
+            self.flamegraph.clear_diff();
+        }
+    }
+
     pub fn unset_manual_search_pattern(&mut self) {
         if let Some(p) = self.state.search_pattern.as_ref() {
             if p.is_manual {
@@ -422,12 +873,95 @@ impl FlameGraphView {
         }
     }
 
+    /// Switch the frame widths between total and self counts, keeping the selection visible.
+    pub fn toggle_width_metric(&mut self) {
+        let width_metric = match self.state.width_metric {
+            WidthMetric::Total => WidthMetric::Own,
+            WidthMetric::Own => WidthMetric::Total,
+        };
+        self.state.width_metric = width_metric;
+        self.flamegraph.set_width_metric(width_metric);
+        if let Some(grouping) = &mut self.state.sibling_grouping {
+            grouping.update(&self.flamegraph);
+        }
+        if let Some(zoom) = &self.state.zoom {
+            self.set_zoom_for_id(zoom.stack_id);
+        }
+        self.select_visibly_wide_ancestor();
+    }
+
+    /// Collapse the frames all stacks start with into the root, or expand them again. The
+    /// selection is kept where its frame is still shown.
+    pub fn toggle_collapsed_common_prefix(&mut self) {
+        self.state.collapse_common_prefix = !self.state.collapse_common_prefix;
+        let flamegraph = self
+            .flamegraph
+            .with_collapsed_common_prefix(self.state.collapse_common_prefix);
+        self.replace_flamegraph(flamegraph);
+    }
+
+    pub fn toggle_sibling_grouping(&mut self, top_k: usize, min_share: f64) {
+        self.state
+            .toggle_sibling_grouping(&self.flamegraph, top_k, min_share);
+        if self.state.is_grouped_away(&self.state.selected) {
+            self.select_visible_ancestor();
+        }
+    }
+
+    /// Show the siblings grouped in the selected "other" frame. Returns whether it was one.
+    pub fn expand_selected_other_group(&mut self) -> bool {
+        let selected = self.state.selected;
+        if self.state.get_other_group(&selected).is_none() {
+            return false;
+        }
+        self.state.expand_other_group(&selected, &self.flamegraph);
+        true
+    }
+
+    /// Select the closest ancestor of the selected stack that is not grouped away.
+    fn select_visible_ancestor(&mut self) {
+        let ancestor = self
+            .flamegraph
+            .get_ancestors(&self.state.selected)
+            .into_iter()
+            .find(|stack_id| !self.state.is_grouped_away(stack_id))
+            .unwrap_or(ROOT_ID);
+        self.select_id(&ancestor);
+    }
+
     pub fn reset(&mut self) {
         self.state.select_root();
         self.state.level_offset = 0;
         self.state.unset_zoom();
+        self.state.zoom_stack.clear();
         self.state.table_state.reset();
         self.unset_search_pattern();
+        self.clear_highlight_patterns();
+        self.clear_focus();
+        self.state.pinned = None;
+        self.state.prune_threshold = None;
+        self.state.max_render_depth = None;
+        self.state.sibling_grouping = None;
+    }
+
+    /// Reset everything about how the flamegraph is viewed to the state right after loading it,
+    /// e.g. also the sort column, shading and diff coloring that [`FlameGraphView::reset`] keeps.
+    /// The flamegraph and the live session (freeze, baseline) are left as they are.
+    pub fn reset_view_state(&mut self) {
+        self.reset();
+        self.flamegraph.clear_diff();
+        self.flamegraph.set_width_metric(WidthMetric::Total);
+        self.flamegraph
+            .ordered_stacks
+            .set_sort_column(SortColumn::Own);
+        self.state = FlameGraphState {
+            frame_height: self.state.frame_height,
+            frame_width: self.state.frame_width,
+            freeze: self.state.freeze,
+            baseline: self.state.baseline.take(),
+            collapse_common_prefix: self.state.collapse_common_prefix,
+            ..FlameGraphState::default()
+        };
     }
 
     pub fn to_next_row(&mut self) {
@@ -484,6 +1018,9 @@ impl FlameGraphView {
 #[cfg(test)]
 mod tests {
     use crate::flame::ROOT_ID;
+    use crate::formats::ParseOptions;
+    use crate::state::{ScreenArea, ViewKind, DEFAULT_GROUP_MIN_SHARE, DEFAULT_GROUP_TOP_K};
+    use std::collections::HashSet;
 
     use super::*;
 
@@ -556,6 +1093,464 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_max_render_depth() {
+        let content = "a;b;c;d 3\na;e 1\n".to_string();
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.state.max_render_depth = Some(2);
+        view.to_child_stack();
+        view.to_child_stack();
+        assert_eq!(get_selected_short_name(&view), "b");
+
+        // Moving past the limit selects the marker, which moving up leaves again
+        view.to_child_stack();
+        assert!(view.is_depth_marker_selected());
+        assert_eq!(get_selected_short_name(&view), "b");
+        assert!(view.state.zoom.is_none());
+        view.to_child_stack();
+        assert!(view.is_depth_marker_selected());
+        view.to_parent_stack();
+        assert!(!view.is_depth_marker_selected());
+        assert_eq!(get_selected_short_name(&view), "b");
+
+        // Opening the marker re-roots the zoom one level deeper
+        view.to_child_stack();
+        view.open_depth_marker();
+        assert!(!view.is_depth_marker_selected());
+        assert_eq!(get_selected_short_name(&view), "c");
+        assert_eq!(
+            view.state.zoom.as_ref().unwrap().stack_id,
+            get_id(&view, "a")
+        );
+        assert_eq!(view.get_max_render_level(), Some(3));
+
+        // Decreasing the depth keeps the selection visible
+        view.decrease_max_render_depth();
+        assert_eq!(view.state.max_render_depth, Some(1));
+        assert_eq!(get_selected_short_name(&view), "b");
+
+        // The limit is removed once everything below the zoom root fits
+        view.increase_max_render_depth();
+        view.increase_max_render_depth();
+        assert_eq!(view.state.max_render_depth, None);
+    }
+
+    #[test]
+    fn test_depth_marker_with_reversed_stacks() {
+        // Leaf first input, flipped at load time
+        let options = ParseOptions {
+            reverse_stacks: true,
+            ..ParseOptions::default()
+        };
+        let fg = FlameGraph::from_string_with_options("d;c;b;a 3\ne;a 1\n".to_string(), &options);
+        let mut view = FlameGraphView::new(fg);
+        view.set_frame_height(2);
+        view.state.max_render_depth = Some(1);
+        view.to_child_stack();
+        assert_eq!(get_selected_short_name(&view), "a");
+
+        // The marker below the last rendered level is scrolled into view when selected
+        view.to_child_stack();
+        assert!(view.is_depth_marker_selected());
+        assert_eq!(view.state.level_offset, 1);
+
+        // Changing the limit leaves the marker
+        view.increase_max_render_depth();
+        assert!(!view.is_depth_marker_selected());
+        view.decrease_max_render_depth();
+        assert!(!view.is_depth_marker_selected());
+
+        // Clicking the marker opens it
+        view.state.depth_marker_areas = vec![(
+            ScreenArea {
+                x: 0,
+                y: 5,
+                width: 10,
+                height: 1,
+            },
+            get_id(&view, "a"),
+        )];
+        assert!(!view.open_depth_marker_at(10, 5));
+        assert!(view.open_depth_marker_at(9, 5));
+        assert_eq!(
+            view.state.zoom.as_ref().unwrap().stack_id,
+            get_id(&view, "a")
+        );
+        assert_eq!(get_selected_short_name(&view), "b");
+        assert!(!view.is_depth_marker_selected());
+    }
+
+    #[test]
+    fn test_zoom_out_and_in_by_one_level() {
+        let fg = FlameGraph::from_string("a;b;c;d 10\na;e 5\n".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        let zoom_root = |view: &FlameGraphView| view.state.zoom.as_ref().map(|z| z.stack_id);
+
+        view.state.select_id(&get_id(&view, "a;b;c;d"));
+        view.zoom_in();
+        assert_eq!(zoom_root(&view), Some(get_id(&view, "a")));
+        view.zoom_in();
+        view.zoom_in();
+        view.zoom_in();
+        assert_eq!(zoom_root(&view), Some(get_id(&view, "a;b;c;d")));
+        // Already zoomed into the selection
+        view.zoom_in();
+        assert_eq!(zoom_root(&view), Some(get_id(&view, "a;b;c;d")));
+
+        // Zoom out keeps the selection
+        view.zoom_out();
+        view.zoom_out();
+        view.zoom_out();
+        assert_eq!(zoom_root(&view), Some(get_id(&view, "a")));
+        assert_eq!(view.state.selected, get_id(&view, "a;b;c;d"));
+
+        // Zoom back in along the same path even after selecting the zoom root, which stays
+        // selected as an ancestor of the new one
+        view.state.select_id(&get_id(&view, "a"));
+        view.zoom_in();
+        assert_eq!(zoom_root(&view), Some(get_id(&view, "a;b")));
+        assert_eq!(view.state.selected, get_id(&view, "a"));
+
+        view.zoom_out();
+        view.zoom_out();
+        assert_eq!(zoom_root(&view), None);
+        view.zoom_out();
+        assert_eq!(zoom_root(&view), None);
+    }
+
+    #[test]
+    fn test_pinned_selection_across_replacements() {
+        let fg = FlameGraph::from_string("main;parse;alloc 10\nmain;render 5\n".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.state.select_id(&get_id(&view, "main;parse;alloc"));
+        view.toggle_pin_selected();
+        assert_eq!(view.state.pinned.as_deref(), Some("main;parse;alloc"));
+
+        // Missing from the update: the deepest sampled ancestor is selected but the pin is kept
+        let fg = FlameGraph::from_string("main;parse 3\nmain;render 5\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        assert_eq!(view.state.selected, get_id(&view, "main;parse"));
+        assert!(!view.state.is_pinned_sampled(&view.flamegraph));
+
+        // Moving away does not lose the pin, which is selected again once it is sampled
+        view.state.select_id(&get_id(&view, "main;render"));
+        let fg = FlameGraph::from_string("main;parse;alloc 1\nmain;render 5\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        assert_eq!(view.state.selected, get_id(&view, "main;parse;alloc"));
+        assert!(view.state.is_pinned_sampled(&view.flamegraph));
+
+        view.toggle_pin_selected();
+        assert_eq!(view.state.pinned, None);
+    }
+
+    #[test]
+    fn test_selected_delta_across_replacements() {
+        let fg = FlameGraph::from_string("main;parse 10\nmain;render 10\n".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.state.select_id(&get_id(&view, "main;parse"));
+        assert_eq!(view.state.selected_delta, None);
+
+        let fg = FlameGraph::from_string("main;parse 25\nmain;render 15\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        let delta = view.state.selected_delta.unwrap();
+        assert_eq!(delta.stack_id, get_id(&view, "main;parse"));
+        assert_eq!(delta.count, 15);
+        assert!((delta.share - 12.5).abs() < 1e-9);
+
+        // No delta once the selected path is gone
+        let fg = FlameGraph::from_string("main;render 15\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        assert_eq!(view.state.selected_delta, None);
+    }
+
+    #[test]
+    fn test_reset_view_state() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "level1-a;level2-b"));
+        view.set_zoom();
+        view.set_search_pattern(SearchPattern::new("level2", true, true).unwrap());
+        view.toggle_prune_to_selected();
+        view.state.toggle_show_self();
+        view.state.toggle_view_kind();
+        view.set_sort_by_total();
+
+        view.reset_view_state();
+        assert_eq!(view.state.selected, ROOT_ID);
+        assert!(view.state.zoom.is_none());
+        assert!(view.state.search_pattern.is_none());
+        assert_eq!(view.flamegraph.hit_coverage_count(), None);
+        assert_eq!(view.state.prune_threshold, None);
+        assert!(!view.state.show_self);
+        assert_eq!(view.state.view_kind, ViewKind::FlameGraph);
+        assert_eq!(
+            view.flamegraph.ordered_stacks.sorted_column,
+            SortColumn::Own
+        );
+    }
+
+    #[test]
+    fn test_churn_across_replacements() {
+        let fg = FlameGraph::from_string("main;parse 10\nmain;render 10\n".to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.state.toggle_churn(2);
+
+        let fg = FlameGraph::from_string("main;parse;alloc 5\nmain;parse 10\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        let alloc = get_id(&view, "main;parse;alloc");
+        let churn = view.state.churn.as_ref().unwrap();
+        assert_eq!(churn.new_ids, HashSet::from([alloc]));
+        assert_eq!((churn.appeared, churn.vanished), (1, 1));
+
+        // Still marked for the second replacement, then no longer
+        let fg = FlameGraph::from_string("main;parse;alloc 5\nmain;parse 10\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        let churn = view.state.churn.as_ref().unwrap();
+        assert_eq!(
+            churn.new_ids,
+            HashSet::from([get_id(&view, "main;parse;alloc")])
+        );
+        assert_eq!((churn.appeared, churn.vanished), (0, 0));
+        let fg = FlameGraph::from_string("main;parse;alloc 5\nmain;parse 10\n".to_string(), true);
+        view.replace_flamegraph(fg);
+        assert!(view.state.churn.as_ref().unwrap().new_ids.is_empty());
+
+        view.state.toggle_churn(2);
+        assert!(view.state.churn.is_none());
+    }
+
+    #[test]
+    fn test_resize_keeps_selection() {
+        let content = ["main;a;b;c;d;e 90", "main;f 10"].join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.set_frame_height(10);
+        view.set_frame_width(100);
+        let e = get_id(&view, "main;a;b;c;d;e");
+        let f = get_id(&view, "main;f");
+        view.select_id(&e);
+
+        // Shrinking scrolls down to the selection instead of selecting another stack
+        view.set_frame_height(3);
+        assert_eq!((view.state.selected, view.state.level_offset), (e, 4));
+        view.set_frame_height(10);
+        assert_eq!((view.state.selected, view.state.level_offset), (e, 0));
+
+        // A stack too narrow to show is replaced by its ancestor until there is room again
+        view.select_id(&f);
+        view.set_frame_width(5);
+        assert_eq!(view.state.selected, get_id(&view, "main"));
+        view.set_frame_width(100);
+        assert_eq!(view.state.selected, f);
+
+        // Nothing is restored after moving in between
+        view.set_frame_width(5);
+        view.to_child_stack();
+        view.set_frame_width(100);
+        assert_eq!(view.state.selected, get_id(&view, "main;a"));
+        assert!(view.state.resize_memory.is_none());
+    }
+
+    #[test]
+    fn test_zoom_extent() {
+        let content = ["main;a;b 20", "main;a;c 30", "main;d 50"].join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        assert_eq!(view.get_zoom_extent(), None);
+
+        view.select_id(&get_id(&view, "main;a;c"));
+        view.set_zoom();
+        let (start, width) = view.get_zoom_extent().unwrap();
+        assert!((start - 0.2).abs() < 1e-9 && (width - 0.3).abs() < 1e-9);
+        view.select_id(&get_id(&view, "main;d"));
+        view.set_zoom();
+        let (start, width) = view.get_zoom_extent().unwrap();
+        assert!((start - 0.5).abs() < 1e-9 && (width - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_toggle_width_metric() {
+        let content = ["main;b 10", "main;b;x 10", "main;c 80"].join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.state.frame_width = Some(7);
+        view.select_id(&get_id(&view, "main;b"));
+
+        // Only half of b is its own, which is too narrow to show
+        view.toggle_width_metric();
+        assert_eq!(view.state.width_metric, WidthMetric::Own);
+        assert_eq!(view.state.selected, get_id(&view, "main"));
+
+        // The zoom follows the widths
+        view.select_id(&get_id(&view, "main;c"));
+        view.set_zoom();
+        view.toggle_width_metric();
+        assert_eq!(view.state.zoom.as_ref().unwrap().zoom_factor, 1.25);
+        view.toggle_width_metric();
+
+        // Replacing flamegraphs keeps the metric
+        let fg = FlameGraph::from_string("main;a 1\nmain;c 1".to_string(), false);
+        view.replace_flamegraph(fg);
+        assert_eq!(view.flamegraph.width_metric(), WidthMetric::Own);
+        view.reset_view_state();
+        assert_eq!(view.flamegraph.width_metric(), WidthMetric::Total);
+    }
+
+    #[test]
+    fn test_toggle_collapsed_common_prefix() {
+        let content = ["run;loop 5", "run;loop;poll;a 10", "run;loop;poll;b;c 20"].join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "run;loop;poll;b"));
+
+        view.toggle_collapsed_common_prefix();
+        assert_eq!(view.flamegraph.root_prefix(), Some("run;loop;poll"));
+        assert_eq!(view.flamegraph.get_num_levels(), 3);
+        assert_eq!(view.state.selected, get_id(&view, "run;loop;poll;b"));
+
+        // Live updates are parsed with the setting before being swapped in
+        let options = ParseOptions {
+            collapse_common_prefix: true,
+            ..ParseOptions::default()
+        };
+        let fg = FlameGraph::from_string_with_options(
+            "run;loop;poll;a 1\nrun;loop;poll;d 1".to_string(),
+            &options,
+        );
+        view.replace_flamegraph(fg);
+        assert_eq!(view.flamegraph.root_name(), "run;loop;poll");
+
+        view.toggle_collapsed_common_prefix();
+        assert_eq!(view.flamegraph.root_prefix(), None);
+        assert_eq!(view.flamegraph.root_name(), "all");
+    }
+
+    #[test]
+    fn test_sibling_grouping() {
+        let content = [
+            "main;a 50",
+            "main;b 30",
+            "main;c 10",
+            "main;d 1",
+            "main;e;f 1",
+        ]
+        .join("\n");
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.state.frame_width = Some(100);
+        view.select_id(&get_id(&view, "main;e;f"));
+        view.toggle_sibling_grouping(DEFAULT_GROUP_TOP_K, DEFAULT_GROUP_MIN_SHARE);
+        // The children below 2% of the parent are grouped, keeping their weight
+        assert!(view.state.is_grouped_away(&get_id(&view, "main;e;f")));
+        assert!(!view.state.is_grouped_away(&view.state.selected));
+        let group = ["main;d", "main;e"]
+            .iter()
+            .find_map(|name| view.state.get_other_group(&get_id(&view, name)))
+            .unwrap();
+        assert_eq!((group.stack_ids.len(), group.total_count), (2, 2));
+
+        // Also the children beyond the widest 2, standing in for the group being the widest one
+        let grouping = view.state.sibling_grouping.as_mut().unwrap();
+        grouping.top_k = 2;
+        grouping.update(&view.flamegraph);
+        let c = get_id(&view, "main;c");
+        let group = view.state.get_other_group(&c).unwrap();
+        assert_eq!((group.stack_ids.len(), group.total_count), (3, 12));
+        view.select_id(&get_id(&view, "main;b"));
+        view.to_next_sibling();
+        assert_eq!(view.state.selected, c);
+        view.to_next_sibling();
+        assert_eq!(view.state.selected, c);
+
+        assert!(view.expand_selected_other_group());
+        view.to_next_sibling();
+        assert_eq!(view.state.selected, get_id(&view, "main;d"));
+        assert!(!view.expand_selected_other_group());
+
+        view.toggle_sibling_grouping(DEFAULT_GROUP_TOP_K, DEFAULT_GROUP_MIN_SHARE);
+        assert!(view.state.sibling_grouping.is_none());
+    }
+
+    #[test]
+    fn test_sibling_grouping_across_value_columns() {
+        let content = ["main;a 50 1", "main;b 1 50", "main;c 1 1", "main;d;e 1 1"].join("\n");
+        let options = ParseOptions {
+            value_names: vec!["cpu".to_string(), "wall".to_string()],
+            ..ParseOptions::default()
+        };
+        let fg = FlameGraph::from_string_with_options(content, &options);
+        let mut view = FlameGraphView::new(fg);
+        view.state.frame_width = Some(100);
+        view.select_id(&get_id(&view, "main;d;e"));
+        view.toggle_sibling_grouping(DEFAULT_GROUP_TOP_K, 0.05);
+        let b = get_id(&view, "main;b");
+        assert_eq!(view.state.get_other_group(&b).unwrap().stack_ids.len(), 3);
+        assert_eq!(view.state.selected, get_id(&view, "main"));
+
+        // The groups follow the weights of the new value
+        view.to_next_value_column();
+        assert!(view.state.get_other_group(&b).is_none());
+        let a = get_id(&view, "main;a");
+        let group = view.state.get_other_group(&a).unwrap();
+        assert_eq!((group.stack_ids.len(), group.total_count), (3, 3));
+        assert!(view.state.is_grouped_away(&get_id(&view, "main;d;e")));
+        assert!(!view.state.is_grouped_away(&b));
+    }
+
+    #[test]
+    fn test_prune_to_selected() {
+        let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
+        let fg = FlameGraph::from_string(content, false);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "level1-a;level2-b"));
+        view.toggle_prune_to_selected();
+        assert!(view.state.prune_threshold.is_some());
+
+        // Frames narrower than the selected one are skipped when navigating
+        view.to_previous_sibling();
+        assert_eq!(get_selected_short_name(&view), "level2-b");
+        view.to_next_sibling();
+        assert_eq!(get_selected_short_name(&view), "level2-e");
+
+        view.toggle_prune_to_selected();
+        assert_eq!(view.state.prune_threshold, None);
+        view.to_previous_sibling();
+        assert_eq!(get_selected_short_name(&view), "level2-d");
+    }
+
+    #[test]
+    fn test_spotlight_path() {
+        let content = "main;parse;alloc 30\nmain;render 10\nother 40\nother;render 5\n";
+        let fg = FlameGraph::from_string(content.to_string(), true);
+        let mut view = FlameGraphView::new(fg);
+        view.select_id(&get_id(&view, "main;parse"));
+        assert!(view.get_spotlight_path().is_none());
+
+        view.state.toggle_spotlight();
+        let on_path = view.get_spotlight_path().unwrap();
+        let is_off = |view: &FlameGraphView, full_name: &str| {
+            let stack = view.flamegraph.get_stack_by_full_name(full_name).unwrap();
+            view.is_off_spotlight(&on_path, stack)
+        };
+        assert!(!is_off(&view, "main"));
+        assert!(!is_off(&view, "main;parse"));
+        assert!(!is_off(&view, "main;parse;alloc"));
+        assert!(is_off(&view, "main;render"));
+        assert!(is_off(&view, "other"));
+
+        // Search hits and highlights off the path stay bright
+        view.set_search_pattern(SearchPattern::new("render", false, true).unwrap());
+        assert!(!is_off(&view, "main;render"));
+        assert!(!is_off(&view, "other;render"));
+        assert!(is_off(&view, "other"));
+        view.unset_search_pattern();
+        view.add_highlight_pattern(SearchPattern::new("other", false, true).unwrap());
+        assert!(!is_off(&view, "other"));
+        assert!(is_off(&view, "main;render"));
+    }
+
     #[test]
     fn test_get_next_and_previous_search_result() {
         let content = std::fs::read_to_string("tests/data/readable.txt").unwrap();
@@ -603,4 +1598,29 @@ mod tests {
         view.to_previous_search_result();
         assert_eq!(get_selected_short_name(&view), "level1-b");
     }
+
+    #[test]
+    fn test_scroll_to_minimap_position() {
+        let stack = (0..20)
+            .map(|i| format!("f{}", i))
+            .collect::<Vec<_>>()
+            .join(";");
+        let fg = FlameGraph::from_string(format!("{} 1\n", stack), false);
+        let mut view = FlameGraphView::new(fg);
+        view.set_frame_height(4);
+        view.state.minimap_area = Some(ScreenArea {
+            x: 10,
+            y: 2,
+            width: 3,
+            height: 10,
+        });
+
+        // Outside of the strip
+        view.scroll_to_minimap_position(9, 7);
+        view.scroll_to_minimap_position(11, 12);
+        assert_eq!(view.state.level_offset, 0);
+
+        view.scroll_to_minimap_position(11, 7);
+        assert_eq!(view.state.level_offset, 8);
+    }
 }